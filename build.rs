@@ -1,12 +1,12 @@
-#[cfg(not(feature = "performance-counter"))]
+#[cfg(not(any(feature = "performance-counter", feature = "no-phf")))]
 fn main() {}
 
-#[cfg(feature = "performance-counter")]
+#[cfg(any(feature = "performance-counter", feature = "no-phf"))]
 fn main() {
     performance_counter::main();
 }
 
-#[cfg(feature = "performance-counter")]
+#[cfg(any(feature = "performance-counter", feature = "no-phf"))]
 mod performance_counter {
 
     use std::collections::HashMap;
@@ -22,6 +22,13 @@ mod performance_counter {
         "/src/perfcnt/intel/description.rs"
     ));
 
+    mod amd {
+        include!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/perfcnt/amd/description.rs"
+        ));
+    }
+
     /// We need to convert parsed strings to static because we're reusing
     /// the struct definition which declare strings as static in the generated
     /// code.
@@ -41,9 +48,12 @@ mod performance_counter {
         }
     }
 
+    /// Parses a list of hex numbers, empty entries are skipped.
     fn parse_hex_numbers(split_str_parts: Vec<&str>) -> Vec<u64> {
         split_str_parts
             .iter()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
             .map(|x| {
                 assert!(x.starts_with("0x"));
                 match u64::from_str_radix(&x[2..], 16) {
@@ -54,8 +64,13 @@ mod performance_counter {
             .collect()
     }
 
+    /// Parses a hex (0x prefixed) or decimal number,
+    /// an empty string is treated as 0.
     fn parse_number(value_str: &str) -> u64 {
-        if value_str.len() > 2 && value_str[..2].starts_with("0x") {
+        let value_str = value_str.trim();
+        if value_str.is_empty() {
+            0
+        } else if value_str.len() > 2 && value_str[..2].starts_with("0x") {
             match u64::from_str_radix(&value_str[2..], 16) {
                 Ok(u) => u,
                 Err(e) => panic!("{}: Can not parse {}", e, value_str),
@@ -68,6 +83,25 @@ mod performance_counter {
         }
     }
 
+    /// Parses a comma separated list of (up to two) MSR indexes,
+    /// "0" or an empty string means no MSR is used.
+    fn parse_msr_index(value_str: &str) -> MSRIndex {
+        let split_parts: Vec<u64> = value_str
+            .split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(parse_number)
+            .collect();
+
+        match split_parts.len() {
+            0 => MSRIndex::None,
+            1 if split_parts[0] == 0 => MSRIndex::None,
+            1 => MSRIndex::One(split_parts[0]),
+            2 => MSRIndex::Two(split_parts[0], split_parts[1]),
+            _ => panic!("More than two MSR indexes in {}?", value_str),
+        }
+    }
+
     fn parse_counter_values(value_str: &str) -> u64 {
         value_str
             .split(',')
@@ -110,6 +144,63 @@ mod performance_counter {
         }
     }
 
+    /// Parses EventCode and UMask values, returns `None` for an empty field.
+    fn parse_tuple(split_str_parts: Vec<&str>) -> Option<Tuple> {
+        let split_parts: Vec<u64> = parse_hex_numbers(split_str_parts);
+        match split_parts.len() {
+            0 => None,
+            1 => {
+                assert!(split_parts[0] <= u8::max_value() as u64);
+                Some(Tuple::One(split_parts[0] as u8))
+            }
+            2 => {
+                assert!(split_parts[0] <= u8::max_value() as u64);
+                assert!(split_parts[1] <= u8::max_value() as u64);
+                Some(Tuple::Two(split_parts[0] as u8, split_parts[1] as u8))
+            }
+            _ => panic!("More than two event codes?"),
+        }
+    }
+
+    /// Returns `None` for units we don't know about (yet).
+    fn parse_uncore_unit(value_str: &str) -> Option<UncoreUnit> {
+        Some(match value_str.trim() {
+            "CBO" => UncoreUnit::Cbo,
+            "CHA" => UncoreUnit::Cha,
+            "HA" => UncoreUnit::Ha,
+            "iMC" => UncoreUnit::Imc,
+            "iMC_DCLK" => UncoreUnit::ImcDclk,
+            "iMC_UCLK" => UncoreUnit::ImcUclk,
+            "EDC_ECLK" => UncoreUnit::EdcEclk,
+            "EDC_UCLK" => UncoreUnit::EdcUclk,
+            "IIO" => UncoreUnit::Iio,
+            "IRP" => UncoreUnit::Irp,
+            "M2M" => UncoreUnit::M2m,
+            "M2PCIe" => UncoreUnit::M2Pcie,
+            "M3UPI" => UncoreUnit::M3Upi,
+            "PCU" => UncoreUnit::Pcu,
+            "UBOX" => UncoreUnit::Ubox,
+            "UPI LL" => UncoreUnit::UpiLl,
+            "QPI LL" => UncoreUnit::QpiLl,
+            "R2PCIe" => UncoreUnit::R2Pcie,
+            "R3QPI" => UncoreUnit::R3Qpi,
+            "SBO" => UncoreUnit::Sbo,
+            "ARB" => UncoreUnit::Arb,
+            "NCU" => UncoreUnit::Ncu,
+            "iMPH-U" => UncoreUnit::ImphU,
+            _ => return None,
+        })
+    }
+
+    /// Returns `None` for counter types we don't know about (yet).
+    fn parse_counter_type(value_str: &str) -> Option<UncoreCounterType> {
+        match value_str {
+            "PGMABLE" => Some(UncoreCounterType::Programmable),
+            "FIXED" => Some(UncoreCounterType::Fixed),
+            _ => None,
+        }
+    }
+
     fn parse_pebs(value_str: &str) -> PebsType {
         match value_str.trim() {
             "0" => PebsType::Regular,
@@ -119,7 +210,14 @@ mod performance_counter {
         }
     }
 
-    fn parse_performance_counters(inputs: Vec<String>, variable: &str, file: &mut BufWriter<File>) {
+    /// Parses the event files in `inputs` into the table(s) `variable`, every
+    /// generated static is prefixed with `attributes` (e.g., `#[cfg(test)]`).
+    fn parse_performance_counters(
+        inputs: Vec<String>,
+        variable: &str,
+        attributes: &str,
+        file: &mut BufWriter<File>,
+    ) {
         let mut builder_values = HashMap::new();
         let mut all_events = HashMap::new();
         let mut builder = phf_codegen::Map::new();
@@ -127,10 +225,10 @@ mod performance_counter {
         for input in inputs {
             println!("input = {}", input);
 
-            let f = File::open(format!("x86data/perfmon_data{}", input.clone()).as_str()).unwrap();
+            let f = File::open(input.as_str()).unwrap();
             let reader = BufReader::new(f);
             let data: Value = serde_json::from_reader(reader).unwrap();
-            let uncore = get_file_suffix(input.clone()) == "uncore";
+            let uncore = get_file_suffix(input.clone()) == Some("uncore");
 
             if data.is_array() {
                 let entries = data.as_array().unwrap();
@@ -140,8 +238,8 @@ mod performance_counter {
                     }
                     let pcn = entry.as_object().unwrap();
 
-                    let mut event_code = Tuple::One(0);
-                    let mut umask = Tuple::One(0);
+                    let mut event_code = None;
+                    let mut umask = None;
                     let mut event_name = "";
                     let mut brief_description = "";
                     let mut public_description = None;
@@ -159,6 +257,7 @@ mod performance_counter {
                     let mut pebs = PebsType::Regular;
                     let mut precise_store = false;
                     let mut data_la = false;
+                    let mut pdir_counter = None;
                     let mut l1_hit_indication = false;
                     let mut errata = None;
                     let mut offcore = false;
@@ -172,6 +271,7 @@ mod performance_counter {
                     let mut filter_value: u64 = 0;
                     let mut port_mask: u8 = 0;
                     let mut umask_ext: u8 = 0;
+                    let mut counter_type = None;
 
                     let mut do_insert: bool = false;
 
@@ -198,38 +298,8 @@ mod performance_counter {
                                 }
                                 event_name = value_str;
                             }
-                            "EventCode" => {
-                                let split_parts: Vec<u64> = parse_hex_numbers(split_str_parts);
-                                match split_parts.len() {
-                                    1 => {
-                                        assert!(split_parts[0] <= u8::max_value() as u64);
-                                        event_code = Tuple::One(split_parts[0] as u8)
-                                    }
-                                    2 => {
-                                        assert!(split_parts[0] <= u8::max_value() as u64);
-                                        assert!(split_parts[1] <= u8::max_value() as u64);
-                                        event_code =
-                                            Tuple::Two(split_parts[0] as u8, split_parts[1] as u8)
-                                    }
-                                    _ => panic!("More than two event codes?"),
-                                }
-                            }
-                            "UMask" => {
-                                let split_parts: Vec<u64> = parse_hex_numbers(split_str_parts);
-                                match split_parts.len() {
-                                    1 => {
-                                        assert!(split_parts[0] <= u8::max_value() as u64);
-                                        umask = Tuple::One(split_parts[0] as u8)
-                                    }
-                                    2 => {
-                                        assert!(split_parts[0] <= u8::max_value() as u64);
-                                        assert!(split_parts[1] <= u8::max_value() as u64);
-                                        umask =
-                                            Tuple::Two(split_parts[0] as u8, split_parts[1] as u8)
-                                    }
-                                    _ => panic!("More than two event codes?"),
-                                }
-                            }
+                            "EventCode" => event_code = parse_tuple(split_str_parts),
+                            "UMask" => umask = parse_tuple(split_str_parts),
                             "BriefDescription" => brief_description = value_str,
                             "PublicDescription" => {
                                 if brief_description != value_str && value_str != "tbd" {
@@ -242,26 +312,7 @@ mod performance_counter {
                             "CounterHTOff" => counter_ht_off = Some(parse_counters(value_str)),
                             "PEBScounters" => pebs_counters = Some(parse_counters(value_str)),
                             "SampleAfterValue" => sample_after_value = parse_number(value_str),
-                            "MSRIndex" => {
-                                let split_parts: Vec<u64> = value_str
-                                    .split(",")
-                                    .map(|x| x.trim())
-                                    .map(|x| parse_number(x))
-                                    .collect();
-                                println!("{:?}", split_parts);
-
-                                msr_index = match split_parts.len() {
-                                    1 => {
-                                        if split_parts[0] != 0 {
-                                            MSRIndex::One(split_parts[0])
-                                        } else {
-                                            MSRIndex::None
-                                        }
-                                    }
-                                    2 => MSRIndex::Two(split_parts[0], split_parts[1]),
-                                    _ => panic!("More than two MSR indexes?"),
-                                }
-                            }
+                            "MSRIndex" => msr_index = parse_msr_index(value_str),
                             "MSRValue" => msr_value = parse_number(value_str),
                             "TakenAlone" => taken_alone = parse_bool(value_str),
                             "CounterMask" => counter_mask = parse_number(value_str) as u8,
@@ -282,7 +333,10 @@ mod performance_counter {
                             }
                             "ELLC" => { /* Ignored due to missing documentation. */ }
                             "EVENT_STATUS" => event_status = parse_number(value_str),
-                            "PDIR_COUNTER" => { /* Ignored */ }
+                            "PDIR_COUNTER" => {
+                                pdir_counter = parse_null_string(value_str).filter(|c| *c != "na")
+                            }
+                            "CounterType" => counter_type = Some(value_str),
                             "Deprecated" => deprecated = parse_bool(value_str),
                             "FCMask" => fc_mask = parse_number(value_str) as u8,
                             "FILTER_VALUE" => filter_value = parse_number(value_str),
@@ -292,6 +346,52 @@ mod performance_counter {
                         };
                     }
 
+                    let uncore_spec = if uncore {
+                        let unit_name = unit.expect("Uncore event without unit");
+                        let unit = match parse_uncore_unit(unit_name) {
+                            Some(unit) => unit,
+                            None => {
+                                println!(
+                                    "cargo:warning=Skipping {} ({}): unknown uncore unit {}",
+                                    event_name, input, unit_name
+                                );
+                                if do_insert {
+                                    all_events.remove(event_name);
+                                }
+                                continue;
+                            }
+                        };
+                        let counter_type = match counter_type {
+                            // Older event files don't have a CounterType field:
+                            None => match counter {
+                                Counter::Fixed(_) => UncoreCounterType::Fixed,
+                                Counter::Programmable(_) => UncoreCounterType::Programmable,
+                            },
+                            Some(value) => match parse_counter_type(value) {
+                                Some(counter_type) => counter_type,
+                                None => {
+                                    println!(
+                                        "cargo:warning=Skipping {} ({}): unknown uncore counter type {}",
+                                        event_name, input, value
+                                    );
+                                    if do_insert {
+                                        all_events.remove(event_name);
+                                    }
+                                    continue;
+                                }
+                            },
+                        };
+                        Some(UncoreSpec {
+                            unit,
+                            fc_mask,
+                            port_mask,
+                            umask_ext,
+                            counter_type,
+                        })
+                    } else {
+                        None
+                    };
+
                     let ipcd = EventDescription::new(
                         event_code,
                         umask,
@@ -313,6 +413,7 @@ mod performance_counter {
                         precise_store,
                         collect_pebs_record,
                         data_la,
+                        pdir_counter,
                         l1_hit_indication,
                         errata,
                         offcore,
@@ -322,14 +423,15 @@ mod performance_counter {
                         uncore,
                         deprecated,
                         event_status,
-                        fc_mask,
                         filter_value,
-                        port_mask,
-                        umask_ext,
+                        uncore_spec,
                     );
 
                     //println!("{:?}", ipcd.event_name);
-                    if do_insert {
+                    if do_insert && ipcd.deprecated && cfg!(feature = "skip-deprecated") {
+                        // A later file may still have a current version of the event:
+                        all_events.remove(ipcd.event_name);
+                    } else if do_insert {
                         builder_values.insert(String::from(ipcd.event_name), format!("{:?}", ipcd));
                     }
                 }
@@ -338,30 +440,170 @@ mod performance_counter {
             }
         }
 
-        write!(
+        if cfg!(feature = "performance-counter") {
+            write!(
+                file,
+                "{}pub static {}: phf::Map<&'static str, EventDescription<'static>> = ",
+                attributes, variable
+            )
+            .unwrap();
+
+            for (key, val) in builder_values.iter() {
+                // entry needs &'static str so we leak a bunch of Strings
+                builder.entry(string_to_static_str(key), string_to_static_str(val));
+            }
+            writeln!(file, "{};", builder.build()).unwrap();
+        }
+
+        if cfg!(feature = "no-phf") {
+            // The same events as a plain slice (sorted by name) for `events_linear`:
+            let mut events: Vec<_> = builder_values.iter().collect();
+            events.sort();
+            write!(
+                file,
+                "{}pub static {}_EVENTS: &[(&str, EventDescription<'static>)] = &[",
+                attributes, variable
+            )
+            .unwrap();
+            for (key, val) in events {
+                write!(file, "({:?}, {}),", key, val).unwrap();
+            }
+            writeln!(file, "];").unwrap();
+        }
+        file.flush().ok();
+    }
+
+    /// Parses a FP_ARITH_INST file (the FLOPs per unit mask bit of the
+    /// FP_ARITH_INST_RETIRED events) into a `[FpArithMetric]` constant.
+    fn parse_fp_arith_metrics(input: String, variable: &str, file: &mut BufWriter<File>) {
+        let f = File::open(input.as_str()).unwrap();
+        let reader = BufReader::new(f);
+        let data: Value = serde_json::from_reader(reader).unwrap();
+        let entries = data.as_array().expect("JSON data is not an array.");
+
+        let mut metrics: Vec<FpArithMetric> = Vec::new();
+        for entry in entries.iter() {
+            let pcn = entry.as_object().expect("Expected JSON object.");
+            let field = |key| str_to_static_str(pcn[key].as_str().unwrap_or("")).trim();
+
+            let metric = FpArithMetric {
+                bit_name: field("BitName"),
+                bit_index: parse_number(field("BitIndex")) as u8,
+                flops_multiplier: parse_number(field("FlopsMultiplier")) as u8,
+                description: field("Description"),
+            };
+            // Some files list (a subset of) the bits twice:
+            if metrics.iter().all(|m| m.bit_index != metric.bit_index) {
+                metrics.push(metric);
+            }
+        }
+
+        writeln!(
             file,
-            "pub const {}: phf::Map<&'static str, EventDescription<'static>> = ",
-            variable
+            "pub const {}: &[FpArithMetric<'static>] = &{:?};",
+            variable, metrics
         )
         .unwrap();
+    }
+
+    /// Parses an AMD event file, the format is a subset of the Intel one
+    /// (EventName, EventCode, UMask and BriefDescription).
+    fn parse_amd_performance_counters(input: String, variable: &str, file: &mut BufWriter<File>) {
+        let mut builder = phf_codegen::Map::new();
+
+        let f = File::open(input.as_str()).unwrap();
+        let reader = BufReader::new(f);
+        let data: Value = serde_json::from_reader(reader).unwrap();
+        let entries = data.as_array().expect("JSON data is not an array.");
+
+        for entry in entries.iter() {
+            let pcn = entry.as_object().expect("Expected JSON object.");
+
+            let mut event_code = 0;
+            let mut umask = 0;
+            let mut event_name = "";
+            let mut brief_description = "";
+
+            for (key, value) in pcn.iter() {
+                let value_str = str_to_static_str(value.as_str().unwrap_or("unknown")).trim();
+                match key.as_str() {
+                    "EventName" => event_name = value_str,
+                    "EventCode" => event_code = parse_number(value_str),
+                    "UMask" => umask = parse_number(value_str),
+                    "BriefDescription" => brief_description = value_str,
+                    _ => panic!("Unknown member: {} in file {}", key, input),
+                };
+            }
 
-        for (key, val) in builder_values.iter() {
-            // entry needs &'static str so we leak a bunch of Strings
-            builder.entry(string_to_static_str(key), string_to_static_str(val));
+            assert!(event_code <= 0xfff, "Event select is 12 bits");
+            assert!(umask <= u8::max_value() as u64);
+            let ed = amd::EventDescription::new(
+                event_code as u16,
+                umask as u8,
+                event_name,
+                brief_description,
+            );
+            builder.entry(event_name, string_to_static_str(&format!("{:?}", ed)));
         }
+
+        write!(
+            file,
+            "pub static {}: phf::Map<&'static str, EventDescription<'static>> = ",
+            variable
+        )
+        .unwrap();
         writeln!(file, "{};", builder.build()).unwrap();
         file.flush().ok();
     }
 
+    /// Generates `amd_counters.rs` from `x86data/amd/mapfile.csv`.
+    fn amd_main() {
+        let mut rdr = csv::Reader::from_path("./x86data/amd/mapfile.csv").unwrap();
+        let path = Path::new(&env::var("OUT_DIR").unwrap()).join("amd_counters.rs");
+        let mut filewriter = BufWriter::new(File::create(&path).unwrap());
+
+        let mut builder = phf_codegen::Map::new();
+        let mut files: HashMap<String, String> = HashMap::new();
+        for record in rdr.records().map(|v| v.unwrap()) {
+            let family_model = str_to_static_str(record.get(0).unwrap());
+            let file_name = record.get(2).unwrap().to_string();
+            assert_eq!(get_file_suffix(file_name.clone()), Some("core"));
+
+            let (_, variable_upper) = make_file_name(Path::new(file_name.as_str()));
+            // Hashes things like this: AuthenticAMD-17-31 -> &ZEN2
+            builder.entry(
+                family_model,
+                string_to_static_str(&format!("&{}", variable_upper)),
+            );
+            files.insert(variable_upper, file_name);
+        }
+
+        writeln!(
+            &mut filewriter,
+            "pub static COUNTER_MAP: phf::Map<&'static str, &'static phf::Map<&'static str, \
+             EventDescription<'static>>> = {};",
+            builder.build()
+        )
+        .unwrap();
+
+        for (arch, file) in files {
+            parse_amd_performance_counters(
+                format!("x86data/amd{}", file),
+                arch.as_str(),
+                &mut filewriter,
+            );
+        }
+    }
+
     fn make_file_name<'a>(path: &'a Path) -> (String, String) {
         let stem = path.file_stem().unwrap().to_str().unwrap();
 
         // File name without _core*.json
         println!("{:?}", path);
-        let mut core_start = stem.find("_core");
-        if core_start.is_none() {
-            core_start = stem.find("_uncore");
-        }
+        let core_start = stem
+            .find("_core")
+            .or_else(|| stem.find("_uncore"))
+            .or_else(|| stem.find("_fp_arith_inst"));
         assert!(!core_start.is_none());
         let (output_file, _) = stem.split_at(core_start.unwrap());
 
@@ -374,63 +616,124 @@ mod performance_counter {
         (output_file.to_string(), variable_upper.to_string())
     }
 
-    pub fn get_file_suffix(file_name: String) -> &'static str {
+    /// Returns the kind of data in an event file, `None` if we don't know it.
+    pub fn get_file_suffix(file_name: String) -> Option<&'static str> {
         if file_name.contains("_core_") {
-            "core"
+            Some("core")
         } else if file_name.contains("_uncore_") {
-            "uncore"
+            Some("uncore")
         } else if file_name.contains("_matrix_") {
-            "matrix"
+            Some("matrix")
         } else if file_name.contains("_FP_ARITH_INST_") || file_name.contains("_fp_arith_inst_") {
-            "fparith"
+            Some("fparith")
         } else {
-            panic!("Unknown suffix {}", file_name);
+            None
         }
     }
 
-    pub fn main() {
-        //println!("cargo:rerun-if-changed=build.rs");
-        //println!("cargo:rerun-if-changed=x86data/perfmon_data");
+    /// The event files listed in a mapfile.
+    #[derive(Default)]
+    struct MapFile {
+        /// Core and uncore event files along with (family-model, version, type) rows.
+        data_files: HashMap<String, Vec<(String, String, String)>>,
+        /// FP_ARITH_INST files and the family-models they apply to.
+        fparith_files: HashMap<String, Vec<String>>,
+    }
 
-        // First, parse mapfile.csv to find out all supported architectures and their event description locations
-        let mut rdr = csv::Reader::from_path("./x86data/perfmon_data/mapfile.csv").unwrap();
-        let mut data_files = HashMap::new();
+    fn parse_mapfile(path: &str) -> MapFile {
+        let mut rdr = csv::Reader::from_path(path).unwrap();
+        let mut mapfile: MapFile = Default::default();
 
         for record in rdr.records().map(|v| v.unwrap()) {
             let family_model = record.get(0).unwrap().to_string();
             let version = record.get(1).unwrap().to_string();
             let file_name = record.get(2).unwrap().to_string();
             let event_type = record.get(3).unwrap().to_string();
-            // TODO: Parse offcore counter descriptions.
 
-            let suffix = get_file_suffix(file_name.clone());
-            if suffix == "core" || suffix == "uncore" {
-                if !data_files.contains_key(&file_name) {
-                    data_files.insert(file_name.clone(), vec![(family_model, version, event_type)]);
-                } else {
-                    data_files.get_mut(&file_name).unwrap().push((
-                        family_model,
-                        version,
-                        event_type,
-                    ));
+            match get_file_suffix(file_name.clone()) {
+                Some("core") | Some("uncore") => mapfile
+                    .data_files
+                    .entry(file_name)
+                    .or_default()
+                    .push((family_model, version, event_type)),
+                Some("fparith") => mapfile
+                    .fparith_files
+                    .entry(file_name)
+                    .or_default()
+                    .push(family_model),
+                // Note: The offcore entries in the mapfile point to the matrix files
+                // which only describe the bits of the offcore response MSRs. The offcore
+                // events themselves (along with MSRIndex and MSRValue) are part of the core
+                // files, so they end up in the generated tables anyways.
+                Some("matrix") => {}
+                _ => println!(
+                    "cargo:warning=Skipping {} ({}): unknown event file type",
+                    file_name, event_type
+                ),
+            }
+        }
+
+        mapfile
+    }
+
+    /// Writes a map from family-model to the FP_ARITH_INST metrics (METRIC_MAP).
+    fn write_metric_map(fparith_files: &HashMap<String, Vec<String>>, file: &mut BufWriter<File>) {
+        let mut builder = phf_codegen::Map::new();
+        let mut inserted: HashMap<String, bool> = HashMap::new();
+        let mut files: Vec<_> = fparith_files.iter().collect();
+        files.sort();
+
+        for (fparith_file, family_models) in files {
+            let (_, variable_upper) = make_file_name(Path::new(fparith_file.as_str()));
+            let variable = string_to_static_str(&format!("{}_FP_ARITH_INST", variable_upper));
+            parse_fp_arith_metrics(
+                format!("x86data/perfmon_data{}", fparith_file),
+                variable,
+                file,
+            );
+
+            for family_model in family_models {
+                if inserted.insert(family_model.clone(), true).is_none() {
+                    builder.entry(string_to_static_str(family_model), variable);
                 }
             }
         }
 
+        writeln!(
+            file,
+            "pub static METRIC_MAP: phf::Map<&'static str, &'static [FpArithMetric<'static>]> = {};",
+            builder.build()
+        )
+        .unwrap();
+    }
+
+    pub fn main() {
+        //println!("cargo:rerun-if-changed=build.rs");
+        //println!("cargo:rerun-if-changed=x86data/perfmon_data");
+
+        // First, parse mapfile.csv to find out all supported architectures and their event description locations
+        let MapFile {
+            data_files,
+            fparith_files,
+        } = parse_mapfile("./x86data/perfmon_data/mapfile.csv");
+
         // Now build hash-table so we can later select performance counters for each architecture
         let path = Path::new(&env::var("OUT_DIR").unwrap()).join("counters.rs");
         let mut filewriter = BufWriter::new(File::create(&path).unwrap());
 
+        // Each architecture table is generated once, models sharing the same
+        // data files refer to the same static:
         let mut builder = phf_codegen::Map::new();
         let mut inserted: HashMap<String, bool> = HashMap::new();
         for (file, values) in &data_files {
             let path = Path::new(file.as_str());
             let (_, ref variable_upper) = make_file_name(&path);
+            let table = string_to_static_str(&format!("&{}", variable_upper));
             for data in values {
                 let (ref family_model, _, _): (String, String, String) = *data;
                 if !inserted.contains_key(&family_model.to_string()) {
-                    // Hashes things like this: GenuineIntel-6-25 -> WESTMERE_EP_SP
-                    builder.entry(family_model.as_str(), variable_upper.as_str());
+                    // Hashes things like this: GenuineIntel-6-25 -> &WESTMERE_EP_SP
+                    builder.entry(family_model.as_str(), table);
                     inserted.insert(family_model.clone(), true);
                 } else {
                     // ignore
@@ -439,13 +742,15 @@ mod performance_counter {
         }
 
         // Next, we write this hash-table (COUNTER_MAP) into our generated rust code file:
-        writeln!(
-            &mut filewriter,
-            "pub static COUNTER_MAP: phf::Map<&'static str, phf::Map<&'static str, \
-             EventDescription<'static>>> = {};",
-            builder.build()
-        )
-        .unwrap();
+        if cfg!(feature = "performance-counter") {
+            writeln!(
+                &mut filewriter,
+                "pub static COUNTER_MAP: phf::Map<&'static str, &'static phf::Map<&'static str, \
+                 EventDescription<'static>>> = {};",
+                builder.build()
+            )
+            .unwrap();
+        }
 
         // Now, parse all JSON files with event data for each architecture and generate hash-tables
         let mut architectures: HashMap<String, Vec<String>> = HashMap::new();
@@ -460,9 +765,66 @@ mod performance_counter {
                 .push(file.clone());
         }
 
-        for (ref arch, ref files) in architectures {
+        let mut names: Vec<&String> = architectures.keys().collect();
+        names.sort();
+        writeln!(
+            &mut filewriter,
+            "pub static ARCHITECTURES: &[&str] = &{:?};",
+            names
+        )
+        .unwrap();
+        if cfg!(feature = "no-phf") {
+            write!(
+                &mut filewriter,
+                "pub static LINEAR_TABLES: &[(&str, &[(&str, EventDescription<'static>)])] = &["
+            )
+            .unwrap();
+            for name in &names {
+                write!(&mut filewriter, "({:?}, {}_EVENTS),", name, name).unwrap();
+            }
+            writeln!(&mut filewriter, "];").unwrap();
+        }
+
+        for (ref arch, ref mut files) in architectures {
+            // Parse core files before uncore files so that, in case an event name
+            // shows up in both, we deterministically keep the core description:
+            files.sort_by_key(|f| (get_file_suffix(f.clone()) != Some("core"), f.clone()));
             println!("Processing {:?} {:?}", arch, files);
-            parse_performance_counters(files.to_vec(), arch, &mut filewriter);
+            let inputs = files
+                .iter()
+                .map(|f| format!("x86data/perfmon_data{}", f))
+                .collect();
+            parse_performance_counters(inputs, arch, "", &mut filewriter);
+        }
+
+        if cfg!(feature = "performance-counter") {
+            write_metric_map(&fparith_files, &mut filewriter);
+        }
+
+        // Finally, generate tables from our test fixtures which contain
+        // corner cases not (yet) present in the shipped perfmon data:
+        parse_performance_counters(
+            vec![String::from("tests/data/perfmon/fixture_uncore_v1.json")],
+            "TEST_FIXTURE",
+            "#[cfg(test)]\n",
+            &mut filewriter,
+        );
+
+        let fixture = parse_mapfile("tests/data/perfmon/fixture_mapfile.csv");
+        let mut data_files: Vec<_> = fixture.data_files.keys().collect();
+        let mut fparith_files: Vec<_> = fixture.fparith_files.keys().collect();
+        data_files.sort();
+        fparith_files.sort();
+        writeln!(&mut filewriter, "#[cfg(test)]").unwrap();
+        writeln!(
+            &mut filewriter,
+            "pub const TEST_MAPFILE: (&[&str], &[&str]) = (&{:?}, &{:?});",
+            data_files, fparith_files
+        )
+        .unwrap();
+
+        if cfg!(feature = "performance-counter") {
+            amd_main();
         }
     }
 }
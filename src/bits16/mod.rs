@@ -1,3 +1,4 @@
 //! Data structures and functions used by 16-bit mode.
 
+pub mod realmode;
 pub mod segmentation;
@@ -0,0 +1,103 @@
+//! Real-mode segment:offset (far) pointers.
+
+use core::fmt;
+
+/// The highest linear address reachable with a segment:offset pair
+/// (`0xFFFF:0xFFFF`), assuming the A20 line is enabled.
+pub const MAX_LINEAR: u32 = 0x10FFEF;
+
+/// A real-mode far pointer, as used by the BIOS and in IVT entries.
+///
+/// The linear address is `segment * 16 + offset`, so many pairs
+/// address the same byte.
+#[derive(Copy, Clone, Eq, PartialEq, Default)]
+#[repr(C)]
+pub struct RealModePtr {
+    /// Offset within the segment (stored first, as in the IVT).
+    pub offset: u16,
+    /// Segment (paragraph number).
+    pub segment: u16,
+}
+
+impl RealModePtr {
+    /// Create a far pointer from a segment and an offset.
+    pub const fn new(segment: u16, offset: u16) -> RealModePtr {
+        RealModePtr { offset, segment }
+    }
+
+    /// The linear address this pointer refers to.
+    ///
+    /// This does not wrap at 1 MiB: with A20 disabled the CPU would
+    /// access `linear() & 0xFFFFF` instead.
+    pub const fn linear(self) -> u32 {
+        ((self.segment as u32) << 4) + self.offset as u32
+    }
+
+    /// Pick a canonical segment:offset pair for a linear address.
+    ///
+    /// The offset is kept below 16 whenever possible; addresses in the
+    /// high memory area (above `0xFFFF0`) use segment `0xFFFF`.
+    ///
+    /// # Panics
+    /// If `linear` is above [`MAX_LINEAR`].
+    pub fn from_linear(linear: u32) -> RealModePtr {
+        assert!(
+            linear <= MAX_LINEAR,
+            "Address is not reachable in real mode."
+        );
+        if linear >= 0xFFFF0 {
+            RealModePtr::new(0xFFFF, (linear - 0xFFFF0) as u16)
+        } else {
+            RealModePtr::new((linear >> 4) as u16, (linear & 0xf) as u16)
+        }
+    }
+}
+
+impl fmt::Debug for RealModePtr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#06x}:{:#06x}", self.segment, self.offset)
+    }
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn linear() {
+        assert_eq!(RealModePtr::new(0x07c0, 0x0000).linear(), 0x7c00);
+        assert_eq!(RealModePtr::new(0x0000, 0x7c00).linear(), 0x7c00);
+        assert_eq!(RealModePtr::new(0xb800, 0x0010).linear(), 0xb8010);
+        assert_eq!(RealModePtr::new(0xFFFF, 0xFFFF).linear(), 0x10FFEF);
+    }
+
+    #[test]
+    fn from_linear() {
+        assert_eq!(
+            RealModePtr::from_linear(0x7c00),
+            RealModePtr::new(0x07c0, 0)
+        );
+        assert_eq!(
+            RealModePtr::from_linear(0xb8013),
+            RealModePtr::new(0xb801, 3)
+        );
+        assert_eq!(
+            RealModePtr::from_linear(0xFFFEF),
+            RealModePtr::new(0xFFFE, 0xF)
+        );
+        assert_eq!(
+            RealModePtr::from_linear(0x10FFEF),
+            RealModePtr::new(0xFFFF, 0xFFFF)
+        );
+
+        for &linear in &[0u32, 0x1234, 0xFFFF0, 0x100000, MAX_LINEAR] {
+            assert_eq!(RealModePtr::from_linear(linear).linear(), linear);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_linear_unreachable() {
+        RealModePtr::from_linear(MAX_LINEAR + 1);
+    }
+}
@@ -110,3 +110,63 @@ pub unsafe fn clac() {
 pub unsafe fn stac() {
     asm!("stac");
 }
+
+/// Runs `f` with explicit supervisor-mode accesses to user-mode pages allowed.
+///
+/// With CR4.SMAP set, the kernel faults on accesses to user-mode pages
+/// unless EFLAGS.AC is set. This sets AC ([stac]) before calling `f` and
+/// clears it ([clac]) afterwards, also in case `f` panics.
+///
+/// # Safety
+/// Same as [stac] and [clac]: needs CPL 0 and SMAP support.
+pub unsafe fn allow_user_access<F: FnOnce() -> R, R>(f: F) -> R {
+    bracket(stac, clac, f)
+}
+
+/// Calls `exit` when dropped.
+struct Guard(unsafe fn());
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        unsafe { (self.0)() }
+    }
+}
+
+/// Runs `f` between `enter` and `exit`, `exit` runs even if `f` panics.
+unsafe fn bracket<F: FnOnce() -> R, R>(enter: unsafe fn(), exit: unsafe fn(), f: F) -> R {
+    enter();
+    let _guard = Guard(exit);
+    f()
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    // stac, clac need CPL 0, so check the guard with stand-ins.
+    static AC: AtomicBool = AtomicBool::new(false);
+
+    unsafe fn fake_stac() {
+        AC.store(true, Ordering::SeqCst);
+    }
+
+    unsafe fn fake_clac() {
+        AC.store(false, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn user_access_guard() {
+        extern crate std;
+
+        let value = unsafe { bracket(fake_stac, fake_clac, || AC.load(Ordering::SeqCst)) };
+        assert!(value, "AC not set while running the closure");
+        assert!(!AC.load(Ordering::SeqCst));
+
+        let result = std::panic::catch_unwind(|| unsafe {
+            bracket(fake_stac, fake_clac, || panic!("user access failed"))
+        });
+        assert!(result.is_err());
+        assert!(!AC.load(Ordering::SeqCst), "AC not cleared on panic");
+    }
+}
@@ -1,6 +1,7 @@
 //! Data structures and functions used by 32-bit mode.
 
 pub mod eflags;
+pub mod pae;
 pub mod paging;
 pub mod segmentation;
 pub mod task;
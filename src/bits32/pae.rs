@@ -0,0 +1,431 @@
+//! Description of the data-structures for IA-32 PAE paging mode.
+//!
+//! PAE paging translates 32-bit linear addresses to physical addresses of up
+//! to 52 bits using three levels of tables with 64-bit entries.
+//! See Intel 3a, Section 4.4 "PAE Paging".
+
+use bitflags::*;
+
+use core::fmt;
+
+pub use super::paging::VAddr;
+pub use crate::bits64::paging::PAddr;
+
+macro_rules! check_flag {
+    ($doc:meta, $fun:ident, $flag:expr) => {
+        #[$doc]
+        pub fn $fun(self) -> bool {
+            self.flags().contains($flag)
+        }
+    };
+}
+
+/// Size of a base page (4 KiB)
+pub const BASE_PAGE_SIZE: usize = 4096;
+
+/// Size of a large page (2 MiB)
+pub const LARGE_PAGE_SIZE: usize = 1024 * 1024 * 2;
+
+/// Mask to find the physical address of an entry in a page-table.
+const ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Mask to find the physical address of a 2 MiB page in a PD entry.
+const ADDRESS_MASK_LARGE: u64 = 0x000f_ffff_ffe0_0000;
+
+/// The PDPT has 4 entries.
+pub const PDPT_ENTRIES: usize = 4;
+
+/// Page directories and page tables have 512 = 4096 / 64 entries.
+pub const PAGE_SIZE_ENTRIES: usize = 512;
+
+/// A page directory pointer table.
+///
+/// Note that CR3 holds the address of the PDPT in PAE mode,
+/// which needs to be 32-byte aligned.
+pub type PDPT = [PDPTEntry; PDPT_ENTRIES];
+
+/// A page directory.
+pub type PD = [PDEntry; PAGE_SIZE_ENTRIES];
+
+/// A page table.
+pub type PT = [PTEntry; PAGE_SIZE_ENTRIES];
+
+/// Given virtual address calculate corresponding entry in PDPT.
+#[inline]
+pub fn pdpt_index(addr: VAddr) -> usize {
+    ((addr >> 30usize) & 0b11) as usize
+}
+
+/// Given virtual address calculate corresponding entry in PD.
+#[inline]
+pub fn pd_index(addr: VAddr) -> usize {
+    ((addr >> 21usize) & 0b111111111) as usize
+}
+
+/// Given virtual address calculate corresponding entry in PT.
+#[inline]
+pub fn pt_index(addr: VAddr) -> usize {
+    ((addr >> 12usize) & 0b111111111) as usize
+}
+
+bitflags! {
+    /// PDPT configuration bits description.
+    ///
+    /// Unlike in IA-32e mode, PAE PDPT entries don't have RW, US, A or XD bits.
+    #[repr(transparent)]
+    pub struct PDPTFlags: u64 {
+        /// Present; must be 1 to reference a page directory.
+        const P       = bit!(0);
+        /// Page-level write-through.
+        const PWT     = bit!(3);
+        /// Page-level cache disable.
+        const PCD     = bit!(4);
+    }
+}
+
+/// A PDPT Entry consists of an address and a bunch of flags.
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+pub struct PDPTEntry(pub u64);
+
+impl fmt::Debug for PDPTEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PDPTEntry {{ {:#x}, {:?} }}",
+            self.address(),
+            self.flags()
+        )
+    }
+}
+
+impl PDPTEntry {
+    /// Creates a new PDPTEntry.
+    ///
+    /// # Arguments
+    ///
+    ///  * `pd` - The physical address of the page directory.
+    ///  * `flags`- Additional flags for the entry.
+    pub fn new(pd: PAddr, flags: PDPTFlags) -> PDPTEntry {
+        let pd_val = pd & ADDRESS_MASK;
+        assert!(pd_val == pd.into());
+        assert!(pd % BASE_PAGE_SIZE == 0);
+        PDPTEntry(pd_val | flags.bits)
+    }
+
+    /// Retrieves the physical address in this entry.
+    pub fn address(self) -> PAddr {
+        PAddr::from(self.0 & ADDRESS_MASK)
+    }
+
+    /// Returns the flags corresponding to this entry.
+    pub fn flags(self) -> PDPTFlags {
+        PDPTFlags::from_bits_truncate(self.0)
+    }
+
+    check_flag!(
+        doc = "Present; must be 1 to reference a page directory.",
+        is_present,
+        PDPTFlags::P
+    );
+    check_flag!(
+        doc = "Page-level write-through.",
+        is_page_write_through,
+        PDPTFlags::PWT
+    );
+    check_flag!(
+        doc = "Page-level cache disable.",
+        is_page_level_cache_disabled,
+        PDPTFlags::PCD
+    );
+}
+
+bitflags! {
+    /// PD configuration bits description.
+    #[repr(transparent)]
+    pub struct PDFlags: u64 {
+        /// Present; must be 1 to map a 2-MByte page or reference a page table.
+        const P       = bit!(0);
+        /// Read/write; if 0, writes may not be allowed to the 2-MByte region controlled by this entry
+        const RW      = bit!(1);
+        /// User/supervisor; user-mode accesses are not allowed to the 2-MByte region controlled by this entry.
+        const US      = bit!(2);
+        /// Page-level write-through.
+        const PWT     = bit!(3);
+        /// Page-level cache disable.
+        const PCD     = bit!(4);
+        /// Accessed; indicates whether software has accessed the region controlled by this entry.
+        const A       = bit!(5);
+        /// Dirty; if PS indicates whether software has written to the 2-MByte page referenced by this entry.
+        const D       = bit!(6);
+        /// Page size; if set this entry maps a 2-MByte page; otherwise, this entry references a page table.
+        const PS      = bit!(7);
+        /// Global; if PS && CR4.PGE = 1, determines whether the translation is global; ignored otherwise.
+        const G       = bit!(8);
+        /// Indirectly determines the memory type used to access the 2-MByte page referenced by this entry.
+        /// if not PS this is ignored.
+        const PAT     = bit!(12);
+        /// If IA32_EFER.NXE = 1, execute-disable
+        /// If 1, instruction fetches are not allowed from the 2-MByte region.
+        const XD      = bit!(63);
+    }
+}
+
+/// A PD Entry consists of an address and a bunch of flags.
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+pub struct PDEntry(pub u64);
+
+impl fmt::Debug for PDEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PDEntry {{ {:#x}, {:?} }}", self.address(), self.flags())
+    }
+}
+
+impl PDEntry {
+    /// Creates a new PDEntry.
+    ///
+    /// # Arguments
+    ///
+    ///  * `pt` - The physical address of the page table.
+    ///  * `flags`- Additional flags for the entry.
+    pub fn new(pt: PAddr, flags: PDFlags) -> PDEntry {
+        let pt_val = pt & ADDRESS_MASK;
+        assert!(pt_val == pt.into());
+        assert!(pt % BASE_PAGE_SIZE == 0);
+        PDEntry(pt_val | flags.bits)
+    }
+
+    /// Creates a new PDEntry that maps a 2 MiB page (sets PS).
+    ///
+    /// # Arguments
+    ///
+    ///  * `page` - The physical address of the 2 MiB page, needs to be 2 MiB aligned.
+    ///  * `flags`- Additional flags for the entry.
+    pub fn new_large_page(page: PAddr, flags: PDFlags) -> PDEntry {
+        let page_val = page & ADDRESS_MASK_LARGE;
+        assert!(page_val == page.into(), "2 MiB page address not aligned");
+        PDEntry(page_val | (flags | PDFlags::PS).bits)
+    }
+
+    /// Retrieves the physical address in this entry.
+    pub fn address(self) -> PAddr {
+        if self.flags().contains(PDFlags::PS) {
+            PAddr::from(self.0 & ADDRESS_MASK_LARGE)
+        } else {
+            PAddr::from(self.0 & ADDRESS_MASK)
+        }
+    }
+
+    /// Returns the flags corresponding to this entry.
+    pub fn flags(self) -> PDFlags {
+        PDFlags::from_bits_truncate(self.0)
+    }
+
+    check_flag!(
+        doc = "Present; must be 1 to map a 2-MByte page or reference a page table.",
+        is_present,
+        PDFlags::P
+    );
+    check_flag!(doc = "Read/write; if 0, writes may not be allowed to the 2-MByte region controlled by this entry",
+                is_writeable, PDFlags::RW);
+    check_flag!(doc = "User/supervisor; user-mode accesses are not allowed to the 2-MByte region controlled by this entry.",
+                is_user_mode_allowed, PDFlags::US);
+    check_flag!(
+        doc = "Page-level write-through.",
+        is_page_write_through,
+        PDFlags::PWT
+    );
+    check_flag!(
+        doc = "Page-level cache disable.",
+        is_page_level_cache_disabled,
+        PDFlags::PCD
+    );
+    check_flag!(doc = "Accessed; indicates whether software has accessed the region controlled by this entry.",
+                is_accessed, PDFlags::A);
+    check_flag!(doc = "Dirty; if PS indicates whether software has written to the 2-MByte page referenced by this entry.",
+                is_dirty, PDFlags::D);
+    check_flag!(doc = "Page size; if set this entry maps a 2-MByte page; otherwise, this entry references a page table.",
+                is_page, PDFlags::PS);
+    check_flag!(doc = "Global; if PS && CR4.PGE = 1, determines whether the translation is global; ignored otherwise.",
+                is_global, PDFlags::G);
+    check_flag!(doc = "If IA32_EFER.NXE = 1, execute-disable. If 1, instruction fetches are not allowed from the 2-MByte region.",
+                is_instruction_fetching_disabled, PDFlags::XD);
+}
+
+bitflags! {
+    /// PT Entry bits description.
+    #[repr(transparent)]
+    pub struct PTFlags: u64 {
+        /// Present; must be 1 to map a 4-KByte page.
+        const P       = bit!(0);
+        /// Read/write; if 0, writes may not be allowed to the 4-KByte page referenced by this entry.
+        const RW      = bit!(1);
+        /// User/supervisor; if 0, user-mode accesses are not allowed to the 4-KByte page referenced by this entry.
+        const US      = bit!(2);
+        /// Page-level write-through.
+        const PWT     = bit!(3);
+        /// Page-level cache disable.
+        const PCD     = bit!(4);
+        /// Accessed; indicates whether software has accessed the 4-KByte page referenced by this entry.
+        const A       = bit!(5);
+        /// Dirty; indicates whether software has written to the 4-KByte page referenced by this entry.
+        const D       = bit!(6);
+        /// Indirectly determines the memory type used to access the 4-KByte page referenced by this entry.
+        const PAT     = bit!(7);
+        /// Global; if CR4.PGE = 1, determines whether the translation is global; ignored otherwise.
+        const G       = bit!(8);
+        /// If IA32_EFER.NXE = 1, execute-disable
+        /// If 1, instruction fetches are not allowed from the 4-KByte page.
+        const XD      = bit!(63);
+    }
+}
+
+/// A PT Entry consists of an address and a bunch of flags.
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+pub struct PTEntry(pub u64);
+
+impl fmt::Debug for PTEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PTEntry {{ {:#x}, {:?} }}", self.address(), self.flags())
+    }
+}
+
+impl PTEntry {
+    /// Creates a new PTEntry.
+    ///
+    /// # Arguments
+    ///
+    ///  * `page` - The physical address of the backing 4 KiB page.
+    ///  * `flags`- Additional flags for the entry.
+    pub fn new(page: PAddr, flags: PTFlags) -> PTEntry {
+        let page_val = page & ADDRESS_MASK;
+        assert!(page_val == page.into());
+        assert!(page % BASE_PAGE_SIZE == 0);
+        PTEntry(page_val | flags.bits)
+    }
+
+    /// Retrieves the physical address in this entry.
+    pub fn address(self) -> PAddr {
+        PAddr::from(self.0 & ADDRESS_MASK)
+    }
+
+    /// Returns the flags corresponding to this entry.
+    pub fn flags(self) -> PTFlags {
+        PTFlags::from_bits_truncate(self.0)
+    }
+
+    check_flag!(
+        doc = "Present; must be 1 to map a 4-KByte page.",
+        is_present,
+        PTFlags::P
+    );
+    check_flag!(doc = "Read/write; if 0, writes may not be allowed to the 4-KByte page referenced by this entry.",
+                is_writeable, PTFlags::RW);
+    check_flag!(doc = "User/supervisor; if 0, user-mode accesses are not allowed to the 4-KByte page referenced by this entry.",
+                is_user_mode_allowed, PTFlags::US);
+    check_flag!(
+        doc = "Page-level write-through.",
+        is_page_write_through,
+        PTFlags::PWT
+    );
+    check_flag!(
+        doc = "Page-level cache disable.",
+        is_page_level_cache_disabled,
+        PTFlags::PCD
+    );
+    check_flag!(doc = "Accessed; indicates whether software has accessed the 4-KByte page referenced by this entry.",
+                is_accessed, PTFlags::A);
+    check_flag!(doc = "Dirty; indicates whether software has written to the 4-KByte page referenced by this entry.",
+                is_dirty, PTFlags::D);
+    check_flag!(doc = "Indirectly determines the memory type used to access the 4-KByte page referenced by this entry.",
+                is_pat, PTFlags::PAT);
+    check_flag!(doc = "Global; if CR4.PGE = 1, determines whether the translation is global; ignored otherwise.",
+                is_global, PTFlags::G);
+    check_flag!(doc = "If IA32_EFER.NXE = 1, execute-disable. If 1, instruction fetches are not allowed from the 4-KByte page.",
+                is_instruction_fetching_disabled, PTFlags::XD);
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[repr(align(4096))]
+    struct Aligned<T>(T);
+
+    /// Walks the tables assuming they are identity mapped.
+    unsafe fn translate(pdpt: &PDPT, addr: VAddr) -> Option<PAddr> {
+        let pdpte = pdpt[pdpt_index(addr)];
+        if !pdpte.is_present() {
+            return None;
+        }
+        let pd = &*(pdpte.address().as_u64() as *const PD);
+        let pde = pd[pd_index(addr)];
+        if !pde.is_present() {
+            return None;
+        }
+        if pde.is_page() {
+            return Some(pde.address() + (addr.as_u32() & (LARGE_PAGE_SIZE as u32 - 1)) as u64);
+        }
+        let pt = &*(pde.address().as_u64() as *const PT);
+        let pte = pt[pt_index(addr)];
+        if !pte.is_present() {
+            return None;
+        }
+        Some(pte.address() + (addr.as_u32() & (BASE_PAGE_SIZE as u32 - 1)) as u64)
+    }
+
+    #[test]
+    fn indices() {
+        let addr = VAddr::from(0xc060_1234u32);
+        assert_eq!(pdpt_index(addr), 3);
+        assert_eq!(pd_index(addr), 3);
+        assert_eq!(pt_index(addr), 1);
+    }
+
+    #[test]
+    fn identity_map_4mib() {
+        // First 2 MiB with 4 KiB pages, the second with one large page:
+        let mut pt: Aligned<PT> = Aligned([PTEntry(0); PAGE_SIZE_ENTRIES]);
+        for (i, entry) in pt.0.iter_mut().enumerate() {
+            *entry = PTEntry::new(PAddr::from(i * BASE_PAGE_SIZE), PTFlags::P | PTFlags::RW);
+        }
+
+        let mut pd: Aligned<PD> = Aligned([PDEntry(0); PAGE_SIZE_ENTRIES]);
+        pd.0[0] = PDEntry::new(
+            PAddr::from(&pt.0 as *const PT as u64),
+            PDFlags::P | PDFlags::RW,
+        );
+        pd.0[1] = PDEntry::new_large_page(PAddr::from(LARGE_PAGE_SIZE), PDFlags::P | PDFlags::RW);
+
+        let mut pdpt: Aligned<PDPT> = Aligned([PDPTEntry(0); PDPT_ENTRIES]);
+        pdpt.0[0] = PDPTEntry::new(PAddr::from(&pd.0 as *const PD as u64), PDPTFlags::P);
+
+        assert!(pd.0[1].is_page());
+        assert_eq!(pd.0[1].address(), PAddr::from(0x20_0000u64));
+        assert_eq!(pd.0[1].0, 0x20_0083);
+
+        for addr in [0x0u32, 0x1234, 0x1f_ffff, 0x20_0000, 0x2a_bcde, 0x3f_ffff] {
+            let vaddr = VAddr::from(addr);
+            assert_eq!(
+                unsafe { translate(&pdpt.0, vaddr) },
+                Some(PAddr::from(addr as u64))
+            );
+        }
+        assert_eq!(
+            unsafe { translate(&pdpt.0, VAddr::from(0x40_0000u32)) },
+            None
+        );
+        assert_eq!(
+            unsafe { translate(&pdpt.0, VAddr::from(0x4000_0000u32)) },
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn large_page_misaligned() {
+        PDEntry::new_large_page(PAddr::from(0x1000u64), PDFlags::P);
+    }
+}
@@ -7,6 +7,8 @@ use core::fmt;
 use core::hash::{Hash, Hasher};
 use core::ops;
 
+use crate::bits64::paging::fmt_pointer;
+
 macro_rules! check_flag {
     ($doc:meta, $fun:ident, $flag:expr) => {
         #[$doc]
@@ -46,7 +48,7 @@ pub struct PAddr(pub u32);
 
 impl PAddr {
     /// Convert to `u32`
-    pub fn as_u32(self) -> u32 {
+    pub const fn as_u32(self) -> u32 {
         self.0
     }
 
@@ -339,8 +341,7 @@ impl fmt::UpperHex for PAddr {
 
 impl fmt::Pointer for PAddr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use core::fmt::LowerHex;
-        self.0.fmt(f)
+        fmt_pointer(self.0 as u64, f)
     }
 }
 
@@ -641,8 +642,7 @@ impl fmt::UpperHex for IOAddr {
 
 impl fmt::Pointer for IOAddr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use core::fmt::LowerHex;
-        self.0.fmt(f)
+        fmt_pointer(self.0 as u64, f)
     }
 }
 
@@ -794,6 +794,18 @@ impl From<usize> for VAddr {
     }
 }
 
+impl<T> From<*const T> for VAddr {
+    fn from(ptr: *const T) -> Self {
+        VAddr(ptr as usize as u32)
+    }
+}
+
+impl<T> From<*mut T> for VAddr {
+    fn from(ptr: *mut T) -> Self {
+        VAddr(ptr as usize as u32)
+    }
+}
+
 #[allow(clippy::clippy::from_over_into)]
 impl Into<usize> for VAddr {
     fn into(self) -> usize {
@@ -1009,8 +1021,7 @@ impl fmt::UpperHex for VAddr {
 
 impl fmt::Pointer for VAddr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use core::fmt::LowerHex;
-        self.0.fmt(f)
+        fmt_pointer(self.0 as u64, f)
     }
 }
 
@@ -1114,18 +1125,42 @@ impl PDEntry {
     /// # Implementation notes
     ///
     /// This doesn't support PSE-36 or PSE-40.
-    pub fn new(pt: PAddr, flags: PDFlags) -> PDEntry {
+    pub const fn new(pt: PAddr, flags: PDFlags) -> PDEntry {
         let mask = if flags.contains(PDFlags::PS) {
             ADDRESS_MASK_PSE
         } else {
             ADDRESS_MASK
         };
-        let pt_val = pt & mask;
-        assert!(pt_val == pt.into());
-        assert!(pt % BASE_PAGE_SIZE == 0);
+        let pt_val = pt.as_u32() & mask;
+        assert!(pt_val == pt.as_u32());
+        assert!(pt.as_u32() & (BASE_PAGE_SIZE as u32 - 1) == 0);
         PDEntry(pt_val | flags.bits)
     }
 
+    /// Creates a new PDEntry that references a page table (PS is cleared).
+    ///
+    /// # Arguments
+    ///
+    ///  * `pt` - The physical address of the page table, needs to be 4 KiB aligned.
+    ///  * `flags`- Additional flags for the entry.
+    pub const fn new_page_table(pt: PAddr, flags: PDFlags) -> PDEntry {
+        PDEntry::new(pt, flags.difference(PDFlags::PS))
+    }
+
+    /// Creates a new PDEntry that maps a 4 MiB page (sets PS, requires CR4.PSE).
+    ///
+    /// # Arguments
+    ///
+    ///  * `page` - The physical address of the page, needs to be 4 MiB aligned.
+    ///  * `flags`- Additional flags for the entry.
+    pub const fn new_4mib(page: PAddr, flags: PDFlags) -> PDEntry {
+        assert!(
+            page.as_u32() & (LARGE_PAGE_SIZE as u32 - 1) == 0,
+            "4 MiB page address not aligned"
+        );
+        PDEntry::new(page, flags.union(PDFlags::PS))
+    }
+
     /// Retrieves the physical address in this entry.
     pub fn address(self) -> PAddr {
         if self.flags().contains(PDFlags::PS) {
@@ -1215,10 +1250,10 @@ impl PTEntry {
     ///
     ///  * `page` - The physical address of the backing 4 KiB page.
     ///  * `flags`- Additional flags for the entry.
-    pub fn new(page: PAddr, flags: PTFlags) -> PTEntry {
-        let page_val = page & ADDRESS_MASK;
-        assert!(page_val == page.into());
-        assert!(page % BASE_PAGE_SIZE == 0);
+    pub const fn new(page: PAddr, flags: PTFlags) -> PTEntry {
+        let page_val = page.as_u32() & ADDRESS_MASK;
+        assert!(page_val == page.as_u32());
+        assert!(page.as_u32() & (BASE_PAGE_SIZE as u32 - 1) == 0);
         PTEntry(page_val | flags.bits)
     }
 
@@ -1265,6 +1300,50 @@ impl PTEntry {
 mod test {
     use super::*;
 
+    #[test]
+    fn pd_entry_constructors() {
+        let pt =
+            PDEntry::new_page_table(PAddr::from(0x1000), PDFlags::P | PDFlags::RW | PDFlags::PS);
+        assert!(!pt.is_page());
+        assert_eq!(pt.address(), PAddr::from(0x1000));
+        assert_eq!(pt.0, 0x1003);
+
+        let page = PDEntry::new_4mib(PAddr::from(0xc00000), PDFlags::P | PDFlags::RW);
+        assert!(page.is_page());
+        assert_eq!(page.address(), PAddr::from(0xc00000));
+        assert_eq!(page.0, 0xc00083);
+
+        // PAT moves to bit 12 for 4 MiB pages and doesn't end up in the address:
+        let pat = PDEntry::new_4mib(PAddr::from(0x400000), PDFlags::P | PDFlags::PAT);
+        assert_eq!(pat.address(), PAddr::from(0x400000));
+        assert!(pat.is_pat());
+    }
+
+    #[test]
+    fn static_entries() {
+        static PD_ENTRY: PDEntry =
+            PDEntry::new_4mib(PAddr(0xc0_0000), PDFlags::P.union(PDFlags::RW));
+        static PT_ENTRY: PTEntry = PTEntry::new(PAddr(0x1000), PTFlags::P);
+        assert_eq!(PD_ENTRY.0, 0xc00083);
+        assert_eq!(PT_ENTRY.0, 0x1001);
+    }
+
+    #[test]
+    fn address_formatting() {
+        extern crate std;
+        use std::format;
+
+        assert_eq!(format!("{:#x}", PAddr::from(0x1000)), "0x1000");
+        assert_eq!(format!("{:#010X}", VAddr::from(0xbeef)), "0x0000BEEF");
+        assert_eq!(format!("{:p}", VAddr::from(0xc000_0000u32)), "0xc0000000");
+    }
+
+    #[test]
+    #[should_panic]
+    fn pd_entry_4mib_misaligned() {
+        PDEntry::new_4mib(PAddr::from(0x200000), PDFlags::P);
+    }
+
     #[test]
     fn paddr_align() {
         let base = PAddr::from(0x1000);
@@ -9,6 +9,9 @@ use core::arch::asm;
 /// to %cs. Instead we push the new segment selector
 /// and return value on the stack and use lretl
 /// to reload cs and continue at 1:.
+///
+/// # Safety
+/// Can cause a GP-fault with a bad `sel` value.
 #[cfg(target_arch = "x86")]
 pub unsafe fn load_cs(sel: SegmentSelector) {
     asm!("pushl {0}; \
@@ -0,0 +1,139 @@
+//! Bit manipulation instructions (POPCNT, ABM/LZCNT, BMI1 and BMI2).
+//!
+//! The instructions raise #UD if the processor doesn't support them, check
+//! CPUID (e.g., with [features](crate::cpuid::features)) before using them.
+
+use core::arch::asm;
+
+/// popcnt -- Return the number of bits set in `value`.
+///
+/// # Safety
+/// Needs CPUID.01H:ECX.POPCNT\[bit 23\].
+#[inline(always)]
+pub unsafe fn popcnt(value: u64) -> u64 {
+    let count: u64;
+    asm!("popcnt {}, {}", out(reg) count, in(reg) value, options(pure, nomem, nostack));
+    count
+}
+
+/// lzcnt -- Return the number of leading zero bits in `value` (64 for 0).
+///
+/// # Safety
+/// Needs CPUID.80000001H:ECX.ABM\[bit 5\], on older processors the
+/// encoding is executed as `bsr` instead which gives different results.
+#[inline(always)]
+pub unsafe fn lzcnt(value: u64) -> u64 {
+    let count: u64;
+    asm!("lzcnt {}, {}", out(reg) count, in(reg) value, options(pure, nomem, nostack));
+    count
+}
+
+/// tzcnt -- Return the number of trailing zero bits in `value` (64 for 0).
+///
+/// # Safety
+/// Needs CPUID.(EAX=07H, ECX=0H):EBX.BMI1\[bit 3\], on older processors the
+/// encoding is executed as `bsf` instead which gives different results.
+#[inline(always)]
+pub unsafe fn tzcnt(value: u64) -> u64 {
+    let count: u64;
+    asm!("tzcnt {}, {}", out(reg) count, in(reg) value, options(pure, nomem, nostack));
+    count
+}
+
+/// bextr -- Extract `len` bits starting at bit `start` of `src`.
+///
+/// Bits beyond bit 63 are read as zero.
+///
+/// # Safety
+/// Needs CPUID.(EAX=07H, ECX=0H):EBX.BMI1\[bit 3\].
+#[inline(always)]
+pub unsafe fn bextr(src: u64, start: u8, len: u8) -> u64 {
+    let control = start as u64 | (len as u64) << 8;
+    let result: u64;
+    asm!("bextr {}, {}, {}", out(reg) result, in(reg) src, in(reg) control, options(pure, nomem, nostack));
+    result
+}
+
+/// pext -- Gather the bits of `src` selected by `mask` into the low bits of the result.
+///
+/// # Safety
+/// Needs CPUID.(EAX=07H, ECX=0H):EBX.BMI2\[bit 8\].
+#[inline(always)]
+pub unsafe fn pext(src: u64, mask: u64) -> u64 {
+    let result: u64;
+    asm!("pext {}, {}, {}", out(reg) result, in(reg) src, in(reg) mask, options(pure, nomem, nostack));
+    result
+}
+
+/// pdep -- Scatter the low bits of `src` to the bit positions selected by `mask`.
+///
+/// # Safety
+/// Needs CPUID.(EAX=07H, ECX=0H):EBX.BMI2\[bit 8\].
+#[inline(always)]
+pub unsafe fn pdep(src: u64, mask: u64) -> u64 {
+    let result: u64;
+    asm!("pdep {}, {}, {}", out(reg) result, in(reg) src, in(reg) mask, options(pure, nomem, nostack));
+    result
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+    use crate::cpuid::{features, CpuId, FeatureFlags};
+
+    /// A xorshift PRNG, good enough to generate test inputs.
+    fn inputs() -> impl Iterator<Item = u64> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let random = core::iter::repeat_with(move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        });
+        IntoIterator::into_iter([0, 1, 1 << 63, u64::max_value()]).chain(random.take(1000))
+    }
+
+    #[test]
+    fn count_bits() {
+        let has_lzcnt = CpuId::new()
+            .get_extended_processor_and_feature_identifiers()
+            .is_some_and(|info| info.has_lzcnt());
+        let features = features();
+
+        for value in inputs() {
+            unsafe {
+                if features.contains(FeatureFlags::POPCNT) {
+                    assert_eq!(popcnt(value), value.count_ones() as u64);
+                }
+                if has_lzcnt {
+                    assert_eq!(lzcnt(value), value.leading_zeros() as u64);
+                }
+                if features.contains(FeatureFlags::BMI1) {
+                    assert_eq!(tzcnt(value), value.trailing_zeros() as u64);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extract_and_deposit() {
+        let features = features();
+
+        for value in inputs() {
+            unsafe {
+                if features.contains(FeatureFlags::BMI1) {
+                    assert_eq!(bextr(value, 0, 64), value);
+                    assert_eq!(bextr(value, 12, 9), (value >> 12) & 0x1ff);
+                    assert_eq!(bextr(value, 60, 8), value >> 60);
+                    assert_eq!(bextr(value, 0, 0), 0);
+                }
+                if features.contains(FeatureFlags::BMI2) {
+                    assert_eq!(pext(value, 0xff00), (value >> 8) & 0xff);
+                    assert_eq!(pdep(value, 0xff00), (value & 0xff) << 8);
+                    assert_eq!(pext(pdep(value, 0xf0f0), 0xf0f0), value & 0xff);
+                    assert_eq!(pext(value, u64::max_value()), value);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,433 @@
+//! Interrupt descriptor table, its entries and the interrupt stack frame for IA-32e mode.
+
+use crate::bits64::paging::VAddr;
+use crate::bits64::rflags::RFlags;
+use crate::dtables::{lidt, DescriptorTablePointer};
+use crate::segmentation::SegmentSelector;
+use crate::Ring;
+
+use core::arch::asm;
+
+/// Type of an IDT gate descriptor.
+///
+/// See Intel 3a, Section 6.12.1.2 "Flag Usage By Exception- or Interrupt-Handler Procedure".
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum GateType {
+    /// Clears IF on entry, so the handler runs with interrupts disabled.
+    Interrupt = 0b1110,
+    /// Leaves IF unchanged.
+    Trap = 0b1111,
+}
+
+/// A 64-bit IDT gate descriptor (16 bytes).
+///
+/// See Intel 3a, Section 6.14.1 "64-Bit Mode IDT", Figure 6-8.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct IdtEntry {
+    offset_low: u16,
+    selector: u16,
+    ist: u8,
+    flags: u8,
+    offset_mid: u16,
+    offset_high: u32,
+    reserved: u32,
+}
+
+impl IdtEntry {
+    /// A not-present entry.
+    pub const MISSING: IdtEntry = IdtEntry {
+        offset_low: 0,
+        selector: 0,
+        ist: 0,
+        flags: 0,
+        offset_mid: 0,
+        offset_high: 0,
+        reserved: 0,
+    };
+
+    /// Creates a present gate that jumps to `handler` in the code segment `selector`.
+    ///
+    /// `handler` is the address of the handler function, e.g., an
+    /// `extern "x86-interrupt" fn` cast to `u64`.
+    /// `dpl` is the highest ring allowed to trigger the gate with `int n`.
+    pub const fn new(
+        handler: u64,
+        selector: SegmentSelector,
+        dpl: Ring,
+        gate: GateType,
+    ) -> IdtEntry {
+        IdtEntry {
+            offset_low: handler as u16,
+            selector: selector.bits(),
+            ist: 0,
+            flags: 1 << 7 | (dpl as u8) << 5 | gate as u8,
+            offset_mid: (handler >> 16) as u16,
+            offset_high: (handler >> 32) as u32,
+            reserved: 0,
+        }
+    }
+
+    /// Sets the interrupt stack table index (1-7, 0 disables the IST mechanism).
+    pub fn set_ist(&mut self, index: u8) {
+        assert!(index <= 0b111);
+        self.ist = index;
+    }
+
+    /// Returns the address of the handler.
+    pub fn handler(&self) -> u64 {
+        (self.offset_high as u64) << 32 | (self.offset_mid as u64) << 16 | self.offset_low as u64
+    }
+
+    /// Returns the code segment selector of the handler.
+    pub fn selector(&self) -> SegmentSelector {
+        SegmentSelector::from_raw(self.selector)
+    }
+
+    /// Is the entry present?
+    pub fn is_present(&self) -> bool {
+        self.flags & (1 << 7) > 0
+    }
+}
+
+/// Generates accessors for the entries of architectural exceptions.
+macro_rules! exception_entries {
+    ($($(#[$doc:meta])* $name:ident => $vector:ident,)*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(&mut self) -> &mut IdtEntry {
+                &mut self.entries[crate::irq::$vector as usize]
+            }
+        )*
+    };
+}
+
+/// An interrupt descriptor table with entries for all 256 vectors.
+///
+/// See Intel 3a, Section 6.10 "Interrupt Descriptor Table (IDT)".
+#[derive(Clone, Debug)]
+#[repr(C, align(16))]
+pub struct Idt {
+    pub entries: [IdtEntry; 256],
+}
+
+impl Idt {
+    /// Creates a table with all entries missing.
+    pub const fn new() -> Idt {
+        Idt {
+            entries: [IdtEntry::MISSING; 256],
+        }
+    }
+
+    /// Installs `entry` for `vector`.
+    pub fn set_handler(&mut self, vector: u8, entry: IdtEntry) {
+        self.entries[vector as usize] = entry;
+    }
+
+    exception_entries! {
+        /// Entry for the divide error (#DE).
+        divide_error => DIVIDE_ERROR_VECTOR,
+        /// Entry for the debug exception (#DB).
+        debug => DEBUG_VECTOR,
+        /// Entry for the non-maskable interrupt.
+        non_maskable_interrupt => NONMASKABLE_INTERRUPT_VECTOR,
+        /// Entry for the breakpoint exception (#BP).
+        breakpoint => BREAKPOINT_VECTOR,
+        /// Entry for the overflow exception (#OF).
+        overflow => OVERFLOW_VECTOR,
+        /// Entry for the BOUND range exceeded exception (#BR).
+        bound_range_exceeded => BOUND_RANGE_EXCEEDED_VECTOR,
+        /// Entry for the invalid opcode exception (#UD).
+        invalid_opcode => INVALID_OPCODE_VECTOR,
+        /// Entry for the device not available exception (#NM).
+        device_not_available => DEVICE_NOT_AVAILABLE_VECTOR,
+        /// Entry for the double fault (#DF), pushes an error code.
+        double_fault => DOUBLE_FAULT_VECTOR,
+        /// Entry for the invalid TSS exception (#TS), pushes an error code.
+        invalid_tss => INVALID_TSS_VECTOR,
+        /// Entry for the segment not present exception (#NP), pushes an error code.
+        segment_not_present => SEGMENT_NOT_PRESENT_VECTOR,
+        /// Entry for the stack fault (#SS), pushes an error code.
+        stack_segment_fault => STACK_SEGEMENT_FAULT_VECTOR,
+        /// Entry for the general protection fault (#GP), pushes an error code.
+        general_protection_fault => GENERAL_PROTECTION_FAULT_VECTOR,
+        /// Entry for the page fault (#PF), pushes a [PageFaultErrorCode].
+        page_fault => PAGE_FAULT_VECTOR,
+        /// Entry for the x87 floating-point error (#MF).
+        x87_floating_point => X87_FPU_VECTOR,
+        /// Entry for the alignment check exception (#AC), pushes an error code.
+        alignment_check => ALIGNMENT_CHECK_VECTOR,
+        /// Entry for the machine check exception (#MC).
+        machine_check => MACHINE_CHECK_VECTOR,
+        /// Entry for the SIMD floating-point exception (#XM).
+        simd_floating_point => SIMD_FLOATING_POINT_VECTOR,
+        /// Entry for the virtualization exception (#VE).
+        virtualization => VIRTUALIZATION_VECTOR,
+    }
+
+    /// Returns a pointer to the table that can be loaded with `lidt`.
+    pub fn pointer(&self) -> DescriptorTablePointer<IdtEntry> {
+        DescriptorTablePointer::new_from_slice(&self.entries)
+    }
+
+    /// Loads the table into the IDTR register.
+    ///
+    /// # Safety
+    /// Needs CPL 0. The entries must point to valid handlers.
+    pub unsafe fn load(&'static self) {
+        lidt(&self.pointer());
+    }
+}
+
+impl Default for Idt {
+    fn default() -> Idt {
+        Idt::new()
+    }
+}
+
+pub use crate::irq::{disable, enable};
+
+/// The error code pushed for a page fault (#PF).
+pub use crate::irq::PageFaultError as PageFaultErrorCode;
+
+/// The frame the processor pushes on the stack when delivering an
+/// interrupt or exception in IA-32e mode (as received by `extern "x86-interrupt"`
+/// handlers).
+///
+/// For exceptions with an error code, the error code is pushed after
+/// (i.e., below) this frame.
+///
+/// See Intel 3a, Section 6.14.2 "64-Bit Mode Stack Frame", Figure 6-9.
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct InterruptStackFrame {
+    /// Return address (RIP of the interrupted instruction or the one after it).
+    pub instruction_pointer: VAddr,
+    /// CS of the interrupted code (upper 48 bits are undefined).
+    pub code_segment: u64,
+    /// RFLAGS before the interrupt.
+    pub cpu_flags: RFlags,
+    /// RSP of the interrupted code.
+    pub stack_pointer: VAddr,
+    /// SS of the interrupted code (upper 48 bits are undefined).
+    pub stack_segment: u64,
+}
+
+/// Is the interrupt flag (RFLAGS.IF) set?
+#[cfg(target_arch = "x86_64")]
+pub fn are_enabled() -> bool {
+    use crate::bits64::rflags::{self, RFlags};
+    rflags::read().contains(RFlags::FLAGS_IF)
+}
+
+/// Trigger a breakpoint exception (#BP) with `int3`.
+///
+/// # Safety
+/// A handler for vector 3 must be installed (or the process must handle
+/// SIGTRAP in user-space).
+pub unsafe fn int3() {
+    asm!("int3", options(nostack));
+}
+
+/// Generate software interrupt `n` with `int n`.
+///
+/// `int` only takes an immediate, so this dispatches to one of 256
+/// instructions; use the `int!` macro if `n` is known at compile-time.
+///
+/// # Safety
+/// The IDT gate for `n` must be present and allow the current privilege
+/// level (DPL >= CPL), otherwise this raises a #GP.
+pub unsafe fn int(n: u8) {
+    macro_rules! dispatch {
+        ($n:expr; $($vector:literal)*) => {
+            match $n {
+                $($vector => asm!("int {}", const $vector),)*
+            }
+        };
+    }
+
+    dispatch!(n;
+            0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+            16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+            32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47
+            48 49 50 51 52 53 54 55 56 57 58 59 60 61 62 63
+            64 65 66 67 68 69 70 71 72 73 74 75 76 77 78 79
+            80 81 82 83 84 85 86 87 88 89 90 91 92 93 94 95
+            96 97 98 99 100 101 102 103 104 105 106 107 108 109 110 111
+            112 113 114 115 116 117 118 119 120 121 122 123 124 125 126 127
+            128 129 130 131 132 133 134 135 136 137 138 139 140 141 142 143
+            144 145 146 147 148 149 150 151 152 153 154 155 156 157 158 159
+            160 161 162 163 164 165 166 167 168 169 170 171 172 173 174 175
+            176 177 178 179 180 181 182 183 184 185 186 187 188 189 190 191
+            192 193 194 195 196 197 198 199 200 201 202 203 204 205 206 207
+            208 209 210 211 212 213 214 215 216 217 218 219 220 221 222 223
+            224 225 226 227 228 229 230 231 232 233 234 235 236 237 238 239
+            240 241 242 243 244 245 246 247 248 249 250 251 252 253 254 255
+    );
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+    use crate::bits64::segmentation::Descriptor64;
+    use crate::segmentation::{
+        BuildDescriptor, DescriptorBuilder, GateDescriptorBuilder, SegmentTable,
+    };
+    use core::mem::{size_of, transmute};
+
+    #[test]
+    fn idt_entry() {
+        assert_eq!(size_of::<IdtEntry>(), 16);
+
+        let cs = SegmentSelector::new(1, Ring::Ring0, SegmentTable::Gdt);
+        let entry = IdtEntry::new(0xffff_8000_1234_5678, cs, Ring::Ring0, GateType::Interrupt);
+        assert_eq!(entry.handler(), 0xffff_8000_1234_5678);
+        assert_eq!(entry.selector(), cs);
+        assert!(entry.is_present());
+        assert!(!IdtEntry::MISSING.is_present());
+
+        let raw: [u64; 2] = unsafe { transmute(entry) };
+        assert_eq!(raw, [0x1234_8e00_0008_5678, 0xffff_8000]);
+
+        let trap = IdtEntry::new(0x1000, cs, Ring::Ring3, GateType::Trap);
+        let raw: [u64; 2] = unsafe { transmute(trap) };
+        assert_eq!(raw, [0x0000_ef00_0008_1000, 0]);
+    }
+
+    #[test]
+    fn idt_handlers() {
+        let cs = SegmentSelector::new(1, Ring::Ring0, SegmentTable::Gdt);
+        let entry = IdtEntry::new(0xffff_8000_0000_1000, cs, Ring::Ring0, GateType::Interrupt);
+
+        let mut idt = Idt::new();
+        assert!(idt.entries.iter().all(|e| !e.is_present()));
+
+        *idt.page_fault() = entry;
+        assert_eq!(idt.entries[14], entry);
+        *idt.general_protection_fault() = entry;
+        assert_eq!(idt.entries[13], entry);
+
+        idt.set_handler(0x80, entry);
+        assert_eq!(idt.entries[0x80], entry);
+        assert!(!idt.entries[0x7f].is_present());
+        assert!(!idt.entries[0x81].is_present());
+
+        let ptr = idt.pointer();
+        let (limit, base) = (ptr.limit, ptr.base);
+        assert_eq!(limit, 256 * 16 - 1);
+        assert_eq!(base, idt.entries.as_ptr());
+    }
+
+    #[test]
+    fn interrupt_stack_frame_layout() {
+        use core::ptr::addr_of;
+
+        assert_eq!(size_of::<InterruptStackFrame>(), 40);
+
+        // Pushed by the processor: SS, RSP, RFLAGS, CS, RIP (lowest address)
+        let raw: [u64; 5] = [0xffff_8000_0000_1234, 0x8, 0x202, 0x7fff_ffff_e000, 0x10];
+        let frame: InterruptStackFrame = unsafe { transmute(raw) };
+        assert_eq!(frame.instruction_pointer.as_u64(), 0xffff_8000_0000_1234);
+        assert_eq!(frame.code_segment, 0x8);
+        assert!(frame
+            .cpu_flags
+            .contains(RFlags::FLAGS_IF | RFlags::FLAGS_A1));
+        assert_eq!(frame.stack_pointer.as_u64(), 0x7fff_ffff_e000);
+        assert_eq!(frame.stack_segment, 0x10);
+
+        let base = &frame as *const InterruptStackFrame as usize;
+        assert_eq!(addr_of!(frame.cpu_flags) as usize - base, 16);
+        assert_eq!(addr_of!(frame.stack_segment) as usize - base, 32);
+    }
+
+    #[test]
+    fn page_fault_error_code() {
+        let code = PageFaultErrorCode::from_bits_truncate(0b10111);
+        assert!(code.contains(PageFaultErrorCode::P | PageFaultErrorCode::WR));
+        assert!(code.contains(PageFaultErrorCode::US | PageFaultErrorCode::ID));
+        assert!(!code.contains(PageFaultErrorCode::RSVD));
+    }
+
+    #[test]
+    fn page_fault_description() {
+        let describe = |bits| PageFaultErrorCode::from_bits_truncate(bits).describe();
+        assert_eq!(describe(0b111), "user-mode write to present page");
+        assert_eq!(
+            describe(0b000),
+            "supervisor-mode read from non-present page"
+        );
+        assert_eq!(
+            describe(0b10001),
+            "supervisor-mode instruction fetch from present page"
+        );
+        assert_eq!(
+            describe(0b1101),
+            "reserved bit set in paging-structure entry"
+        );
+        assert_eq!(describe(0b100111), "protection-key violation");
+        assert_eq!(describe(1 << 15 | 0b1), "SGX access-control violation");
+
+        assert_eq!(
+            PageFaultErrorCode::PRESENT | PageFaultErrorCode::WRITE | PageFaultErrorCode::USER,
+            PageFaultErrorCode::from_bits_truncate(0b111)
+        );
+        assert_eq!(PageFaultErrorCode::INSTRUCTION_FETCH.bits(), 1 << 4);
+        assert_eq!(PageFaultErrorCode::RESERVED_WRITE.bits(), 1 << 3);
+        assert_eq!(
+            PageFaultErrorCode::RESERVED_WRITE,
+            PageFaultErrorCode::RESERVED_BIT
+        );
+        assert_eq!(PageFaultErrorCode::SHADOW_STACK.bits(), 1 << 6);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn interrupts_enabled_in_user_space() {
+        assert!(are_enabled());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn int3_raises_sigtrap() {
+        extern crate libc;
+        extern crate std;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static TRAPS: AtomicUsize = AtomicUsize::new(0);
+        extern "C" fn on_sigtrap(_signal: libc::c_int) {
+            TRAPS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        unsafe {
+            // #BP is a trap: the handler returns to the instruction after `int3`.
+            let previous = libc::signal(
+                libc::SIGTRAP,
+                on_sigtrap as extern "C" fn(libc::c_int) as *const () as libc::sighandler_t,
+            );
+            int3();
+            assert_eq!(TRAPS.load(Ordering::SeqCst), 1);
+            // Vector 3 is accessible from ring 3 so this also works for `int 3`:
+            int(3);
+            assert_eq!(TRAPS.load(Ordering::SeqCst), 2);
+            libc::signal(libc::SIGTRAP, previous);
+        }
+    }
+
+    #[test]
+    fn idt_entry_matches_builder() {
+        let cs = SegmentSelector::new(1, Ring::Ring0, SegmentTable::Gdt);
+        let mut entry = IdtEntry::new(0xdead_beef_cafe, cs, Ring::Ring0, GateType::Interrupt);
+        entry.set_ist(2);
+
+        let desc: Descriptor64 = DescriptorBuilder::interrupt_descriptor(cs, 0xdead_beef_cafe_u64)
+            .present()
+            .dpl(Ring::Ring0)
+            .ist(2)
+            .finish();
+
+        let a: [u64; 2] = unsafe { transmute(entry) };
+        let b: [u64; 2] = unsafe { transmute(desc) };
+        assert_eq!(a, b);
+    }
+}
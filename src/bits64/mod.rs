@@ -1,5 +1,8 @@
 //! Data structures and functions used by IA-32e but not Protected Mode.
 
+#[cfg(target_arch = "x86_64")]
+pub mod bitmanip;
+pub mod irq;
 pub mod paging;
 #[cfg(target_arch = "x86_64")]
 pub mod registers;
@@ -7,6 +10,8 @@ pub mod rflags;
 pub mod segmentation;
 #[cfg(target_arch = "x86_64")]
 pub mod sgx;
+#[cfg(target_arch = "x86_64")]
+pub mod svm;
 pub mod syscall;
 pub mod task;
 #[cfg(target_arch = "x86_64")]
@@ -5,7 +5,9 @@ use bitflags::*;
 use core::convert::{From, Into};
 use core::fmt;
 use core::hash::{Hash, Hasher};
+use core::iter;
 use core::ops;
+use core::slice;
 
 macro_rules! check_flag {
     ($doc:meta, $fun:ident, $flag:expr) => {
@@ -22,6 +24,7 @@ macro_rules! check_flag {
 /// The alignment must be a power of 2.
 #[inline(always)]
 fn align_down(addr: u64, align: u64) -> u64 {
+    debug_assert!(align.is_power_of_two());
     addr & !(align - 1)
 }
 
@@ -31,6 +34,7 @@ fn align_down(addr: u64, align: u64) -> u64 {
 /// The alignment must be a power of 2.
 #[inline(always)]
 fn align_up(addr: u64, align: u64) -> u64 {
+    debug_assert!(align.is_power_of_two());
     let align_mask = align - 1;
     if addr & align_mask == 0 {
         addr
@@ -39,14 +43,39 @@ fn align_up(addr: u64, align: u64) -> u64 {
     }
 }
 
+/// Formats an address for `{:p}` (used by the `fmt::Pointer` impls of the
+/// address types): in hex with a `0x` prefix, like a raw pointer.
+///
+/// Width, fill and alignment are honored; `{:#p}` behaves like `{:#x}`
+/// (e.g., `{:#018p}` zero-pads to 16 digits).
+pub(crate) fn fmt_pointer(addr: u64, f: &mut fmt::Formatter) -> fmt::Result {
+    if f.alternate() {
+        return fmt::LowerHex::fmt(&addr, f);
+    }
+
+    let digits = core::cmp::max(1, (64 - addr.leading_zeros() as usize).div_ceil(4));
+    let mut buf = [0u8; 18];
+    buf[..2].copy_from_slice(b"0x");
+    for (i, c) in buf[2..2 + digits].iter_mut().enumerate() {
+        let nibble = (addr >> (4 * (digits - 1 - i))) & 0xf;
+        *c = b"0123456789abcdef"[nibble as usize];
+    }
+    f.pad(core::str::from_utf8(&buf[..2 + digits]).unwrap())
+}
+
 /// A wrapper for a physical address.
 #[repr(transparent)]
 #[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct PAddr(pub u64);
 
 impl PAddr {
+    /// Convert from `u64`
+    pub const fn from_u64(v: u64) -> Self {
+        PAddr(v)
+    }
+
     /// Convert to `u64`
-    pub fn as_u64(self) -> u64 {
+    pub const fn as_u64(self) -> u64 {
         self.0
     }
 
@@ -70,14 +99,22 @@ impl PAddr {
         (self.0 as u32, (self.0 >> 32) as u32)
     }
 
-    fn align_up<U>(self, align: U) -> Self
+    /// Return the smallest address with alignment `align` that is >= self.
+    ///
+    /// # Note
+    /// `align` must be a power of two.
+    pub fn align_up<U>(self, align: U) -> Self
     where
         U: Into<u64>,
     {
         PAddr(align_up(self.0, align.into()))
     }
 
-    fn align_down<U>(self, align: U) -> Self
+    /// Return the greatest address with alignment `align` that is <= self.
+    ///
+    /// # Note
+    /// `align` must be a power of two.
+    pub fn align_down<U>(self, align: U) -> Self
     where
         U: Into<u64>,
     {
@@ -354,8 +391,7 @@ impl fmt::UpperHex for PAddr {
 
 impl fmt::Pointer for PAddr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use core::fmt::LowerHex;
-        self.0.fmt(f)
+        fmt_pointer(self.0, f)
     }
 }
 
@@ -397,14 +433,22 @@ impl IOAddr {
         (self.0 as u32, (self.0 >> 32) as u32)
     }
 
-    fn align_up<U>(self, align: U) -> Self
+    /// Return the smallest address with alignment `align` that is >= self.
+    ///
+    /// # Note
+    /// `align` must be a power of two.
+    pub fn align_up<U>(self, align: U) -> Self
     where
         U: Into<u64>,
     {
         IOAddr(align_up(self.0, align.into()))
     }
 
-    fn align_down<U>(self, align: U) -> Self
+    /// Return the greatest address with alignment `align` that is <= self.
+    ///
+    /// # Note
+    /// `align` must be a power of two.
+    pub fn align_down<U>(self, align: U) -> Self
     where
         U: Into<u64>,
     {
@@ -681,8 +725,7 @@ impl fmt::UpperHex for IOAddr {
 
 impl fmt::Pointer for IOAddr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use core::fmt::LowerHex;
-        self.0.fmt(f)
+        fmt_pointer(self.0, f)
     }
 }
 
@@ -739,14 +782,44 @@ impl VAddr {
         self == VAddr::zero()
     }
 
-    fn align_up<U>(self, align: U) -> Self
+    /// Convert from `u64`, fails if `v` is not a canonical address.
+    pub fn try_new(v: u64) -> Result<VAddr, NonCanonicalAddr> {
+        let vaddr = VAddr(v);
+        if vaddr.is_canonical() {
+            Ok(vaddr)
+        } else {
+            Err(NonCanonicalAddr(v))
+        }
+    }
+
+    /// Is this a canonical address with 4-level paging
+    /// (i.e., bits 63:48 are copies of bit 47)?
+    pub const fn is_canonical(self) -> bool {
+        sign_extend(self.0, 48) == self.0
+    }
+
+    /// Is this a canonical address with 5-level paging
+    /// (i.e., bits 63:57 are copies of bit 56)?
+    pub const fn is_canonical_la57(self) -> bool {
+        sign_extend(self.0, 57) == self.0
+    }
+
+    /// Return the smallest address with alignment `align` that is >= self.
+    ///
+    /// # Note
+    /// `align` must be a power of two.
+    pub fn align_up<U>(self, align: U) -> Self
     where
         U: Into<u64>,
     {
         VAddr(align_up(self.0, align.into()))
     }
 
-    fn align_down<U>(self, align: U) -> Self
+    /// Return the greatest address with alignment `align` that is <= self.
+    ///
+    /// # Note
+    /// `align` must be a power of two.
+    pub fn align_down<U>(self, align: U) -> Self
     where
         U: Into<u64>,
     {
@@ -768,6 +841,38 @@ impl VAddr {
         self.0 & (HUGE_PAGE_SIZE as u64 - 1)
     }
 
+    /// Offset within the 4 KiB page (same as `base_page_offset`).
+    pub fn page_offset(self) -> u64 {
+        self.base_page_offset()
+    }
+
+    /// Index of the PML5 entry for this address (see [`pml5_index`]).
+    #[cfg(target_arch = "x86_64")]
+    pub fn pml5_index(self) -> usize {
+        pml5_index(self)
+    }
+
+    /// Index of the PML4 entry for this address (see [`pml4_index`]).
+    #[cfg(target_arch = "x86_64")]
+    pub fn pml4_index(self) -> usize {
+        pml4_index(self)
+    }
+
+    /// Index of the PDPT entry for this address (see [`pdpt_index`]).
+    pub fn pdpt_index(self) -> usize {
+        pdpt_index(self)
+    }
+
+    /// Index of the PD entry for this address (see [`pd_index`]).
+    pub fn pd_index(self) -> usize {
+        pd_index(self)
+    }
+
+    /// Index of the PT entry for this address (see [`pt_index`]).
+    pub fn pt_index(self) -> usize {
+        pt_index(self)
+    }
+
     /// Return address of nearest 4 KiB page (lower or equal than self).
     pub fn align_down_to_base_page(self) -> Self {
         self.align_down(BASE_PAGE_SIZE as u64)
@@ -829,6 +934,21 @@ impl VAddr {
     }
 }
 
+/// Sign-extend the lower `bits` of `addr` into the upper ones.
+const fn sign_extend(addr: u64, bits: u32) -> u64 {
+    (((addr << (64 - bits)) as i64) >> (64 - bits)) as u64
+}
+
+/// Error for an address that isn't canonical (see [`VAddr::try_new`]).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct NonCanonicalAddr(pub u64);
+
+impl fmt::Display for NonCanonicalAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x} is not a canonical address", self.0)
+    }
+}
+
 impl From<u64> for VAddr {
     fn from(num: u64) -> Self {
         VAddr(num)
@@ -854,6 +974,18 @@ impl From<usize> for VAddr {
     }
 }
 
+impl<T> From<*const T> for VAddr {
+    fn from(ptr: *const T) -> Self {
+        VAddr(ptr as usize as u64)
+    }
+}
+
+impl<T> From<*mut T> for VAddr {
+    fn from(ptr: *mut T) -> Self {
+        VAddr(ptr as usize as u64)
+    }
+}
+
 #[allow(clippy::clippy::from_over_into)]
 impl Into<usize> for VAddr {
     fn into(self) -> usize {
@@ -1069,8 +1201,7 @@ impl fmt::UpperHex for VAddr {
 
 impl fmt::Pointer for VAddr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use core::fmt::LowerHex;
-        self.0.fmt(f)
+        fmt_pointer(self.0, f)
     }
 }
 
@@ -1140,6 +1271,67 @@ pub type PD = [PDEntry; PAGE_SIZE_ENTRIES];
 /// A page table.
 pub type PT = [PTEntry; PAGE_SIZE_ENTRIES];
 
+/// An iterator over the present entries of a page-table,
+/// yields the index and a reference to the entry.
+pub type PresentEntries<'a, E> =
+    iter::Filter<iter::Enumerate<slice::Iter<'a, E>>, fn(&(usize, &'a E)) -> bool>;
+
+/// A page-table (or anything else) aligned to a base page.
+///
+/// The table types are plain arrays, wrap them in this to get a correctly
+/// aligned `static` or stack allocation:
+///
+/// ```
+/// use x86::bits64::paging::{AlignedTable, PML4Entry, PML4, PAGE_SIZE_ENTRIES};
+///
+/// static KERNEL_PML4: AlignedTable<PML4> = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+/// assert_eq!(KERNEL_PML4.as_ptr() as usize % 4096, 0);
+/// ```
+#[derive(Clone, Copy, Debug)]
+#[repr(C, align(4096))]
+pub struct AlignedTable<T>(pub T);
+
+impl<T> ops::Deref for AlignedTable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> ops::DerefMut for AlignedTable<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// Common functionality of all page-table types.
+pub trait PageTable {
+    /// The entry type stored in this table.
+    type Entry;
+
+    /// Returns an iterator over all entries that have the P bit set.
+    fn present_entries(&self) -> PresentEntries<'_, Self::Entry>;
+}
+
+macro_rules! impl_page_table {
+    ($table:ty, $entry:ty) => {
+        impl PageTable for $table {
+            type Entry = $entry;
+
+            fn present_entries(&self) -> PresentEntries<'_, $entry> {
+                self.iter().enumerate().filter(|(_, e)| e.is_present())
+            }
+        }
+    };
+}
+
+impl_page_table!(PML5, PML5Entry);
+impl_page_table!(PML4, PML4Entry);
+impl_page_table!(PDPT, PDPTEntry);
+impl_page_table!(PD, PDEntry);
+impl_page_table!(PT, PTEntry);
+
 /// Given virtual address calculate corresponding entry in PML4.
 #[cfg(target_arch = "x86_64")]
 #[inline]
@@ -1233,9 +1425,10 @@ impl fmt::Debug for PML4Entry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "PML4Entry {{ {:#x}, {:?} }}",
+            "PML4Entry {{ {:#x}, {:?}, raw: {:#x} }}",
             self.address(),
-            self.flags()
+            self.flags(),
+            self.0
         )
     }
 }
@@ -1247,10 +1440,10 @@ impl PML4Entry {
     ///
     ///  * `pdpt` - The physical address of the pdpt table.
     ///  * `flags`- Additional flags for the entry.
-    pub fn new(pml4: PAddr, flags: PML4Flags) -> PML4Entry {
-        let pml4_val = pml4 & ADDRESS_MASK;
-        assert!(pml4_val == pml4.into());
-        assert!(pml4 % BASE_PAGE_SIZE == 0);
+    pub const fn new(pml4: PAddr, flags: PML4Flags) -> PML4Entry {
+        let pml4_val = pml4.as_u64() & ADDRESS_MASK;
+        assert!(pml4_val == pml4.as_u64());
+        assert!(pml4.as_u64() & (BASE_PAGE_SIZE as u64 - 1) == 0);
         PML4Entry(pml4_val | flags.bits)
     }
 
@@ -1280,6 +1473,11 @@ impl PML4Entry {
     );
     check_flag!(doc = "If IA32_EFER.NXE = 1, execute-disable. If 1, instruction fetches are not allowed from the 512-GByte region.",
                 is_instruction_fetching_disabled, PML4Flags::XD);
+
+    /// Are instruction fetches allowed from the region controlled by this entry (XD not set)?
+    pub fn is_executable(self) -> bool {
+        !self.flags().contains(PML4Flags::XD)
+    }
 }
 
 /// A PML5 Entry consists of an address and a bunch of flags.
@@ -1291,9 +1489,10 @@ impl fmt::Debug for PML5Entry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "PML5Entry {{ {:#x}, {:?} }}",
+            "PML5Entry {{ {:#x}, {:?}, raw: {:#x} }}",
             self.address(),
-            self.flags()
+            self.flags(),
+            self.0
         )
     }
 }
@@ -1305,10 +1504,10 @@ impl PML5Entry {
     ///
     ///  * `pml4` - The physical address of the PML4 table that this entry points to.
     ///  * `flags` - Additional flags for the entry.
-    pub fn new(pml4: PAddr, flags: PML5Flags) -> PML5Entry {
-        let pml5_val = pml4 & ADDRESS_MASK;
-        assert!(pml5_val == pml4.into());
-        assert!(pml4 % BASE_PAGE_SIZE == 0);
+    pub const fn new(pml4: PAddr, flags: PML5Flags) -> PML5Entry {
+        let pml5_val = pml4.as_u64() & ADDRESS_MASK;
+        assert!(pml5_val == pml4.as_u64());
+        assert!(pml4.as_u64() & (BASE_PAGE_SIZE as u64 - 1) == 0);
         PML5Entry(pml5_val | flags.bits)
     }
 
@@ -1338,6 +1537,11 @@ impl PML5Entry {
     );
     check_flag!(doc = "If IA32_EFER.NXE = 1, execute-disable. If 1, instruction fetches are not allowed from the 256-TByte region.",
                 is_instruction_fetching_disabled, PML5Flags::XD);
+
+    /// Are instruction fetches allowed from the region controlled by this entry (XD not set)?
+    pub fn is_executable(self) -> bool {
+        !self.flags().contains(PML5Flags::XD)
+    }
 }
 
 bitflags! {
@@ -1383,9 +1587,10 @@ impl fmt::Debug for PDPTEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "PDPTEntry {{ {:#x}, {:?} }}",
+            "PDPTEntry {{ {:#x}, {:?}, raw: {:#x} }}",
             self.address(),
-            self.flags()
+            self.flags(),
+            self.0
         )
     }
 }
@@ -1397,16 +1602,34 @@ impl PDPTEntry {
     ///
     ///  * `pd` - The physical address of the page directory.
     ///  * `flags`- Additional flags for the entry.
-    pub fn new(pd: PAddr, flags: PDPTFlags) -> PDPTEntry {
-        let pd_val = pd & ADDRESS_MASK;
-        assert!(pd_val == pd.into());
-        assert!(pd % BASE_PAGE_SIZE == 0);
+    pub const fn new(pd: PAddr, flags: PDPTFlags) -> PDPTEntry {
+        let pd_val = pd.as_u64() & ADDRESS_MASK;
+        assert!(pd_val == pd.as_u64());
+        assert!(pd.as_u64() & (BASE_PAGE_SIZE as u64 - 1) == 0);
         PDPTEntry(pd_val | flags.bits)
     }
 
+    /// Creates a new PDPTEntry that maps a 1 GiB page (sets PS).
+    ///
+    /// # Arguments
+    ///
+    ///  * `page` - The physical address of the 1 GiB page. The offset within the
+    ///    page is masked off, so it can't end up in the PAT or reserved bits.
+    ///  * `flags`- Additional flags for the entry.
+    pub const fn new_huge_page(page: PAddr, flags: PDPTFlags) -> PDPTEntry {
+        let frame = PAddr(page.as_u64() & !(HUGE_PAGE_SIZE as u64 - 1));
+        PDPTEntry::new(frame, flags.union(PDPTFlags::PS))
+    }
+
     /// Retrieves the physical address in this entry.
+    ///
+    /// For 1 GiB pages this is the address of the page (without the PAT bit).
     pub fn address(self) -> PAddr {
-        PAddr::from(self.0 & ADDRESS_MASK)
+        if self.is_page() {
+            PAddr::from(self.0 & ADDRESS_MASK & !(HUGE_PAGE_SIZE as u64 - 1))
+        } else {
+            PAddr::from(self.0 & ADDRESS_MASK)
+        }
     }
 
     /// Returns the flags corresponding to this entry.
@@ -1435,12 +1658,29 @@ impl PDPTEntry {
         is_accessed,
         PDPTFlags::A
     );
+    check_flag!(doc = "Dirty; if PS set indicates whether software has written to the 1-GByte page referenced by this entry else ignored.",
+                is_dirty, PDPTFlags::D);
     check_flag!(doc = "Indirectly determines the memory type used to access the 1-GByte page referenced by this entry. if not PS this is ignored.",
                 is_pat, PDPTFlags::PAT);
     check_flag!(doc = "If IA32_EFER.NXE = 1, execute-disable. If 1, instruction fetches are not allowed from the 512-GByte region.",
                 is_instruction_fetching_disabled, PDPTFlags::XD);
     check_flag!(doc = "Page size; if set this entry maps a 1-GByte page; otherwise, this entry references a page directory.",
                 is_page, PDPTFlags::PS);
+
+    /// Are instruction fetches allowed from the region controlled by this entry (XD not set)?
+    pub fn is_executable(self) -> bool {
+        !self.flags().contains(PDPTFlags::XD)
+    }
+
+    /// Clears the accessed (A) bit, all other bits are preserved.
+    pub fn clear_accessed(&mut self) {
+        self.0 &= !PDPTFlags::A.bits;
+    }
+
+    /// Clears the dirty (D) bit, all other bits are preserved.
+    pub fn clear_dirty(&mut self) {
+        self.0 &= !PDPTFlags::D.bits;
+    }
 }
 
 bitflags! {
@@ -1484,7 +1724,13 @@ pub struct PDEntry(pub u64);
 
 impl fmt::Debug for PDEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "PDEntry {{ {:#x}, {:?} }}", self.address(), self.flags())
+        write!(
+            f,
+            "PDEntry {{ {:#x}, {:?}, raw: {:#x} }}",
+            self.address(),
+            self.flags(),
+            self.0
+        )
     }
 }
 
@@ -1495,16 +1741,34 @@ impl PDEntry {
     ///
     ///  * `pt` - The physical address of the page table.
     ///  * `flags`- Additional flags for the entry.
-    pub fn new(pt: PAddr, flags: PDFlags) -> PDEntry {
-        let pt_val = pt & ADDRESS_MASK;
-        assert!(pt_val == pt.into());
-        assert!(pt % BASE_PAGE_SIZE == 0);
+    pub const fn new(pt: PAddr, flags: PDFlags) -> PDEntry {
+        let pt_val = pt.as_u64() & ADDRESS_MASK;
+        assert!(pt_val == pt.as_u64());
+        assert!(pt.as_u64() & (BASE_PAGE_SIZE as u64 - 1) == 0);
         PDEntry(pt_val | flags.bits)
     }
 
+    /// Creates a new PDEntry that maps a 2 MiB page (sets PS).
+    ///
+    /// # Arguments
+    ///
+    ///  * `page` - The physical address of the 2 MiB page. The offset within the
+    ///    page is masked off, so it can't end up in the PAT or reserved bits.
+    ///  * `flags`- Additional flags for the entry.
+    pub const fn new_large_page(page: PAddr, flags: PDFlags) -> PDEntry {
+        let frame = PAddr(page.as_u64() & !(LARGE_PAGE_SIZE as u64 - 1));
+        PDEntry::new(frame, flags.union(PDFlags::PS))
+    }
+
     /// Retrieves the physical address in this entry.
+    ///
+    /// For 2 MiB pages this is the address of the page (without the PAT bit).
     pub fn address(self) -> PAddr {
-        PAddr::from(self.0 & ADDRESS_MASK)
+        if self.is_page() {
+            PAddr::from(self.0 & ADDRESS_MASK & !(LARGE_PAGE_SIZE as u64 - 1))
+        } else {
+            PAddr::from(self.0 & ADDRESS_MASK)
+        }
     }
 
     /// Returns the flags corresponding to this entry.
@@ -1543,6 +1807,21 @@ impl PDEntry {
                 is_pat, PDFlags::PAT);
     check_flag!(doc = "If IA32_EFER.NXE = 1, execute-disable. If 1, instruction fetches are not allowed from the 2-Mbyte region.",
                 is_instruction_fetching_disabled, PDFlags::XD);
+
+    /// Are instruction fetches allowed from the region controlled by this entry (XD not set)?
+    pub fn is_executable(self) -> bool {
+        !self.flags().contains(PDFlags::XD)
+    }
+
+    /// Clears the accessed (A) bit, all other bits are preserved.
+    pub fn clear_accessed(&mut self) {
+        self.0 &= !PDFlags::A.bits;
+    }
+
+    /// Clears the dirty (D) bit, all other bits are preserved.
+    pub fn clear_dirty(&mut self) {
+        self.0 &= !PDFlags::D.bits;
+    }
 }
 
 bitflags! {
@@ -1563,6 +1842,9 @@ bitflags! {
         const A       = bit!(5);
         /// Dirty; indicates whether software has written to the 4-KByte page referenced by this entry.
         const D       = bit!(6);
+        /// Page attribute table; together with PCD and PWT selects the PAT entry
+        /// that determines the memory type of the 4-KByte page.
+        const PAT     = bit!(7);
         /// Global; if CR4.PGE = 1, determines whether the translation is global (see Section 4.10); ignored otherwise
         const G       = bit!(8);
         /// If IA32_EFER.NXE = 1, execute-disable
@@ -1578,7 +1860,13 @@ pub struct PTEntry(pub u64);
 
 impl fmt::Debug for PTEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "PTEntry {{ {:#x}, {:?} }}", self.address(), self.flags())
+        write!(
+            f,
+            "PTEntry {{ {:#x}, {:?}, raw: {:#x} }}",
+            self.address(),
+            self.flags(),
+            self.0
+        )
     }
 }
 
@@ -1589,10 +1877,10 @@ impl PTEntry {
     ///
     ///  * `page` - The physical address of the backing 4 KiB page.
     ///  * `flags`- Additional flags for the entry.
-    pub fn new(page: PAddr, flags: PTFlags) -> PTEntry {
-        let page_val = page & ADDRESS_MASK;
-        assert!(page_val == page.into());
-        assert!(page % BASE_PAGE_SIZE == 0);
+    pub const fn new(page: PAddr, flags: PTFlags) -> PTEntry {
+        let page_val = page.as_u64() & ADDRESS_MASK;
+        assert!(page_val == page.as_u64());
+        assert!(page.as_u64() & (BASE_PAGE_SIZE as u64 - 1) == 0);
         PTEntry(page_val | flags.bits)
     }
 
@@ -1633,12 +1921,960 @@ impl PTEntry {
                 is_global, PTFlags::G);
     check_flag!(doc = "If IA32_EFER.NXE = 1, execute-disable. If 1, instruction fetches are not allowed from the 4-KByte region.",
                 is_instruction_fetching_disabled, PTFlags::XD);
+
+    /// Are instruction fetches allowed from the region controlled by this entry (XD not set)?
+    pub fn is_executable(self) -> bool {
+        !self.flags().contains(PTFlags::XD)
+    }
+
+    /// Clears the accessed (A) bit, all other bits are preserved.
+    pub fn clear_accessed(&mut self) {
+        self.0 &= !PTFlags::A.bits;
+    }
+
+    /// Clears the dirty (D) bit, all other bits are preserved.
+    pub fn clear_dirty(&mut self) {
+        self.0 &= !PTFlags::D.bits;
+    }
+
+    /// Returns the protection key (bits 62:59) of the page.
+    ///
+    /// The key selects the access rights in PKRU (or IA32_PKRS for
+    /// supervisor pages), it is ignored unless CR4.PKE (CR4.PKS) is set.
+    pub fn protection_key(self) -> u8 {
+        ((self.0 >> PROTECTION_KEY_SHIFT) & 0xf) as u8
+    }
+
+    /// Sets the protection key (bits 62:59) of the page, `key` must be < 16.
+    pub fn set_protection_key(&mut self, key: u8) {
+        assert!(key < 16, "Protection keys are 4 bits");
+        self.0 = (self.0 & !(0xf << PROTECTION_KEY_SHIFT)) | (key as u64) << PROTECTION_KEY_SHIFT;
+    }
+
+    /// Sets the PAT, PCD and PWT bits to select `cache_type`, all other bits are preserved.
+    pub fn set_cache_type(&mut self, cache_type: PageCacheType) {
+        let mask = PTFlags::PAT | PTFlags::PCD | PTFlags::PWT;
+        self.0 = (self.0 & !mask.bits) | cache_type.pt_flags().bits;
+    }
+}
+
+/// The memory type of a 4 KiB page as selected by the PAT, PCD and PWT bits.
+///
+/// The bits form an index into the PAT (IA32_PAT MSR). WriteBack,
+/// WriteThrough and Uncacheable use PAT entries 0, 1 and 3 which hold
+/// these types after reset (and in the default PAT of all common kernels).
+/// There is no write-combining (or write-protected) entry after reset:
+/// WriteCombining uses PAT entry 4 (PAT set, PCD and PWT clear) which must
+/// be programmed to WC first (see [`Pat`](crate::msr::Pat)), otherwise
+/// the page is mapped write-back.
+///
+/// See Intel 3a, Section 11.12.3 "Selecting a Memory Type from the PAT".
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PageCacheType {
+    /// Write-back (PAT entry 0).
+    WriteBack,
+    /// Write-through (PAT entry 1, PWT).
+    WriteThrough,
+    /// Strong uncacheable (PAT entry 3, PCD and PWT).
+    Uncacheable,
+    /// Write-combining (PAT entry 4, PAT), needs a matching IA32_PAT setup.
+    WriteCombining,
+}
+
+impl PageCacheType {
+    /// The PAT, PCD and PWT flags of a PTEntry for this memory type.
+    pub fn pt_flags(self) -> PTFlags {
+        match self {
+            PageCacheType::WriteBack => PTFlags::empty(),
+            PageCacheType::WriteThrough => PTFlags::PWT,
+            PageCacheType::Uncacheable => PTFlags::PCD | PTFlags::PWT,
+            PageCacheType::WriteCombining => PTFlags::PAT,
+        }
+    }
+}
+
+/// Position of the protection key in a page-table entry.
+const PROTECTION_KEY_SHIFT: u64 = 59;
+
+/// The size of a page mapped by a page-table entry.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PageSize {
+    /// A 4 KiB base page (mapped by a PTEntry).
+    Base,
+    /// A 2 MiB large page (mapped by a PDEntry).
+    Large,
+    /// A 1 GiB huge page (mapped by a PDPTEntry).
+    Huge,
+}
+
+impl PageSize {
+    /// Size of the page in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            PageSize::Base => BASE_PAGE_SIZE,
+            PageSize::Large => LARGE_PAGE_SIZE,
+            PageSize::Huge => HUGE_PAGE_SIZE,
+        }
+    }
+}
+
+/// Walks the page-tables starting at `pml4` to translate `addr`.
+///
+/// Returns the physical address `addr` maps to along with the size of the
+/// page containing it, or `None` in case a non-present entry is encountered
+/// or `addr` is not canonical.
+///
+/// # Safety
+/// The physical addresses stored in the entries are dereferenced as is,
+/// so the referenced page-tables must be identity mapped.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn translate(pml4: &PML4, addr: VAddr) -> Option<(PAddr, PageSize)> {
+    if !addr.is_canonical() {
+        return None;
+    }
+    walk_pml4(pml4, addr)
+}
+
+/// Translates `addr` starting at `pml4` (without checking `addr`).
+#[cfg(target_arch = "x86_64")]
+unsafe fn walk_pml4(pml4: &PML4, addr: VAddr) -> Option<(PAddr, PageSize)> {
+    let pml4_entry = pml4[pml4_index(addr)];
+    if !pml4_entry.is_present() {
+        return None;
+    }
+
+    let pdpt = &*(pml4_entry.address().as_u64() as *const PDPT);
+    let pdpt_entry = pdpt[pdpt_index(addr)];
+    if !pdpt_entry.is_present() {
+        return None;
+    }
+    if pdpt_entry.is_page() {
+        return Some((
+            pdpt_entry.address() + addr.huge_page_offset(),
+            PageSize::Huge,
+        ));
+    }
+
+    let pd = &*(pdpt_entry.address().as_u64() as *const PD);
+    let pd_entry = pd[pd_index(addr)];
+    if !pd_entry.is_present() {
+        return None;
+    }
+    if pd_entry.is_page() {
+        return Some((
+            pd_entry.address() + addr.large_page_offset(),
+            PageSize::Large,
+        ));
+    }
+
+    let pt = &*(pd_entry.address().as_u64() as *const PT);
+    let pt_entry = pt[pt_index(addr)];
+    if !pt_entry.is_present() {
+        return None;
+    }
+    Some((pt_entry.address() + addr.base_page_offset(), PageSize::Base))
+}
+
+/// Walks the page-tables starting at `pml5` to translate `addr`
+/// (when 5-level paging is enabled).
+///
+/// Returns the physical address `addr` maps to along with the size of the
+/// page containing it, or `None` in case a non-present entry is encountered
+/// or `addr` is not canonical (with 5-level paging).
+///
+/// # Safety
+/// The physical addresses stored in the entries are dereferenced as is,
+/// so the referenced page-tables must be identity mapped.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn translate_pml5(pml5: &PML5, addr: VAddr) -> Option<(PAddr, PageSize)> {
+    if !addr.is_canonical_la57() {
+        return None;
+    }
+
+    let pml5_entry = pml5[pml5_index(addr)];
+    if !pml5_entry.is_present() {
+        return None;
+    }
+
+    let pml4 = &*(pml5_entry.address().as_u64() as *const PML4);
+    walk_pml4(pml4, addr)
+}
+
+/// Checks that `pml4` identity maps all addresses below `up_to`.
+///
+/// Walks the tables page by page (so a 512 GiB map of 1 GiB pages takes 512
+/// walks) and returns the first virtual address that is either not mapped or
+/// doesn't map to the same physical address. Only the lower half of the
+/// address space can be identity mapped, addresses from
+/// `0x0000_8000_0000_0000` on are reported as not mapped.
+///
+/// # Safety
+/// The physical addresses stored in the entries are dereferenced as is,
+/// so the referenced page-tables must be identity mapped.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn verify_identity(pml4: &PML4, up_to: PAddr) -> Result<(), VAddr> {
+    let mut addr = VAddr::from(0u64);
+    while addr.as_u64() < up_to.as_u64() {
+        match translate(pml4, addr) {
+            Some((paddr, size)) if paddr.as_u64() == addr.as_u64() => {
+                addr += size.size();
+            }
+            _ => return Err(addr),
+        }
+    }
+    Ok(())
+}
+
+/// Installs a recursive (self-referencing) entry at `recursive_index` in `pml4`.
+///
+/// Once the PML4 is loaded, all page-tables become accessible at fixed
+/// virtual addresses as given by [`recursive_pml4_address`],
+/// [`recursive_pdpt_address`], [`recursive_pd_address`] and
+/// [`recursive_pt_address`].
+///
+/// The entry uses the address of `pml4` so the table must be identity mapped.
+/// It doesn't set US and therefore the tables are only accessible from ring 0.
+#[cfg(target_arch = "x86_64")]
+pub fn recursive_mapping(pml4: &mut PML4, recursive_index: usize) {
+    let paddr = PAddr::from(pml4 as *const PML4 as u64);
+    pml4[recursive_index] = PML4Entry::new(paddr, PML4Flags::P | PML4Flags::RW);
+}
+
+/// Builds the (canonical) address that selects the four given indices.
+#[cfg(target_arch = "x86_64")]
+fn recursive_address(pml4: usize, pdpt: usize, pd: usize, pt: usize) -> VAddr {
+    assert!(pml4 < PAGE_SIZE_ENTRIES, "Invalid recursive index.");
+    let addr = (pml4 as u64) << 39 | (pdpt as u64) << 30 | (pd as u64) << 21 | (pt as u64) << 12;
+    VAddr::from(sign_extend(addr, 48))
+}
+
+/// Virtual address of the PML4 itself with a recursive entry at `recursive_index`.
+#[cfg(target_arch = "x86_64")]
+pub fn recursive_pml4_address(recursive_index: usize) -> VAddr {
+    let r = recursive_index;
+    recursive_address(r, r, r, r)
+}
+
+/// Virtual address of the PDPT that maps `addr` with a recursive entry at `recursive_index`.
+#[cfg(target_arch = "x86_64")]
+pub fn recursive_pdpt_address(recursive_index: usize, addr: VAddr) -> VAddr {
+    let r = recursive_index;
+    recursive_address(r, r, r, addr.pml4_index())
+}
+
+/// Virtual address of the PD that maps `addr` with a recursive entry at `recursive_index`.
+#[cfg(target_arch = "x86_64")]
+pub fn recursive_pd_address(recursive_index: usize, addr: VAddr) -> VAddr {
+    let r = recursive_index;
+    recursive_address(r, r, addr.pml4_index(), addr.pdpt_index())
+}
+
+/// Virtual address of the PT that maps `addr` with a recursive entry at `recursive_index`.
+#[cfg(target_arch = "x86_64")]
+pub fn recursive_pt_address(recursive_index: usize, addr: VAddr) -> VAddr {
+    recursive_address(
+        recursive_index,
+        addr.pml4_index(),
+        addr.pdpt_index(),
+        addr.pd_index(),
+    )
+}
+
+/// Hands out physical frames for new page-tables.
+pub trait FrameAllocator {
+    /// Returns a base page aligned, unused frame or `None` if no memory is left.
+    fn allocate_frame(&mut self) -> Option<PAddr>;
+
+    /// Returns `frame` (previously handed out by `allocate_frame`) to the allocator.
+    fn deallocate_frame(&mut self, frame: PAddr);
+}
+
+/// Allocates frames from a contiguous physical region by bumping a pointer.
+///
+/// Deallocated frames are never reused.
+#[derive(Debug)]
+pub struct BumpFrameAllocator {
+    next: PAddr,
+    end: PAddr,
+}
+
+impl BumpFrameAllocator {
+    /// Creates an allocator handing out the frames in `[start, start + size)`.
+    ///
+    /// # Panics
+    /// In case `start` or `size` are not base page aligned.
+    pub fn new(start: PAddr, size: usize) -> BumpFrameAllocator {
+        assert!(start.is_base_page_aligned());
+        assert!(size & (BASE_PAGE_SIZE - 1) == 0);
+        BumpFrameAllocator {
+            next: start,
+            end: start + size,
+        }
+    }
+
+    /// Number of frames that can still be allocated.
+    pub fn remaining(&self) -> usize {
+        (self.end - self.next).as_usize() / BASE_PAGE_SIZE
+    }
+}
+
+impl FrameAllocator for BumpFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PAddr> {
+        if self.next < self.end {
+            let frame = self.next;
+            self.next = self.next + BASE_PAGE_SIZE;
+            Some(frame)
+        } else {
+            None
+        }
+    }
+
+    fn deallocate_frame(&mut self, _frame: PAddr) {}
+}
+
+/// Index of the first PML4 entry of the kernel half.
+///
+/// By convention the lower half of the address space (PML4 entries 0..256,
+/// addresses `0x0000_0000_0000_0000..=0x0000_7fff_ffff_ffff`) belongs to user
+/// space and the upper half (entries 256..512, addresses
+/// `0xffff_8000_0000_0000..=0xffff_ffff_ffff_ffff`) to the kernel, which is
+/// shared by all address spaces.
+pub const KERNEL_HALF_START: usize = PAGE_SIZE_ENTRIES / 2;
+
+/// Creates a new PML4 sharing the kernel half of `pml4`.
+///
+/// The entries `KERNEL_HALF_START..PAGE_SIZE_ENTRIES` are copied as they are,
+/// so both tables reference the same lower-level tables; the user half of the
+/// new table is empty. Returns the frame of the new PML4 or `None` if `alloc`
+/// is out of frames.
+///
+/// A recursive entry in the kernel half still points to `pml4` and needs
+/// to be updated with [`recursive_mapping`] afterwards.
+///
+/// # Safety
+/// The frame returned by `alloc` must be identity mapped as it is written directly.
+pub unsafe fn clone_kernel_half(pml4: &PML4, alloc: &mut impl FrameAllocator) -> Option<PAddr> {
+    let frame = alloc.allocate_frame()?;
+    assert!(frame.is_base_page_aligned());
+
+    let table = &mut *(frame.as_u64() as *mut PML4);
+    table[..KERNEL_HALF_START].fill(PML4Entry(0));
+    table[KERNEL_HALF_START..].copy_from_slice(&pml4[KERNEL_HALF_START..]);
+    Some(frame)
+}
+
+/// Creates mappings in a set of page-tables, allocating intermediate
+/// tables on demand.
+///
+/// Intermediate tables are mapped with P, RW and US set; the final
+/// permissions are determined by the flags of the leaf entries.
+#[cfg(target_arch = "x86_64")]
+pub struct Mapper<'a> {
+    pml4: &'a mut PML4,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl<'a> Mapper<'a> {
+    /// Creates a new Mapper for the given `pml4` table.
+    ///
+    /// # Safety
+    /// The page-tables referenced by `pml4` as well as the frames handed out by
+    /// the allocators passed to the Mapper must be identity mapped as the
+    /// Mapper writes to them directly.
+    pub unsafe fn new(pml4: &'a mut PML4) -> Mapper<'a> {
+        Mapper { pml4 }
+    }
+
+    /// Allocates a new, zeroed page-table.
+    fn new_table(alloc: &mut impl FrameAllocator) -> PAddr {
+        let frame = alloc
+            .allocate_frame()
+            .expect("Out of frames for page-tables");
+        assert!(frame.is_base_page_aligned());
+        unsafe {
+            *(frame.as_u64() as *mut PT) = [PTEntry(0); PAGE_SIZE_ENTRIES];
+        }
+        frame
+    }
+
+    /// Returns the table stored in `frame`.
+    ///
+    /// The reference borrows the Mapper, so there is never more than one
+    /// live reference into the page-tables.
+    fn table<T>(&mut self, frame: PAddr) -> &mut T {
+        unsafe { &mut *(frame.as_u64() as *mut T) }
+    }
+
+    /// Returns the PDPT for `addr`, creates it in case it doesn't exist.
+    fn pdpt(&mut self, addr: VAddr, alloc: &mut impl FrameAllocator) -> &mut PDPT {
+        let idx = pml4_index(addr);
+        if !self.pml4[idx].is_present() {
+            let table = Mapper::new_table(alloc);
+            self.pml4[idx] = PML4Entry::new(table, PML4Flags::P | PML4Flags::RW | PML4Flags::US);
+        }
+        let frame = self.pml4[idx].address();
+        self.table(frame)
+    }
+
+    /// Returns the PD for `addr`, creates it in case it doesn't exist.
+    fn pd(&mut self, addr: VAddr, alloc: &mut impl FrameAllocator) -> &mut PD {
+        let frame = {
+            let pdpt = self.pdpt(addr, alloc);
+            let idx = pdpt_index(addr);
+            if !pdpt[idx].is_present() {
+                let table = Mapper::new_table(alloc);
+                pdpt[idx] = PDPTEntry::new(table, PDPTFlags::P | PDPTFlags::RW | PDPTFlags::US);
+            }
+            assert!(
+                !pdpt[idx].is_page(),
+                "{} is already mapped by a 1 GiB page",
+                addr
+            );
+            pdpt[idx].address()
+        };
+        self.table(frame)
+    }
+
+    /// Returns the PT for `addr`, creates it in case it doesn't exist.
+    fn pt(&mut self, addr: VAddr, alloc: &mut impl FrameAllocator) -> &mut PT {
+        let frame = {
+            let pd = self.pd(addr, alloc);
+            let idx = pd_index(addr);
+            if !pd[idx].is_present() {
+                let table = Mapper::new_table(alloc);
+                pd[idx] = PDEntry::new(table, PDFlags::P | PDFlags::RW | PDFlags::US);
+            }
+            assert!(
+                !pd[idx].is_page(),
+                "{} is already mapped by a 2 MiB page",
+                addr
+            );
+            pd[idx].address()
+        };
+        self.table(frame)
+    }
+
+    /// Removes the mapping of the page containing `virt` and invalidates
+    /// its TLB entry.
+    ///
+    /// Returns the frame that was mapped along with the size of the page,
+    /// or `None` in case `virt` wasn't mapped.
+    /// Intermediate tables are left in place, even if they become empty.
+    ///
+    /// # Safety
+    /// Needs CPL 0 (uses `invlpg`), and nothing may still access the page.
+    pub unsafe fn unmap(&mut self, virt: VAddr) -> Option<(PAddr, PageSize)> {
+        let unmapped = self.clear_entry(virt);
+        if unmapped.is_some() {
+            crate::tlb::flush(virt);
+        }
+        unmapped
+    }
+
+    /// Clears the leaf entry that maps `virt` without touching the TLB.
+    fn clear_entry(&mut self, virt: VAddr) -> Option<(PAddr, PageSize)> {
+        if !virt.is_canonical() {
+            return None;
+        }
+
+        let pml4_entry = self.pml4[pml4_index(virt)];
+        if !pml4_entry.is_present() {
+            return None;
+        }
+
+        let pdpt: &mut PDPT = self.table(pml4_entry.address());
+        let pdpt_entry = pdpt[pdpt_index(virt)];
+        if !pdpt_entry.is_present() {
+            return None;
+        }
+        if pdpt_entry.is_page() {
+            pdpt[pdpt_index(virt)] = PDPTEntry(0);
+            return Some((pdpt_entry.address(), PageSize::Huge));
+        }
+
+        let pd: &mut PD = self.table(pdpt_entry.address());
+        let pd_entry = pd[pd_index(virt)];
+        if !pd_entry.is_present() {
+            return None;
+        }
+        if pd_entry.is_page() {
+            pd[pd_index(virt)] = PDEntry(0);
+            return Some((pd_entry.address(), PageSize::Large));
+        }
+
+        let pt: &mut PT = self.table(pd_entry.address());
+        let pt_entry = pt[pt_index(virt)];
+        if !pt_entry.is_present() {
+            return None;
+        }
+        pt[pt_index(virt)] = PTEntry(0);
+        Some((pt_entry.address(), PageSize::Base))
+    }
+
+    /// Translates the flags of a 4 KiB page to the ones of a 1 GiB or 2 MiB page:
+    /// PAT is bit 7 in a PT entry but bit 12 in PDPT and PD entries (bit 7 is PS there).
+    fn large_page_flags(flags: PTFlags) -> u64 {
+        let bits = flags.bits & !PTFlags::PAT.bits;
+        if flags.contains(PTFlags::PAT) {
+            bits | PDFlags::PAT.bits
+        } else {
+            bits
+        }
+    }
+
+    /// Maps `size` bytes starting at `virt` to `phys`.
+    ///
+    /// Uses 1 GiB and 2 MiB pages whenever the alignment of both addresses
+    /// and the remaining size permit it, 4 KiB pages otherwise.
+    /// The P flag is always set; `flags` is translated to the corresponding
+    /// PDPTFlags and PDFlags for large pages.
+    ///
+    /// Intermediate page-tables are allocated from `alloc`.
+    ///
+    /// # Panics
+    /// In case the addresses or `size` are not base page aligned,
+    /// the virtual range is not canonical, `alloc` runs out of frames or
+    /// a large page would replace an existing lower-level table (or vice versa).
+    pub fn map_range(
+        &mut self,
+        virt: VAddr,
+        phys: PAddr,
+        size: usize,
+        flags: PTFlags,
+        alloc: &mut impl FrameAllocator,
+    ) {
+        assert!(virt.is_base_page_aligned());
+        assert!(phys.is_base_page_aligned());
+        assert!(size & (BASE_PAGE_SIZE - 1) == 0);
+        assert!(
+            virt.is_canonical() && (size == 0 || (virt + (size - 1)).is_canonical()),
+            "{:#x}..{:#x} is not a canonical range",
+            virt.as_u64(),
+            virt.as_u64().wrapping_add(size as u64)
+        );
+        let flags = flags | PTFlags::P;
+
+        let mut offset = 0;
+        while offset < size {
+            let (vaddr, paddr) = (virt + offset, phys + offset);
+            let remaining = size - offset;
+
+            if vaddr.is_huge_page_aligned()
+                && paddr.is_huge_page_aligned()
+                && remaining >= HUGE_PAGE_SIZE
+            {
+                let pdpt = self.pdpt(vaddr, alloc);
+                let idx = pdpt_index(vaddr);
+                assert!(
+                    !pdpt[idx].is_present() || pdpt[idx].is_page(),
+                    "{} is already mapped through a page directory",
+                    vaddr
+                );
+                let flags = PDPTFlags::from_bits_truncate(Mapper::large_page_flags(flags));
+                pdpt[idx] = PDPTEntry::new_huge_page(paddr, flags);
+                offset += HUGE_PAGE_SIZE;
+            } else if vaddr.is_large_page_aligned()
+                && paddr.is_large_page_aligned()
+                && remaining >= LARGE_PAGE_SIZE
+            {
+                let pd = self.pd(vaddr, alloc);
+                let idx = pd_index(vaddr);
+                assert!(
+                    !pd[idx].is_present() || pd[idx].is_page(),
+                    "{} is already mapped through a page table",
+                    vaddr
+                );
+                let flags = PDFlags::from_bits_truncate(Mapper::large_page_flags(flags));
+                pd[idx] = PDEntry::new_large_page(paddr, flags);
+                offset += LARGE_PAGE_SIZE;
+            } else {
+                let pt = self.pt(vaddr, alloc);
+                pt[pt_index(vaddr)] = PTEntry::new(paddr, flags);
+                offset += BASE_PAGE_SIZE;
+            }
+        }
+    }
 }
 
 #[cfg(all(test, feature = "utest"))]
 mod test {
     use super::*;
 
+    #[test]
+    fn addr_arithmetic() {
+        let paddr = PAddr::from(0x1fffu64);
+        assert_eq!(paddr.align_up(0x1000u64), PAddr::from(0x2000u64));
+        assert_eq!(paddr.align_down(0x1000u64), PAddr::from(0x1000u64));
+        assert!(!paddr.is_aligned(0x1000u64));
+        assert!(paddr.align_up(0x1000u64).is_aligned(0x1000u64));
+        assert_eq!(paddr + 1usize, PAddr::from(0x2000u64));
+        assert_eq!(paddr - PAddr::from(0xfffu64), PAddr::from(0x1000u64));
+        assert_eq!(paddr & 0xf00u64, 0xf00);
+        assert_eq!((paddr + 1usize).as_u64(), 0x2000);
+
+        let vaddr = VAddr::from(0x1fffu64);
+        assert_eq!(vaddr.align_up(0x1000u64), VAddr::from(0x2000u64));
+        assert_eq!(vaddr.align_down(0x1000u64), VAddr::from(0x1000u64));
+        assert!(!vaddr.is_aligned(0x1000u64));
+        assert!(vaddr.align_down(0x1000u64).is_aligned(0x1000u64));
+        assert_eq!(vaddr + 1usize, VAddr::from(0x2000u64));
+        assert_eq!(vaddr - VAddr::from(0xfffu64), VAddr::from(0x1000u64));
+        assert_eq!(vaddr & 0xf00u64, VAddr::from(0xf00u64));
+        assert_eq!((vaddr + 1usize).as_usize(), 0x2000);
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn addr_align_up_overflow() {
+        PAddr::from(u64::max_value()).align_up(0x1000u64);
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn addr_align_not_power_of_two() {
+        VAddr::from(0x1000u64).align_down(0x3u64);
+    }
+
+    #[test]
+    fn huge_and_large_page_entries() {
+        let pdpt_entry = PDPTEntry::new_huge_page(
+            PAddr::from(0x4000_0000u64),
+            PDPTFlags::P | PDPTFlags::RW | PDPTFlags::PAT,
+        );
+        assert!(pdpt_entry.is_page());
+        assert!(pdpt_entry.is_pat());
+        assert_eq!(pdpt_entry.0, 0x4000_1083);
+        assert_eq!(pdpt_entry.address(), PAddr::from(0x4000_0000u64));
+
+        let pd_entry = PDEntry::new_large_page(
+            PAddr::from(0x20_0000u64),
+            PDFlags::P | PDFlags::PAT | PDFlags::XD,
+        );
+        assert!(pd_entry.is_page());
+        assert!(pd_entry.is_pat());
+        assert_eq!(pd_entry.0, 0x8000_0000_0020_1081);
+        assert_eq!(pd_entry.address(), PAddr::from(0x20_0000u64));
+
+        // Without PS the entry references a table:
+        let pd_entry = PDEntry::new(PAddr::from(0x1000u64), PDFlags::P);
+        assert!(!pd_entry.is_page());
+        assert_eq!(pd_entry.address(), PAddr::from(0x1000u64));
+    }
+
+    #[test]
+    fn huge_page_entry_misaligned() {
+        // Bit 12 of the frame would otherwise end up in the PAT bit:
+        let entry = PDPTEntry::new_huge_page(PAddr::from(0x4000_1000u64), PDPTFlags::P);
+        assert_eq!(
+            entry.0,
+            0x4000_0000 | PDPTFlags::P.bits() | PDPTFlags::PS.bits()
+        );
+        assert_eq!(entry.address(), PAddr::from(0x4000_0000u64));
+        assert!(entry.address().is_huge_page_aligned());
+        assert!(!entry.flags().contains(PDPTFlags::PAT));
+    }
+
+    #[test]
+    fn large_page_entry_misaligned() {
+        let entry = PDEntry::new_large_page(PAddr::from(0x20_1000u64), PDFlags::P);
+        assert_eq!(entry.0, 0x20_0000 | PDFlags::P.bits() | PDFlags::PS.bits());
+        assert!(entry.address().is_large_page_aligned());
+        assert!(!entry.flags().contains(PDFlags::PAT));
+    }
+
+    #[test]
+    #[should_panic]
+    fn huge_page_entry_beyond_maxphyaddr() {
+        PDPTEntry::new_huge_page(PAddr::from(1u64 << 52), PDPTFlags::P);
+    }
+
+    #[test]
+    fn execute_disable() {
+        let pt_entry = PTEntry::new(PAddr::from(0x1000u64), PTFlags::P | PTFlags::XD);
+        assert_eq!(pt_entry.0, 1 << 63 | 0x1001);
+        assert!(!pt_entry.is_executable());
+        assert!(pt_entry.is_instruction_fetching_disabled());
+
+        let pt_entry = PTEntry::new(PAddr::from(0x1000u64), PTFlags::P);
+        assert!(pt_entry.is_executable());
+
+        assert!(!PML4Entry::new(PAddr::from(0x1000u64), PML4Flags::XD).is_executable());
+        assert!(!PDPTEntry::new(PAddr::from(0x1000u64), PDPTFlags::XD).is_executable());
+        assert!(!PDEntry::new(PAddr::from(0x1000u64), PDFlags::XD).is_executable());
+    }
+
+    #[test]
+    fn entry_debug_format() {
+        extern crate std;
+        use std::format;
+
+        let pt_entry = PTEntry::new(PAddr::from(0x1000u64), PTFlags::P | PTFlags::RW);
+        assert_eq!(
+            format!("{:?}", pt_entry),
+            "PTEntry { 0x1000, P | RW, raw: 0x1003 }"
+        );
+
+        let pdpt_entry = PDPTEntry::new_huge_page(
+            PAddr::from(0x4000_0000u64),
+            PDPTFlags::P | PDPTFlags::RW | PDPTFlags::XD,
+        );
+        let formatted = format!("{:?}", pdpt_entry);
+        assert!(formatted.contains("0x40000000"));
+        assert!(formatted.contains("P | RW | PS | XD"));
+        assert!(formatted.contains("raw: 0x8000000040000083"));
+    }
+
+    #[test]
+    fn protection_key() {
+        let mut entry = PTEntry::new(
+            PAddr::from(0xdead_b000u64),
+            PTFlags::P | PTFlags::RW | PTFlags::XD,
+        );
+        assert_eq!(entry.protection_key(), 0);
+
+        entry.set_protection_key(0xf);
+        assert_eq!(entry.protection_key(), 0xf);
+        assert_eq!(entry.0, 0xf800_0000_dead_b003);
+        assert_eq!(entry.address(), PAddr::from(0xdead_b000u64));
+        assert_eq!(entry.flags(), PTFlags::P | PTFlags::RW | PTFlags::XD);
+
+        entry.set_protection_key(0x5);
+        assert_eq!(entry.protection_key(), 0x5);
+        assert_eq!(entry.0, 0xa800_0000_dead_b003);
+    }
+
+    #[test]
+    #[should_panic]
+    fn protection_key_too_large() {
+        PTEntry::new(PAddr::from(0x1000u64), PTFlags::P).set_protection_key(16);
+    }
+
+    #[test]
+    fn accessed_dirty() {
+        let mut pt_entry = PTEntry::new(
+            PAddr::from(0x1000u64),
+            PTFlags::P | PTFlags::RW | PTFlags::A | PTFlags::D | PTFlags::XD,
+        );
+        assert!(pt_entry.is_accessed());
+        assert!(pt_entry.is_dirty());
+        pt_entry.clear_accessed();
+        assert!(!pt_entry.is_accessed());
+        assert!(pt_entry.is_dirty());
+        assert_eq!(
+            pt_entry.flags(),
+            PTFlags::P | PTFlags::RW | PTFlags::D | PTFlags::XD
+        );
+        assert_eq!(pt_entry.address(), PAddr::from(0x1000u64));
+        pt_entry.clear_dirty();
+        assert!(!pt_entry.is_dirty());
+        assert_eq!(pt_entry.flags(), PTFlags::P | PTFlags::RW | PTFlags::XD);
+
+        let mut pd_entry = PDEntry::new_large_page(
+            PAddr::from(0x20_0000u64),
+            PDFlags::P | PDFlags::A | PDFlags::D,
+        );
+        pd_entry.clear_accessed();
+        assert!(!pd_entry.is_accessed());
+        assert!(pd_entry.is_dirty());
+        assert!(pd_entry.is_page());
+
+        let mut pdpt_entry = PDPTEntry::new_huge_page(
+            PAddr::from(0x4000_0000u64),
+            PDPTFlags::P | PDPTFlags::A | PDPTFlags::D,
+        );
+        pdpt_entry.clear_accessed();
+        assert!(!pdpt_entry.is_accessed());
+        assert!(pdpt_entry.is_dirty());
+        pdpt_entry.clear_dirty();
+        assert_eq!(pdpt_entry.flags(), PDPTFlags::P | PDPTFlags::PS);
+    }
+
+    #[test]
+    fn present_entries() {
+        let mut pt = [PTEntry(0); PAGE_SIZE_ENTRIES];
+        pt[0] = PTEntry::new(PAddr::from(0x1000), PTFlags::P);
+        pt[42] = PTEntry::new(PAddr::from(0x2000), PTFlags::P | PTFlags::RW);
+        pt[511] = PTEntry::new(PAddr::from(0x3000), PTFlags::P | PTFlags::XD);
+        // Not present:
+        pt[100] = PTEntry::new(PAddr::from(0x4000), PTFlags::RW);
+
+        let mut present = pt.present_entries();
+        assert_eq!(
+            present.next().map(|(i, e)| (i, e.address())),
+            Some((0, PAddr::from(0x1000)))
+        );
+        assert_eq!(
+            present.next().map(|(i, e)| (i, e.address())),
+            Some((42, PAddr::from(0x2000)))
+        );
+        assert_eq!(
+            present.next().map(|(i, e)| (i, e.address())),
+            Some((511, PAddr::from(0x3000)))
+        );
+        assert!(present.next().is_none());
+
+        let pml4 = [PML4Entry(0); PAGE_SIZE_ENTRIES];
+        assert_eq!(pml4.present_entries().count(), 0);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn translate_pml5_identity_map() {
+        let mut pml5 = AlignedTable([PML5Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut pdpt = AlignedTable([PDPTEntry(0); PAGE_SIZE_ENTRIES]);
+
+        pdpt.0[0] = PDPTEntry::new_huge_page(PAddr::from(0x4000_0000u64), PDPTFlags::P);
+        pml4.0[0] = PML4Entry::new(
+            PAddr::from(&pdpt as *const _ as u64),
+            PML4Flags::P | PML4Flags::RW,
+        );
+        let pml4_addr = PAddr::from(&pml4 as *const _ as u64);
+        pml5.0[1] = PML5Entry::new(pml4_addr, PML5Flags::P | PML5Flags::RW | PML5Flags::US);
+
+        assert_eq!(pml5.0[1].address(), pml4_addr);
+        assert_eq!(
+            pml5.0[1].flags(),
+            PML5Flags::P | PML5Flags::RW | PML5Flags::US
+        );
+        assert_eq!(pml5.0[1].0, pml4_addr.as_u64() | 0b111);
+        assert_eq!(pml5_index(VAddr::from(1u64 << 48)), 1);
+        assert_eq!(pml5_index(VAddr::from(0x1ff_0000_0000_0000u64)), 511);
+
+        unsafe {
+            assert_eq!(
+                translate_pml5(&pml5.0, VAddr::from((1u64 << 48) | 0x1234)),
+                Some((PAddr::from(0x4000_1234u64), PageSize::Huge))
+            );
+            assert_eq!(translate_pml5(&pml5.0, VAddr::from(0x1234u64)), None);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn mapper_map_range() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut frames = AlignedTable([[0u8; BASE_PAGE_SIZE]; 8]);
+        let mut alloc = BumpFrameAllocator::new(
+            PAddr::from(frames.0.as_mut_ptr() as u64),
+            8 * BASE_PAGE_SIZE,
+        );
+
+        let mut mapper = unsafe { Mapper::new(&mut pml4.0) };
+        // 4 MiB with 2 MiB pages
+        mapper.map_range(
+            VAddr::from(0x4000_0000u64),
+            PAddr::from(0x20_0000u64),
+            4 * 1024 * 1024,
+            PTFlags::RW,
+            &mut alloc,
+        );
+        // 4 MiB with 4 KiB pages (crossing three page tables)
+        mapper.map_range(
+            VAddr::from(0x1000u64),
+            PAddr::from(0x10_1000u64),
+            4 * 1024 * 1024,
+            PTFlags::RW,
+            &mut alloc,
+        );
+        // A single 1 GiB page
+        mapper.map_range(
+            VAddr::from(0x80_0000_0000u64),
+            PAddr::from(0x4000_0000u64),
+            HUGE_PAGE_SIZE,
+            PTFlags::RW | PTFlags::XD,
+            &mut alloc,
+        );
+        // PDPT, PD, three PTs, PD for the 2 MiB pages and PDPT for the 1 GiB page:
+        assert_eq!(alloc.remaining(), 1);
+
+        unsafe {
+            let pml4 = &pml4.0;
+            assert_eq!(
+                translate(pml4, VAddr::from(0x4000_0000u64)),
+                Some((PAddr::from(0x20_0000u64), PageSize::Large))
+            );
+            assert_eq!(
+                translate(pml4, VAddr::from(0x403f_fff8u64)),
+                Some((PAddr::from(0x5f_fff8u64), PageSize::Large))
+            );
+            assert_eq!(translate(pml4, VAddr::from(0x4040_0000u64)), None);
+
+            assert_eq!(translate(pml4, VAddr::from(0x0u64)), None);
+            assert_eq!(
+                translate(pml4, VAddr::from(0x1000u64)),
+                Some((PAddr::from(0x10_1000u64), PageSize::Base))
+            );
+            assert_eq!(
+                translate(pml4, VAddr::from(0x40_0ff8u64)),
+                Some((PAddr::from(0x50_0ff8u64), PageSize::Base))
+            );
+            assert_eq!(translate(pml4, VAddr::from(0x40_1000u64)), None);
+
+            assert_eq!(
+                translate(pml4, VAddr::from(0x80_1234_5678u64)),
+                Some((PAddr::from(0x5234_5678u64), PageSize::Huge))
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn translate_identity_map() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut pdpt = AlignedTable([PDPTEntry(0); PAGE_SIZE_ENTRIES]);
+        let mut pd = AlignedTable([PDEntry(0); PAGE_SIZE_ENTRIES]);
+        let mut pt = AlignedTable([PTEntry(0); PAGE_SIZE_ENTRIES]);
+
+        // 4 KiB pages at 0x1000 only
+        pt.0[1] = PTEntry::new(PAddr::from(0x1000), PTFlags::P | PTFlags::RW);
+        // [0, 2 MiB) is mapped through a page table
+        pd.0[0] = PDEntry::new(
+            PAddr::from(&pt as *const _ as u64),
+            PDFlags::P | PDFlags::RW,
+        );
+        // [2 MiB, 4 MiB) is a 2 MiB page
+        pd.0[1] = PDEntry::new(
+            PAddr::from(LARGE_PAGE_SIZE),
+            PDFlags::P | PDFlags::RW | PDFlags::PS,
+        );
+        // [0, 1 GiB) is mapped through a page directory
+        pdpt.0[0] = PDPTEntry::new(
+            PAddr::from(&pd as *const _ as u64),
+            PDPTFlags::P | PDPTFlags::RW,
+        );
+        // [1 GiB, 2 GiB) is a 1 GiB page
+        pdpt.0[1] = PDPTEntry::new(
+            PAddr::from(HUGE_PAGE_SIZE),
+            PDPTFlags::P | PDPTFlags::RW | PDPTFlags::PS,
+        );
+        pml4.0[0] = PML4Entry::new(
+            PAddr::from(&pdpt as *const _ as u64),
+            PML4Flags::P | PML4Flags::RW,
+        );
+
+        unsafe {
+            assert_eq!(
+                translate(&pml4.0, VAddr::from(0x1234)),
+                Some((PAddr::from(0x1234), PageSize::Base))
+            );
+            assert_eq!(translate(&pml4.0, VAddr::from(0x2000)), None);
+            assert_eq!(
+                translate(&pml4.0, VAddr::from(0x20_1234)),
+                Some((PAddr::from(0x20_1234), PageSize::Large))
+            );
+            assert_eq!(translate(&pml4.0, VAddr::from(0x40_0000)), None);
+            assert_eq!(
+                translate(&pml4.0, VAddr::from(0x7fff_1234u64)),
+                Some((PAddr::from(0x7fff_1234u64), PageSize::Huge))
+            );
+            assert_eq!(translate(&pml4.0, VAddr::from(0x8000_0000u64)), None);
+            assert_eq!(translate(&pml4.0, VAddr::from(0x80_0000_0000u64)), None);
+        }
+    }
+
     #[test]
     fn paddr_align() {
         let base = PAddr::from(0x1000);
@@ -1789,6 +3025,451 @@ mod test {
         assert!(!base.is_aligned(0x4u64));
     }
 
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn vaddr_indices() {
+        // 0xffff_8123_4567_89ab: PML4 258, PDPT 141, PD 43, PT 120, offset 0x9ab
+        let addr = VAddr::from(0xffff_8123_4567_89abu64);
+        assert_eq!(addr.pml4_index(), 258);
+        assert_eq!(addr.pdpt_index(), 141);
+        assert_eq!(addr.pd_index(), 43);
+        assert_eq!(addr.pt_index(), 120);
+        assert_eq!(addr.page_offset(), 0x9ab);
+        assert_eq!(addr.pml5_index(), 511);
+
+        // Same as the functions used by `translate`:
+        assert_eq!(addr.pml4_index(), pml4_index(addr));
+        assert_eq!(addr.pdpt_index(), pdpt_index(addr));
+        assert_eq!(addr.pd_index(), pd_index(addr));
+        assert_eq!(addr.pt_index(), pt_index(addr));
+
+        // Reassemble the address from its parts:
+        let rebuilt = (addr.pml4_index() as u64) << 39
+            | (addr.pdpt_index() as u64) << 30
+            | (addr.pd_index() as u64) << 21
+            | (addr.pt_index() as u64) << 12
+            | addr.page_offset();
+        assert_eq!(VAddr::from(rebuilt | 0xffff_0000_0000_0000), addr);
+
+        let last = VAddr::from(0xffff_ffff_ffff_ffffu64);
+        assert_eq!(last.pml4_index(), 511);
+        assert_eq!(last.pt_index(), 511);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn recursive_addresses() {
+        let addr = VAddr::from(0xffff_8123_4567_89abu64);
+        assert_eq!(
+            recursive_pml4_address(510),
+            VAddr::from(0xffff_ff7f_bfdf_e000u64)
+        );
+        assert_eq!(
+            recursive_pdpt_address(510, addr),
+            VAddr::from(0xffff_ff7f_bfd0_2000u64)
+        );
+        assert_eq!(
+            recursive_pd_address(510, addr),
+            VAddr::from(0xffff_ff7f_a048_d000u64)
+        );
+        assert_eq!(
+            recursive_pt_address(510, addr),
+            VAddr::from(0xffff_ff40_91a2_b000u64)
+        );
+
+        // The tables for the first 2 MiB:
+        let zero = VAddr::zero();
+        assert_eq!(
+            recursive_pdpt_address(510, zero),
+            VAddr::from(0xffff_ff7f_bfdf_e000u64 - 510 * 0x1000)
+        );
+        assert_eq!(
+            recursive_pt_address(510, zero),
+            VAddr::from(0xffff_ff00_0000_0000u64)
+        );
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn recursive_mapping_translate() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        recursive_mapping(&mut pml4.0, 510);
+
+        let pml4_addr = PAddr::from(&pml4.0 as *const PML4 as u64);
+        assert_eq!(pml4.0[510].address(), pml4_addr);
+        assert_eq!(pml4.0[510].flags(), PML4Flags::P | PML4Flags::RW);
+
+        // The walk ends up at the PML4 itself:
+        unsafe {
+            assert_eq!(
+                translate(&pml4.0, recursive_pml4_address(510) + 0x18usize),
+                Some((pml4_addr + 0x18u64, PageSize::Base))
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn identity_map_verification() {
+        let mut pdpt = AlignedTable([PDPTEntry(0); PAGE_SIZE_ENTRIES]);
+        for (i, entry) in pdpt.iter_mut().enumerate() {
+            let frame = PAddr::from(i as u64 * HUGE_PAGE_SIZE as u64);
+            *entry = PDPTEntry::new_huge_page(frame, PDPTFlags::P | PDPTFlags::RW);
+        }
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let pdpt_addr = PAddr::from(&pdpt.0 as *const PDPT as u64);
+        pml4[0] = PML4Entry::new(pdpt_addr, PML4Flags::P | PML4Flags::RW);
+
+        let end = PAddr::from(512 * HUGE_PAGE_SIZE as u64);
+        unsafe {
+            assert_eq!(verify_identity(&pml4, end), Ok(()));
+            // Nothing is mapped beyond the first 512 GiB:
+            assert_eq!(
+                verify_identity(&pml4, end + 1u64),
+                Err(VAddr::from(end.as_u64()))
+            );
+
+            // Map the 4th GiB to the wrong frame:
+            pdpt[3] = PDPTEntry::new_huge_page(PAddr::from(0u64), PDPTFlags::P | PDPTFlags::RW);
+            assert_eq!(
+                verify_identity(&pml4, end),
+                Err(VAddr::from(3 * HUGE_PAGE_SIZE as u64))
+            );
+            // Only the range up to `up_to` is checked:
+            assert_eq!(
+                verify_identity(&pml4, PAddr::from(3 * HUGE_PAGE_SIZE as u64)),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn page_cache_types() {
+        let frame = PAddr::from(0x1234_5000u64);
+        let flags = PTFlags::P | PTFlags::RW | PTFlags::XD;
+        let bits = |cache_type| {
+            let mut entry = PTEntry::new(frame, flags | PTFlags::PCD);
+            entry.set_cache_type(cache_type);
+            assert_eq!(entry.address(), frame);
+            assert!(entry.flags().contains(flags));
+            entry.flags() & (PTFlags::PAT | PTFlags::PCD | PTFlags::PWT)
+        };
+
+        assert_eq!(bits(PageCacheType::WriteBack), PTFlags::empty());
+        assert_eq!(bits(PageCacheType::WriteThrough), PTFlags::PWT);
+        assert_eq!(
+            bits(PageCacheType::Uncacheable),
+            PTFlags::PCD | PTFlags::PWT
+        );
+        assert_eq!(bits(PageCacheType::WriteCombining), PTFlags::PAT);
+        assert_eq!(PTFlags::PAT.bits(), 1 << 7);
+    }
+
+    #[test]
+    fn canonical_addresses() {
+        assert!(VAddr::from(0x0u64).is_canonical());
+        assert!(VAddr::from(0x0000_7fff_ffff_ffffu64).is_canonical());
+        assert!(!VAddr::from(0x0000_8000_0000_0000u64).is_canonical());
+        assert!(!VAddr::from(0xffff_7fff_ffff_ffffu64).is_canonical());
+        assert!(VAddr::from(0xffff_8000_0000_0000u64).is_canonical());
+        assert!(VAddr::from(0xffff_ffff_ffff_ffffu64).is_canonical());
+
+        assert!(VAddr::from(0x0000_8000_0000_0000u64).is_canonical_la57());
+        assert!(!VAddr::from(0x0100_0000_0000_0000u64).is_canonical_la57());
+        assert!(VAddr::from(0xff00_0000_0000_0000u64).is_canonical_la57());
+
+        assert_eq!(
+            VAddr::try_new(0x0000_7fff_ffff_ffff),
+            Ok(VAddr::from(0x0000_7fff_ffff_ffffu64))
+        );
+        assert_eq!(
+            VAddr::try_new(0x0000_8000_0000_0000),
+            Err(NonCanonicalAddr(0x0000_8000_0000_0000))
+        );
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn translate_non_canonical() {
+        let mut pdpt = AlignedTable([PDPTEntry(0); PAGE_SIZE_ENTRIES]);
+        pdpt.0[0] = PDPTEntry::new_huge_page(PAddr::from(0x0u64), PDPTFlags::P | PDPTFlags::RW);
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let pdpt_addr = PAddr::from(&pdpt.0 as *const PDPT as u64);
+        // Only bits 47:39 select the PML4 entry, so this maps 0xffff_0000_0000_0000 too:
+        pml4.0[0] = PML4Entry::new(pdpt_addr, PML4Flags::P | PML4Flags::RW);
+
+        unsafe {
+            assert_eq!(
+                translate(&pml4.0, VAddr::from(0x1000u64)),
+                Some((PAddr::from(0x1000u64), PageSize::Huge))
+            );
+            assert_eq!(
+                translate(&pml4.0, VAddr::from(0xffff_0000_0000_1000u64)),
+                None
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn mapper_unmap() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut frames = AlignedTable([[0u8; BASE_PAGE_SIZE]; 4]);
+        let mut alloc = BumpFrameAllocator::new(
+            PAddr::from(frames.0.as_mut_ptr() as u64),
+            4 * BASE_PAGE_SIZE,
+        );
+        let mut mapper = unsafe { Mapper::new(&mut pml4.0) };
+        mapper.map_range(
+            VAddr::from(0x20_0000u64),
+            PAddr::from(0x40_0000u64),
+            LARGE_PAGE_SIZE + BASE_PAGE_SIZE,
+            PTFlags::RW,
+            &mut alloc,
+        );
+
+        // `unmap` needs CPL 0 for invlpg, so test the part that edits the tables:
+        assert_eq!(
+            mapper.clear_entry(VAddr::from(0x40_0123u64)),
+            Some((PAddr::from(0x60_0000u64), PageSize::Base))
+        );
+        assert_eq!(
+            mapper.clear_entry(VAddr::from(0x2f_f000u64)),
+            Some((PAddr::from(0x40_0000u64), PageSize::Large))
+        );
+        assert_eq!(mapper.clear_entry(VAddr::from(0x40_0000u64)), None);
+        assert_eq!(mapper.clear_entry(VAddr::from(0x20_0000u64)), None);
+        assert_eq!(mapper.clear_entry(VAddr::from(0x8000_0000u64)), None);
+
+        unsafe {
+            assert_eq!(translate(&pml4.0, VAddr::from(0x40_0000u64)), None);
+            assert_eq!(translate(&pml4.0, VAddr::from(0x20_0000u64)), None);
+            // The PDPT, PD and PT are not freed:
+            assert!(pml4.0[0].is_present());
+        }
+        assert_eq!(alloc.remaining(), 1);
+    }
+
+    #[test]
+    fn kernel_half_clone() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        pml4.0[0] = PML4Entry::new(PAddr::from(0x1000u64), PML4Flags::P | PML4Flags::US);
+        pml4.0[255] = PML4Entry::new(PAddr::from(0x2000u64), PML4Flags::P | PML4Flags::US);
+        pml4.0[256] = PML4Entry::new(PAddr::from(0x3000u64), PML4Flags::P | PML4Flags::RW);
+        pml4.0[511] = PML4Entry::new(PAddr::from(0x4000u64), PML4Flags::P | PML4Flags::RW);
+
+        let mut frame = AlignedTable([PML4Entry(0xdead_beef); PAGE_SIZE_ENTRIES]);
+        let mut alloc = BumpFrameAllocator::new(
+            PAddr::from(&mut frame.0 as *mut PML4 as u64),
+            BASE_PAGE_SIZE,
+        );
+
+        let clone = unsafe { clone_kernel_half(&pml4.0, &mut alloc) };
+        assert_eq!(clone, Some(PAddr::from(&frame.0 as *const PML4 as u64)));
+        assert!(frame.0[..KERNEL_HALF_START].iter().all(|e| e.0 == 0));
+        assert_eq!(frame.0[256].address(), PAddr::from(0x3000u64));
+        assert_eq!(frame.0[511].address(), PAddr::from(0x4000u64));
+        assert!(frame.0[KERNEL_HALF_START..]
+            .iter()
+            .zip(pml4.0[KERNEL_HALF_START..].iter())
+            .all(|(a, b)| a.0 == b.0));
+
+        assert_eq!(unsafe { clone_kernel_half(&pml4.0, &mut alloc) }, None);
+    }
+
+    #[test]
+    fn bump_frame_allocator() {
+        let mut alloc = BumpFrameAllocator::new(PAddr::from(0x10_0000u64), 2 * BASE_PAGE_SIZE);
+        assert_eq!(alloc.remaining(), 2);
+        assert_eq!(alloc.allocate_frame(), Some(PAddr::from(0x10_0000u64)));
+        assert_eq!(alloc.allocate_frame(), Some(PAddr::from(0x10_1000u64)));
+        assert_eq!(alloc.remaining(), 0);
+        assert_eq!(alloc.allocate_frame(), None);
+
+        // Freed frames are not handed out again:
+        alloc.deallocate_frame(PAddr::from(0x10_0000u64));
+        assert_eq!(alloc.allocate_frame(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Out of frames")]
+    #[cfg(target_arch = "x86_64")]
+    fn mapper_out_of_frames() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut frames = AlignedTable([[0u8; BASE_PAGE_SIZE]; 2]);
+        let mut alloc = BumpFrameAllocator::new(
+            PAddr::from(frames.0.as_mut_ptr() as u64),
+            2 * BASE_PAGE_SIZE,
+        );
+        let mut mapper = unsafe { Mapper::new(&mut pml4.0) };
+        // Needs a PDPT, a PD and a PT:
+        mapper.map_range(
+            VAddr::from(0x1000u64),
+            PAddr::from(0x1000u64),
+            BASE_PAGE_SIZE,
+            PTFlags::RW,
+            &mut alloc,
+        );
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn mapper_large_page_pat() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut frames = AlignedTable([[0u8; BASE_PAGE_SIZE]; 2]);
+        let mut alloc = BumpFrameAllocator::new(
+            PAddr::from(frames.0.as_mut_ptr() as u64),
+            2 * BASE_PAGE_SIZE,
+        );
+        let mut mapper = unsafe { Mapper::new(&mut pml4.0) };
+        mapper.map_range(
+            VAddr::from(0x20_0000u64),
+            PAddr::from(0x20_0000u64),
+            LARGE_PAGE_SIZE,
+            PageCacheType::WriteCombining.pt_flags() | PTFlags::RW,
+            &mut alloc,
+        );
+
+        let pd = unsafe { &*(frames.0[1].as_ptr() as *const PD) };
+        let entry = pd[1];
+        assert!(entry.is_page());
+        assert_eq!(entry.0 & bit!(12), bit!(12));
+        assert!(entry.flags().contains(PDFlags::PAT));
+        assert_eq!(entry.address(), PAddr::from(0x20_0000u64));
+    }
+
+    #[test]
+    #[should_panic(expected = "already mapped through a page table")]
+    #[cfg(target_arch = "x86_64")]
+    fn mapper_large_page_over_table() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut frames = AlignedTable([[0u8; BASE_PAGE_SIZE]; 3]);
+        let mut alloc = BumpFrameAllocator::new(
+            PAddr::from(frames.0.as_mut_ptr() as u64),
+            3 * BASE_PAGE_SIZE,
+        );
+        let mut mapper = unsafe { Mapper::new(&mut pml4.0) };
+        mapper.map_range(
+            VAddr::from(0x20_0000u64),
+            PAddr::from(0x20_0000u64),
+            BASE_PAGE_SIZE,
+            PTFlags::RW,
+            &mut alloc,
+        );
+        // Would leak the PT and drop the 4 KiB mapping:
+        mapper.map_range(
+            VAddr::from(0x20_0000u64),
+            PAddr::from(0x40_0000u64),
+            LARGE_PAGE_SIZE,
+            PTFlags::RW,
+            &mut alloc,
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(target_arch = "x86_64")]
+    fn map_range_non_canonical() {
+        let mut pml4 = AlignedTable([PML4Entry(0); PAGE_SIZE_ENTRIES]);
+        let mut alloc = BumpFrameAllocator::new(PAddr::zero(), 0);
+        let mut mapper = unsafe { Mapper::new(&mut pml4.0) };
+        // Runs into the non-canonical hole:
+        mapper.map_range(
+            VAddr::from(0x0000_7fff_ffff_f000u64),
+            PAddr::from(0x0u64),
+            2 * BASE_PAGE_SIZE,
+            PTFlags::RW,
+            &mut alloc,
+        );
+    }
+
+    #[test]
+    fn vaddr_pointers() {
+        let mut value = 0xdead_beefu64;
+        let ptr = &mut value as *mut u64;
+
+        let vaddr = VAddr::from(ptr);
+        assert_eq!(vaddr.as_usize(), ptr as usize);
+        assert_eq!(vaddr.as_mut_ptr::<u64>(), ptr);
+        assert_eq!(VAddr::from(ptr as *const u64), vaddr);
+        assert_eq!(unsafe { *vaddr.as_ptr::<u64>() }, 0xdead_beef);
+
+        assert_eq!(VAddr::from(core::ptr::null::<u8>()), VAddr::zero());
+    }
+
+    #[test]
+    fn aligned_table() {
+        use core::mem::{align_of, size_of};
+
+        assert!(align_of::<AlignedTable<PML4>>() >= 4096);
+        assert_eq!(size_of::<AlignedTable<PT>>(), 4096);
+
+        let mut pdpt = AlignedTable([PDPTEntry(0); PAGE_SIZE_ENTRIES]);
+        pdpt[1] = PDPTEntry::new(PAddr::from(0x1000u64), PDPTFlags::P);
+        assert_eq!(pdpt.present_entries().count(), 1);
+        assert!(PAddr::from(pdpt.as_ptr() as u64).is_base_page_aligned());
+    }
+
+    #[test]
+    fn static_page_tables() {
+        const FLAGS: PML4Flags = PML4Flags::P.union(PML4Flags::RW);
+        static PML4_TABLE: AlignedTable<PML4> = AlignedTable({
+            let mut table = [PML4Entry(0); PAGE_SIZE_ENTRIES];
+            table[0] = PML4Entry::new(PAddr::from_u64(0x1000), FLAGS);
+            table[511] = PML4Entry::new(PAddr::from_u64(0x2000), FLAGS.union(PML4Flags::US));
+            table
+        });
+        static PDPT_ENTRY: PDPTEntry =
+            PDPTEntry::new_huge_page(PAddr::from_u64(0x4000_0000), PDPTFlags::P);
+        static PD_ENTRY: PDEntry =
+            PDEntry::new_large_page(PAddr::from_u64(0x20_0000), PDFlags::P.union(PDFlags::RW));
+        static PT_ENTRY: PTEntry = PTEntry::new(PAddr::from_u64(0x3000), PTFlags::P);
+
+        let table = &PML4_TABLE.0;
+        assert_eq!(table[0].address(), PAddr::from(0x1000u64));
+        assert_eq!(table[0].flags(), PML4Flags::P | PML4Flags::RW);
+        assert_eq!(table[511].0, 0x2007);
+        assert!(!table[1].is_present());
+
+        assert!(PDPT_ENTRY.is_page());
+        assert_eq!(PDPT_ENTRY.address(), PAddr::from(0x4000_0000u64));
+        assert!(PD_ENTRY.is_page());
+        assert_eq!(PD_ENTRY.0, 0x20_0083);
+        assert_eq!(PT_ENTRY.0, 0x3001);
+    }
+
+    #[test]
+    fn address_formatting() {
+        extern crate std;
+        use std::format;
+
+        let paddr = PAddr::from_u64(0x1000);
+        assert_eq!(format!("{:#x}", paddr), "0x1000");
+        assert_eq!(format!("{:x}", paddr), "1000");
+        assert_eq!(format!("{:#018x}", paddr), "0x0000000000001000");
+        assert_eq!(format!("{:p}", paddr), "0x1000");
+
+        let vaddr = VAddr::from(0xffff_8000_dead_beefu64);
+        assert_eq!(format!("{:X}", vaddr), "FFFF8000DEADBEEF");
+        assert_eq!(format!("{:#X}", vaddr), "0xFFFF8000DEADBEEF");
+        assert_eq!(format!("{:p}", vaddr), "0xffff8000deadbeef");
+        assert_eq!(format!("{:p}", IOAddr::from(0x20u64)), "0x20");
+
+        // Width, fill and alignment work the same way as for raw pointers:
+        assert_eq!(format!("{:>8p}", paddr), "  0x1000");
+        assert_eq!(format!("{:*<8p}", paddr), "0x1000**");
+        assert_eq!(format!("{:#010p}", paddr), "0x00001000");
+        assert_eq!(format!("{:p}", PAddr::zero()), "0x0");
+    }
+
+    #[test]
+    fn paddr_conversions() {
+        let paddr = PAddr::from_u64(0x1000);
+        assert_eq!(paddr.as_u64(), 0x1000);
+        assert_eq!(PAddr::from(0x1000usize), paddr);
+        assert_eq!(paddr.as_usize(), 0x1000);
+    }
+
     #[test]
     fn vaddr_align() {
         let base = VAddr::from(0x1000);
@@ -14,6 +14,7 @@ use core::arch::asm;
 bitflags! {
     /// The RFLAGS register.
     /// This is duplicated code from bits32 eflags.rs.
+    #[repr(transparent)]
     pub struct RFlags: u64 {
         /// ID Flag (ID)
         const FLAGS_ID = 1 << 21;
@@ -76,8 +77,19 @@ impl RFlags {
     pub const fn from_raw(bits: u64) -> RFlags {
         RFlags { bits }
     }
+
+    /// Returns the I/O privilege level stored in bits 12-13.
+    pub const fn iopl(&self) -> Ring {
+        match (self.bits >> 12) & 0b11 {
+            0b00 => Ring::Ring0,
+            0b01 => Ring::Ring1,
+            0b10 => Ring::Ring2,
+            _ => Ring::Ring3,
+        }
+    }
 }
 
+/// Reads the RFLAGS register (`pushfq; popq`).
 #[cfg(target_arch = "x86_64")]
 #[inline(always)]
 pub fn read() -> RFlags {
@@ -86,6 +98,10 @@ pub fn read() -> RFlags {
     RFlags::from_bits_truncate(r)
 }
 
+/// Writes the RFLAGS register (`pushq; popfq`).
+///
+/// Flags that can't be modified at the current privilege level
+/// (e.g., IOPL or IF in user-mode) are silently left unchanged by `popfq`.
 #[cfg(target_arch = "x86_64")]
 #[inline(always)]
 pub fn set(val: RFlags) {
@@ -95,4 +111,32 @@ pub fn set(val: RFlags) {
 }
 
 // clac and stac are also usable in 64-bit mode
-pub use crate::bits32::eflags::{clac, stac};
+pub use crate::bits32::eflags::{allow_user_access, clac, stac};
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rflags_bits() {
+        assert_eq!(RFlags::new().bits(), 0x2);
+        assert_eq!(RFlags::from_raw(0x2), RFlags::FLAGS_A1);
+        assert_eq!(RFlags::FLAGS_IF.bits(), 1 << 9);
+        assert_eq!(RFlags::FLAGS_ID.bits(), 1 << 21);
+        assert_eq!(RFlags::FLAGS_IOPL3.bits(), 0x3000);
+
+        assert_eq!(RFlags::new().iopl(), Ring::Ring0);
+        assert_eq!(RFlags::from_priv(Ring::Ring3).iopl(), Ring::Ring3);
+        assert_eq!(RFlags::from_priv(Ring::Ring2).iopl(), Ring::Ring2);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn read_rflags() {
+        let flags = read();
+        assert!(flags.contains(RFlags::FLAGS_A1));
+        // User-space runs with interrupts enabled and IOPL 0.
+        assert!(flags.contains(RFlags::FLAGS_IF));
+        assert_eq!(flags.iopl(), Ring::Ring0);
+    }
+}
@@ -1,9 +1,17 @@
+use core::mem::size_of;
+
+use super::paging::VAddr;
+use super::task::{TaskStateSegment, TssWithIoBitmap};
+use crate::dtables::DescriptorTablePointer;
+#[cfg(all(target_arch = "x86_64", feature = "fsgsbase-msr"))]
+use crate::msr::{rdmsr, wrmsr, IA32_FS_BASE, IA32_GS_BASE};
 #[allow(unused_imports)]
 use crate::segmentation::SegmentSelector;
 use crate::segmentation::{
-    BuildDescriptor, Descriptor, DescriptorBuilder, DescriptorType, GateDescriptorBuilder,
-    LdtDescriptorBuilder, SystemDescriptorTypes64,
+    BuildDescriptor, CodeBits, Descriptor, DescriptorBuilder, DescriptorType,
+    GateDescriptorBuilder, LdtDescriptorBuilder, SegmentTable, SystemDescriptorTypes64,
 };
+use crate::Ring;
 
 #[cfg(target_arch = "x86_64")]
 use core::arch::asm;
@@ -27,6 +35,44 @@ impl Descriptor64 {
         upper: 0,
     };
 
+    /// Creates a present descriptor for an available 64-bit TSS (type 0b1001).
+    ///
+    /// # Arguments
+    ///  * `base` - linear address of the TSS.
+    ///  * `limit` - size of the TSS minus one (20 bits, byte granular).
+    ///  * `dpl` - privilege level required to access the descriptor.
+    pub fn new_tss(base: u64, limit: u32, dpl: Ring) -> Descriptor64 {
+        <DescriptorBuilder as GateDescriptorBuilder<u64>>::tss_descriptor(base, limit.into(), true)
+            .present()
+            .dpl(dpl)
+            .finish()
+    }
+
+    /// Returns the 64-bit base address encoded in the descriptor.
+    pub fn base(&self) -> u64 {
+        let (lower, upper) = (self.desc32.lower, self.desc32.upper);
+        let base = (lower >> 16) | (upper & 0xff) << 16 | (upper & 0xff00_0000);
+        (self.lower as u64) << 32 | base as u64
+    }
+
+    /// Returns the 20-bit limit encoded in the descriptor.
+    pub fn limit(&self) -> u32 {
+        let (lower, upper) = (self.desc32.lower, self.desc32.upper);
+        (lower & 0xffff) | (upper & 0x000f_0000)
+    }
+
+    /// Splits the descriptor into the two consecutive 8-byte entries
+    /// it occupies in the GDT.
+    pub fn as_descriptors(&self) -> [Descriptor; 2] {
+        [
+            self.desc32,
+            Descriptor {
+                lower: self.lower,
+                upper: self.upper,
+            },
+        ]
+    }
+
     pub(crate) fn apply_builder_settings(&mut self, builder: &DescriptorBuilder) {
         self.desc32.apply_builder_settings(builder);
         if let Some((base, limit)) = builder.base_limit {
@@ -133,6 +179,178 @@ impl BuildDescriptor<Descriptor64> for DescriptorBuilder {
     }
 }
 
+impl Descriptor {
+    /// Creates a present, flat 64-bit code segment (execute/read) with
+    /// privilege level `dpl`.
+    ///
+    /// L is set and D is cleared as required for 64-bit code segments
+    /// (base and limit are ignored in 64-bit mode).
+    pub fn new_code_segment_64(dpl: Ring) -> Descriptor {
+        Descriptor::flat_code_segment(dpl, CodeBits::Bits64)
+    }
+
+    /// Creates a present, flat data segment (read/write) with privilege level `dpl`.
+    ///
+    /// The descriptor is usable in 64-bit mode (where only P and DPL matter
+    /// for `ss`) as well as in compatibility mode.
+    pub fn new_data_segment(dpl: Ring) -> Descriptor {
+        Descriptor::flat_data_segment(dpl)
+    }
+}
+
+/// A segment register in the layout KVM uses for the guest state
+/// (`struct kvm_segment` in `KVM_GET_SREGS`/`KVM_SET_SREGS`).
+///
+/// Every flag is stored in its own byte, the selector is not part
+/// of a descriptor and has to be set separately.
+#[cfg(feature = "kvm-interop")]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct KvmSegment {
+    pub base: u64,
+    /// Limit in bytes (already scaled by the granularity).
+    pub limit: u32,
+    pub selector: u16,
+    /// The 4-bit type field of the descriptor.
+    pub type_: u8,
+    pub present: u8,
+    pub dpl: u8,
+    pub db: u8,
+    pub s: u8,
+    pub l: u8,
+    pub g: u8,
+    pub avl: u8,
+    pub unusable: u8,
+    pub padding: u8,
+}
+
+#[cfg(feature = "kvm-interop")]
+impl KvmSegment {
+    /// Returns a copy of the segment with `selector` loaded.
+    pub fn with_selector(mut self, selector: SegmentSelector) -> KvmSegment {
+        self.selector = selector.bits();
+        self
+    }
+}
+
+#[cfg(feature = "kvm-interop")]
+impl From<Descriptor> for KvmSegment {
+    fn from(desc: Descriptor) -> KvmSegment {
+        let upper = desc.upper;
+        let flag = |bit: u32| (upper & bit != 0) as u8;
+        KvmSegment {
+            base: desc.base() as u64,
+            limit: desc.limit(),
+            selector: 0,
+            type_: ((upper >> 8) & 0x0f) as u8,
+            present: flag(bit!(15)),
+            dpl: desc.dpl() as u8,
+            db: flag(bit!(22)),
+            s: flag(bit!(12)),
+            l: flag(bit!(21)),
+            g: flag(bit!(23)),
+            avl: flag(bit!(20)),
+            unusable: !desc.is_present() as u8,
+            padding: 0,
+        }
+    }
+}
+
+/// Builds a GDT for 64-bit mode in a caller provided table.
+///
+/// The null descriptor is placed at index 0, all other descriptors are
+/// added in the order of the `add_*` calls.
+///
+/// # Example
+///
+/// ```
+/// use x86::bits64::segmentation::GdtBuilder;
+/// use x86::bits64::task::TaskStateSegment;
+/// use x86::segmentation::Descriptor;
+/// use x86::Ring;
+///
+/// static TSS: TaskStateSegment = TaskStateSegment::new();
+/// let mut table = [Descriptor::NULL; 8];
+///
+/// let mut builder = GdtBuilder::new(&mut table);
+/// let kcode = builder.add_code_segment(Ring::Ring0);
+/// let kdata = builder.add_data_segment(Ring::Ring0);
+/// let tss = builder.add_tss(&TSS);
+/// let (gdt, _gdtr) = builder.finalize();
+///
+/// assert_eq!(kcode.bits(), 0x8);
+/// assert_eq!(kdata.bits(), 0x10);
+/// assert_eq!(tss.bits(), 0x18);
+/// assert_eq!(gdt.len(), 5);
+/// ```
+pub struct GdtBuilder<'a> {
+    table: &'a mut [Descriptor],
+    next: usize,
+}
+
+impl<'a> GdtBuilder<'a> {
+    /// Start building a GDT in `table`.
+    pub fn new(table: &'a mut [Descriptor]) -> GdtBuilder<'a> {
+        assert!(!table.is_empty());
+        table[0] = Descriptor::NULL;
+        GdtBuilder { table, next: 1 }
+    }
+
+    /// Reserves `slots` consecutive entries and returns the index of the first one.
+    fn allocate(&mut self, slots: usize) -> usize {
+        assert!(self.next + slots <= self.table.len(), "GDT is full.");
+        let index = self.next;
+        self.next += slots;
+        index
+    }
+
+    /// Adds a flat 64-bit code segment with privilege level `dpl`.
+    pub fn add_code_segment(&mut self, dpl: Ring) -> SegmentSelector {
+        let index = self.allocate(1);
+        self.table[index] = Descriptor::new_code_segment_64(dpl);
+        SegmentSelector::new(index as u16, dpl, SegmentTable::Gdt)
+    }
+
+    /// Adds a flat data segment with privilege level `dpl`.
+    pub fn add_data_segment(&mut self, dpl: Ring) -> SegmentSelector {
+        let index = self.allocate(1);
+        self.table[index] = Descriptor::new_data_segment(dpl);
+        SegmentSelector::new(index as u16, dpl, SegmentTable::Gdt)
+    }
+
+    /// Adds a descriptor for `tss` (this occupies two entries).
+    ///
+    /// The GDT refers to `tss` by address, so it must outlive the table.
+    pub fn add_tss(&mut self, tss: &'a TaskStateSegment) -> SegmentSelector {
+        let index = self.allocate(2);
+        let base = tss as *const TaskStateSegment as u64;
+        let limit = (size_of::<TaskStateSegment>() - 1) as u32;
+        let desc = Descriptor64::new_tss(base, limit, Ring::Ring0);
+        self.table[index..index + 2].copy_from_slice(&desc.as_descriptors());
+        SegmentSelector::new(index as u16, Ring::Ring0, SegmentTable::Gdt)
+    }
+
+    /// Adds a descriptor for `tss` whose limit includes the I/O permission bitmap
+    /// (this occupies two entries).
+    ///
+    /// The GDT refers to `tss` by address, so it must outlive the table.
+    pub fn add_tss_with_io_bitmap(&mut self, tss: &'a TssWithIoBitmap) -> SegmentSelector {
+        let index = self.allocate(2);
+        let base = tss as *const TssWithIoBitmap as u64;
+        let limit = (size_of::<TssWithIoBitmap>() - 1) as u32;
+        let desc = Descriptor64::new_tss(base, limit, Ring::Ring0);
+        self.table[index..index + 2].copy_from_slice(&desc.as_descriptors());
+        SegmentSelector::new(index as u16, Ring::Ring0, SegmentTable::Gdt)
+    }
+
+    /// Returns the used part of the table along with a pointer to it
+    /// that can be loaded with [lgdt](crate::dtables::lgdt).
+    pub fn finalize(self) -> (&'a [Descriptor], DescriptorTablePointer<Descriptor>) {
+        let gdt: &'a [Descriptor] = &self.table[..self.next];
+        (gdt, DescriptorTablePointer::new_from_slice(gdt))
+    }
+}
+
 /// Reload code segment register.
 ///
 /// Note this is special since we can not directly move
@@ -140,6 +358,35 @@ impl BuildDescriptor<Descriptor64> for DescriptorBuilder {
 /// and return value on the stack and use lretq
 /// to reload cs and continue at 1:.
 ///
+/// # Example
+///
+/// Switching to a new GDT means reloading all segment registers as
+/// they still hold the descriptors cached from the old table:
+///
+/// ```no_run
+/// use x86::bits64::segmentation::GdtBuilder;
+/// use x86::segmentation::{load_cs, load_ds, load_es, load_fs, load_gs, load_ss, Descriptor};
+/// use x86::{dtables, Ring};
+///
+/// static mut GDT: [Descriptor; 3] = [Descriptor::NULL; 3];
+///
+/// unsafe {
+///     let mut builder = GdtBuilder::new(&mut *core::ptr::addr_of_mut!(GDT));
+///     let code = builder.add_code_segment(Ring::Ring0);
+///     let data = builder.add_data_segment(Ring::Ring0);
+///     let (_, gdtr) = builder.finalize();
+///
+///     dtables::lgdt(&gdtr);
+///     load_cs(code);
+///     load_ss(data);
+///     load_ds(data);
+///     load_es(data);
+///     load_fs(data);
+///     load_gs(data);
+///     assert_eq!(x86::segmentation::cs(), code);
+/// }
+/// ```
+///
 /// # Safety
 /// Can cause a GP-fault with a bad `sel` value.
 #[cfg(target_arch = "x86_64")]
@@ -191,6 +438,66 @@ pub unsafe fn rdfsbase() -> u64 {
     fs_base
 }
 
+/// Read the FS segment base.
+///
+/// Uses `rdfsbase`, or reads `IA32_FS_BASE` if the `fsgsbase-msr` feature is enabled.
+///
+/// # Safety
+/// Needs FSGSBASE-Enable Bit (bit 16 of CR4) set, or CPL 0 with `fsgsbase-msr`.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn fs_base() -> VAddr {
+    #[cfg(not(feature = "fsgsbase-msr"))]
+    let base = rdfsbase();
+    #[cfg(feature = "fsgsbase-msr")]
+    let base = rdmsr(IA32_FS_BASE);
+    VAddr::from(base)
+}
+
+/// Write the FS segment base.
+///
+/// Uses `wrfsbase`, or writes `IA32_FS_BASE` if the `fsgsbase-msr` feature is enabled.
+///
+/// # Safety
+/// Needs FSGSBASE-Enable Bit (bit 16 of CR4) set, or CPL 0 with `fsgsbase-msr`.
+/// Raises #GP if `base` is not canonical.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn set_fs_base(base: VAddr) {
+    #[cfg(not(feature = "fsgsbase-msr"))]
+    wrfsbase(base.as_u64());
+    #[cfg(feature = "fsgsbase-msr")]
+    wrmsr(IA32_FS_BASE, base.as_u64());
+}
+
+/// Read the GS segment base.
+///
+/// Uses `rdgsbase`, or reads `IA32_GS_BASE` if the `fsgsbase-msr` feature is enabled.
+///
+/// # Safety
+/// Needs FSGSBASE-Enable Bit (bit 16 of CR4) set, or CPL 0 with `fsgsbase-msr`.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn gs_base() -> VAddr {
+    #[cfg(not(feature = "fsgsbase-msr"))]
+    let base = rdgsbase();
+    #[cfg(feature = "fsgsbase-msr")]
+    let base = rdmsr(IA32_GS_BASE);
+    VAddr::from(base)
+}
+
+/// Write the GS segment base.
+///
+/// Uses `wrgsbase`, or writes `IA32_GS_BASE` if the `fsgsbase-msr` feature is enabled.
+///
+/// # Safety
+/// Needs FSGSBASE-Enable Bit (bit 16 of CR4) set, or CPL 0 with `fsgsbase-msr`.
+/// Raises #GP if `base` is not canonical.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn set_gs_base(base: VAddr) {
+    #[cfg(not(feature = "fsgsbase-msr"))]
+    wrgsbase(base.as_u64());
+    #[cfg(feature = "fsgsbase-msr")]
+    wrmsr(IA32_GS_BASE, base.as_u64());
+}
+
 /// "Dereferences" the fs register at offset 0.
 ///
 /// # Safety
@@ -220,9 +527,174 @@ pub unsafe fn gs_deref() -> u64 {
 ///
 /// The SWAPGS instruction is available only in 64-bit mode.
 ///
+/// # Example
+///
+/// Kernel entry points coming from user-space swap to the kernel GS base
+/// (set up once with [wrmsr](crate::msr::wrmsr) to `IA32_KERNEL_GS_BASE`)
+/// and swap back before returning:
+///
+/// ```no_run
+/// use x86::bits64::segmentation::{gs_deref, swapgs};
+///
+/// unsafe fn syscall_entry(from_user: bool) -> u64 {
+///     if from_user {
+///         swapgs();
+///     }
+///     // GS now points to the per-CPU data of the kernel:
+///     let cpu_data = gs_deref();
+///     // ... handle the system call ...
+///     if from_user {
+///         swapgs();
+///     }
+///     cpu_data
+/// }
+/// ```
+///
 /// # Safety
 /// The SWAPGS instruction is a privileged instruction intended for use by system software.
+/// Every swap on kernel entry has to be matched by exactly one swap on exit,
+/// and entries from kernel-mode (e.g., nested interrupts) must not swap:
+/// otherwise the kernel runs with the user-controlled GS base.
 #[cfg(target_arch = "x86_64")]
 pub unsafe fn swapgs() {
     asm!("swapgs");
 }
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "kvm-interop")]
+    #[test]
+    fn kvm_segment_from_descriptor() {
+        let cs = KvmSegment::from(Descriptor::new_code_segment_64(Ring::Ring0))
+            .with_selector(SegmentSelector::new(1, Ring::Ring0, SegmentTable::Gdt));
+        assert_eq!((cs.l, cs.db, cs.g), (1, 0, 1));
+        assert_eq!((cs.present, cs.s, cs.dpl, cs.unusable), (1, 1, 0, 0));
+        assert_eq!(cs.type_, 0b1010);
+        assert_eq!(cs.base, 0);
+        assert_eq!(cs.limit, 0xffff_ffff);
+        assert_eq!(cs.selector, 0x8);
+
+        let ds = KvmSegment::from(Descriptor::new_data_segment(Ring::Ring3));
+        assert_eq!((ds.l, ds.db, ds.dpl, ds.type_), (0, 1, 3, 0b0010));
+        assert_eq!(KvmSegment::from(Descriptor::NULL).unusable, 1);
+    }
+
+    #[test]
+    fn gdt_tss_with_io_bitmap() {
+        let tss = TssWithIoBitmap::new();
+        let mut table = [Descriptor::NULL; 3];
+        let sel = GdtBuilder::new(&mut table).add_tss_with_io_bitmap(&tss);
+        assert_eq!(sel.index(), 1);
+        // The limit covers the TSS and the whole bitmap:
+        assert_eq!(table[1].limit(), 104 + 8193 - 1);
+    }
+
+    #[test]
+    fn gdt_builder() {
+        let tss = TaskStateSegment::new();
+        let mut table = [Descriptor::NULL; 8];
+
+        let mut builder = GdtBuilder::new(&mut table);
+        let kcode = builder.add_code_segment(Ring::Ring0);
+        let kdata = builder.add_data_segment(Ring::Ring0);
+        let tss_sel = builder.add_tss(&tss);
+        let ucode = builder.add_code_segment(Ring::Ring3);
+        let (gdt, gdtr) = builder.finalize();
+
+        assert_eq!(kcode.index(), 1);
+        assert_eq!(kdata.index(), 2);
+        assert_eq!(tss_sel.index(), 3);
+        assert_eq!(ucode.index(), 5);
+        assert_eq!(ucode.bits(), 5 << 3 | 0b11);
+
+        assert_eq!(gdt.len(), 6);
+        assert_eq!(gdt[0].as_u64(), 0);
+        // Present, DPL 0, code execute/read, L set, D clear:
+        assert_eq!(gdt[1].as_u64(), 0x00AF9A000000FFFF);
+        assert_eq!(gdt[2].as_u64(), 0x00CF92000000FFFF);
+        // Present, DPL 3, code execute/read, L set, D clear:
+        assert_eq!(gdt[5].as_u64(), 0x00AFFA000000FFFF);
+
+        // Available 64-bit TSS:
+        let tss_base = &tss as *const TaskStateSegment as u64;
+        assert_eq!((gdt[3].upper >> 8) & 0xf, 0x9);
+        assert_eq!(gdt[3].lower & 0xffff, 103);
+        assert_eq!(gdt[4].lower as u64, tss_base >> 32);
+
+        let limit = gdtr.limit;
+        let base = gdtr.base;
+        assert_eq!(limit, 6 * 8 - 1);
+        assert_eq!(base, gdt.as_ptr());
+    }
+
+    #[test]
+    fn long_mode_segments() {
+        const L: u64 = 1 << 53;
+        const D: u64 = 1 << 54;
+        const S: u64 = 1 << 44;
+        const P: u64 = 1 << 47;
+
+        let code = Descriptor::new_code_segment_64(Ring::Ring0).as_u64();
+        assert_eq!(code & L, L);
+        assert_eq!(code & D, 0);
+        assert_eq!(code & (S | P), S | P);
+        // Type: code, execute/read
+        assert_eq!((code >> 40) & 0xf, 0b1010);
+        assert_eq!((code >> 45) & 0b11, 0);
+
+        let ucode = Descriptor::new_code_segment_64(Ring::Ring3).as_u64();
+        assert_eq!((ucode >> 45) & 0b11, 3);
+        assert_eq!(ucode & (L | D), L);
+
+        let data = Descriptor::new_data_segment(Ring::Ring3).as_u64();
+        assert_eq!(data & L, 0);
+        assert_eq!(data & (S | P), S | P);
+        // Type: data, read/write
+        assert_eq!((data >> 40) & 0xf, 0b0010);
+        assert_eq!((data >> 45) & 0b11, 3);
+        assert_eq!(data, 0x00CFF2000000FFFF);
+    }
+
+    #[test]
+    fn tss_descriptor() {
+        let desc = Descriptor64::new_tss(0xffff_8000_dead_b000, 0x67, Ring::Ring0);
+        assert_eq!(desc.base(), 0xffff_8000_dead_b000);
+        assert_eq!(desc.limit(), 0x67);
+
+        let [low, high] = desc.as_descriptors();
+        // Present, DPL 0, type 0b1001 (available 64-bit TSS), S clear:
+        assert_eq!(low.as_u64(), 0xde00_89ad_b000_0067);
+        assert_eq!(high.as_u64(), 0xffff_8000);
+
+        let desc = Descriptor64::new_tss(0x1234_5678, 0xf_ffff, Ring::Ring3);
+        assert_eq!(desc.base(), 0x1234_5678);
+        assert_eq!(desc.limit(), 0xf_ffff);
+        assert_eq!(desc.as_descriptors()[0].as_u64(), 0x120f_e934_5678_ffff);
+        assert_eq!(desc.as_descriptors()[1].as_u64(), 0);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    #[ignore]
+    fn gs_base_round_trip() {
+        // Needs CR4.FSGSBASE (or CPL 0 with `fsgsbase-msr`), GS is unused
+        // by user-space on Linux so we can temporarily clobber it.
+        unsafe {
+            let old = gs_base();
+            set_gs_base(VAddr::from(0x7fff_dead_b000u64));
+            assert_eq!(gs_base(), VAddr::from(0x7fff_dead_b000u64));
+            set_gs_base(old);
+            assert_eq!(gs_base(), old);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn gdt_builder_full() {
+        let tss = TaskStateSegment::new();
+        let mut table = [Descriptor::NULL; 2];
+        GdtBuilder::new(&mut table).add_tss(&tss);
+    }
+}
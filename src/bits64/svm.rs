@@ -0,0 +1,122 @@
+//! AMD Secure Virtual Machine (SVM, AMD-V) instructions.
+//!
+//! See AMD64 Architecture Programmer's Manual, Volume 2, Chapter 15 "Secure Virtual Machine".
+//!
+//! All addresses used by these instructions are physical addresses of
+//! 4 KiB aligned structures: the VMCB (virtual machine control block) of the guest
+//! and the host state-save area set with [set_host_save_area].
+
+use crate::bits64::paging::PAddr;
+use crate::msr::{rdmsr, wrmsr, Efer};
+use core::arch::asm;
+
+/// VM_CR MSR, controls global aspects of SVM.
+pub const VM_CR: u32 = 0xc001_0114;
+
+/// VM_HSAVE_PA MSR, physical address of the host state-save area.
+pub const VM_HSAVE_PA: u32 = 0xc001_0117;
+
+/// VM_CR.SVMDIS: SVM is disabled (by firmware) and EFER.SVME can't be set.
+const VM_CR_SVMDIS: u64 = bit!(4);
+
+/// Does the processor support SVM (CPUID.80000001H:ECX.SVM\[bit 2\])?
+pub fn has_svm() -> bool {
+    crate::cpuid::CpuId::new()
+        .get_extended_processor_and_feature_identifiers()
+        .is_some_and(|info| info.has_svm())
+}
+
+/// Enables SVM by setting EFER.SVME.
+///
+/// Returns false if SVM was disabled by the firmware (VM_CR.SVMDIS).
+///
+/// # Safety
+/// Needs CPL 0 and an AMD processor with SVM support (see [has_svm]).
+pub unsafe fn enable_svm() -> bool {
+    if rdmsr(VM_CR) & VM_CR_SVMDIS != 0 {
+        return false;
+    }
+    (Efer::read() | Efer::SVME).write();
+    true
+}
+
+/// Sets the 4 KiB aligned host state-save area used by [vmrun].
+///
+/// # Safety
+/// Needs CPL 0 and EFER.SVME set. `area` must not be used for anything else.
+pub unsafe fn set_host_save_area(area: PAddr) {
+    assert!(
+        area.is_base_page_aligned(),
+        "Host save area must be 4 KiB aligned"
+    );
+    wrmsr(VM_HSAVE_PA, area.as_u64());
+}
+
+/// Runs the guest described by the VMCB at physical address `vmcb`
+/// until the next #VMEXIT.
+///
+/// The host state is saved to the area set with [set_host_save_area]. General
+/// purpose registers other than RAX and RSP are neither saved nor loaded,
+/// a real hypervisor therefore uses its own assembly around `vmrun`.
+///
+/// # Safety
+/// Needs CPL 0 and EFER.SVME set. `vmcb` must be the 4 KiB aligned physical
+/// address of a valid VMCB (#GP otherwise).
+#[inline(always)]
+pub unsafe fn vmrun(vmcb: PAddr) {
+    asm!("vmrun", in("rax") vmcb.as_u64(), options(att_syntax));
+}
+
+/// Loads the guest state not loaded by [vmrun] (FS, GS, TR, LDTR, KernelGsBase,
+/// and the SYSCALL/SYSENTER MSRs) from the VMCB at physical address `vmcb`.
+///
+/// # Safety
+/// Needs CPL 0 and EFER.SVME set. `vmcb` must be the 4 KiB aligned physical
+/// address of a valid VMCB (#GP otherwise).
+pub unsafe fn vmload(vmcb: PAddr) {
+    asm!("vmload", in("rax") vmcb.as_u64(), options(att_syntax));
+}
+
+/// Saves the state loaded by [vmload] to the VMCB at physical address `vmcb`.
+///
+/// # Safety
+/// Needs CPL 0 and EFER.SVME set. `vmcb` must be the 4 KiB aligned physical
+/// address of a VMCB (#GP otherwise).
+pub unsafe fn vmsave(vmcb: PAddr) {
+    asm!("vmsave", in("rax") vmcb.as_u64(), options(att_syntax));
+}
+
+/// Sets the global interrupt flag (GIF).
+///
+/// # Safety
+/// Needs CPL 0 and EFER.SVME set.
+pub unsafe fn stgi() {
+    asm!("stgi", options(nomem, nostack));
+}
+
+/// Clears the global interrupt flag (GIF), this holds off all
+/// interrupts including NMI and SMI.
+///
+/// # Safety
+/// Needs CPL 0 and EFER.SVME set.
+pub unsafe fn clgi() {
+    asm!("clgi", options(nomem, nostack));
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn svm_support() {
+        // The instructions need CPL 0, only check that they exist and that
+        // the feature check is consistent with the vendor.
+        let _: [unsafe fn(PAddr); 3] = [vmrun, vmload, vmsave];
+        let _: [unsafe fn(); 2] = [stgi, clgi];
+
+        let vendor = crate::cpuid::CpuId::new().get_vendor_info().unwrap();
+        if vendor.as_str() == "GenuineIntel" {
+            assert!(!has_svm());
+        }
+    }
+}
@@ -18,6 +18,12 @@
 #[cfg(target_arch = "x86_64")]
 use core::arch::asm;
 
+use crate::bits64::paging::VAddr;
+use crate::bits64::rflags::RFlags;
+use crate::msr::{wrmsr, Efer, IA32_FMASK, IA32_LSTAR, IA32_STAR};
+use crate::segmentation::SegmentSelector;
+use crate::Ring;
+
 #[macro_export]
 macro_rules! syscall {
     ($arg0:expr) => {
@@ -220,3 +226,97 @@ pub unsafe fn syscall6(
     );
     ret
 }
+
+/// Computes the value of IA32_STAR for the given code segments.
+fn star(kernel_cs: SegmentSelector, user_cs: SegmentSelector) -> u64 {
+    assert!(
+        user_cs.index() >= 2,
+        "User data and 32-bit code segments must precede user_cs"
+    );
+    let sysret_base = SegmentSelector::new(user_cs.index() - 2, Ring::Ring3, user_cs.table());
+    let syscall_base = SegmentSelector::from_raw(kernel_cs.bits() & !0b11);
+    (sysret_base.bits() as u64) << 48 | (syscall_base.bits() as u64) << 32
+}
+
+/// Configures the SYSCALL/SYSRET fast system-call path and enables it (IA32_EFER.SCE).
+///
+/// # Arguments
+///  * `handler` - Entry point of the system-call handler (written to IA32_LSTAR).
+///  * `kernel_cs` - 64-bit kernel code segment, SYSCALL loads SS with `kernel_cs + 8`.
+///  * `user_cs` - 64-bit user code segment, SYSRET loads SS with `user_cs - 8`.
+///  * `flags_mask` - RFLAGS bits cleared on SYSCALL (written to IA32_FMASK),
+///    typically at least `FLAGS_IF`.
+///
+/// IA32_STAR only holds two selectors, the processor derives all others from them
+/// so the GDT has to follow this layout:
+///
+/// | GDT index     | Segment                        |
+/// |---------------|--------------------------------|
+/// | `k`           | kernel code (64-bit)           |
+/// | `k + 1`       | kernel data                    |
+/// | `u`           | user code (32-bit, for SYSRET to compatibility mode) |
+/// | `u + 1`       | user data                      |
+/// | `u + 2`       | user code (64-bit, `user_cs`)  |
+///
+/// # Safety
+/// Needs CPL 0. `handler` must be a valid system-call entry point.
+pub unsafe fn configure(
+    handler: VAddr,
+    kernel_cs: SegmentSelector,
+    user_cs: SegmentSelector,
+    flags_mask: RFlags,
+) {
+    wrmsr(IA32_STAR, star(kernel_cs, user_cs));
+    wrmsr(IA32_LSTAR, handler.as_u64());
+    wrmsr(IA32_FMASK, flags_mask.bits());
+    (Efer::read() | Efer::SCE).write();
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+    use crate::segmentation::SegmentTable;
+
+    #[test]
+    fn star_layout() {
+        // Linux style GDT: kernel code 0x10, user 32-bit code 0x23, user code 0x33
+        let kernel_cs = SegmentSelector::new(2, Ring::Ring0, SegmentTable::Gdt);
+        let user_cs = SegmentSelector::new(6, Ring::Ring3, SegmentTable::Gdt);
+        assert_eq!(star(kernel_cs, user_cs), 0x0023_0010_0000_0000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn star_user_cs_too_low() {
+        let kernel_cs = SegmentSelector::new(1, Ring::Ring0, SegmentTable::Gdt);
+        let user_cs = SegmentSelector::new(1, Ring::Ring3, SegmentTable::Gdt);
+        star(kernel_cs, user_cs);
+    }
+}
+
+#[cfg(all(test, feature = "vmtest"))]
+mod x86testing {
+    use super::*;
+    use crate::msr::rdmsr;
+    use crate::segmentation::SegmentTable;
+    use x86test::*;
+
+    #[x86test]
+    fn check_configure() {
+        let kernel_cs = SegmentSelector::new(1, Ring::Ring0, SegmentTable::Gdt);
+        let user_cs = SegmentSelector::new(5, Ring::Ring3, SegmentTable::Gdt);
+        unsafe {
+            configure(
+                VAddr::from(0xffff_8000_dead_b000u64),
+                kernel_cs,
+                user_cs,
+                RFlags::FLAGS_IF,
+            );
+            kassert!(
+                rdmsr(IA32_LSTAR) == 0xffff_8000_dead_b000,
+                "LSTAR doesn't hold the handler"
+            );
+            kassert!(Efer::read().contains(Efer::SCE), "SCE not set");
+        }
+    }
+}
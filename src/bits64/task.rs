@@ -1,6 +1,9 @@
 //! Helpers to program the task state segment.
 //! See Intel 3a, Chapter 7, Section 7
 
+use core::mem::size_of;
+
+use crate::bits64::paging::VAddr;
 use crate::Ring;
 
 /// Although hardware task-switching is not supported in 64-bit mode,
@@ -27,6 +30,9 @@ use crate::Ring;
 ///
 /// # I/O map base address
 /// The 16-bit offset to the I/O permission bit map from the 64-bit TSS base.
+/// A TSS created with [TaskStateSegment::new] has no bitmap (the offset points
+/// past the end of the TSS) and denies all port accesses with CPL > IOPL,
+/// see [TssWithIoBitmap] to give access to individual ports.
 ///
 /// The operating system must create at least one 64-bit TSS after activating IA-32e mode.
 /// It must execute the LTR instruction (in 64-bit mode) to load the TR register with a
@@ -57,13 +63,14 @@ impl TaskStateSegment {
             ist: [0; 7],
             reserved3: 0,
             reserved4: 0,
-            iomap_base: 0,
+            iomap_base: size_of::<TaskStateSegment>() as u16,
         }
     }
 
     /// Sets the stack pointer (`stack_ptr`) to be used for when
     /// an interrupt causes the CPU to change RPL to `pl`.
-    pub fn set_rsp(&mut self, pl: Ring, stack_ptr: u64) {
+    pub fn set_rsp(&mut self, pl: Ring, stack_ptr: VAddr) {
+        let stack_ptr = stack_ptr.as_u64();
         match pl {
             Ring::Ring0 => self.rsp[0] = stack_ptr,
             Ring::Ring1 => self.rsp[1] = stack_ptr,
@@ -75,7 +82,11 @@ impl TaskStateSegment {
     /// Sets the stack pointer (`stack_ptr`) to be used when
     /// an interrupt with a corresponding IST entry in the Interrupt
     /// Descriptor table pointing to the given `index` is raised.
-    pub fn set_ist(&mut self, index: usize, stack_ptr: u64) {
+    ///
+    /// Note that `index` is 0-based, i.e., `ist[0]` is used by IDT
+    /// entries with an IST field of 1.
+    pub fn set_ist(&mut self, index: usize, stack_ptr: VAddr) {
+        let stack_ptr = stack_ptr.as_u64();
         match index {
             0 => self.ist[0] = stack_ptr,
             1 => self.ist[1] = stack_ptr,
@@ -88,3 +99,111 @@ impl TaskStateSegment {
         }
     }
 }
+
+/// Size of the I/O permission bitmap in bytes: one bit for each of the
+/// 65536 ports plus a final byte with all bits set.
+pub const IO_BITMAP_SIZE: usize = 65536 / 8 + 1;
+
+/// A 64-bit TSS directly followed by an I/O permission bitmap.
+///
+/// Bit `n` of the bitmap controls port `n` for code running with CPL > IOPL:
+/// a clear bit allows the access, a set bit raises a #GP. Multi-byte accesses
+/// check the bits of all ports involved, which is why the bitmap needs the
+/// trailing byte of ones (accesses near port 0xffff would otherwise read past it).
+///
+/// The descriptor for it has to cover the bitmap, see
+/// [GdtBuilder::add_tss_with_io_bitmap](crate::bits64::segmentation::GdtBuilder::add_tss_with_io_bitmap).
+///
+/// See Intel 1, Section 18.5.2 "I/O Permission Bit Map".
+#[derive(Clone, Debug)]
+#[repr(C, packed)]
+pub struct TssWithIoBitmap {
+    pub tss: TaskStateSegment,
+    /// The I/O permission bitmap, `tss.iomap_base` points here.
+    pub io_bitmap: [u8; IO_BITMAP_SIZE],
+}
+
+impl TssWithIoBitmap {
+    /// Creates a new empty TSS that denies access to all ports.
+    pub const fn new() -> TssWithIoBitmap {
+        TssWithIoBitmap {
+            tss: TaskStateSegment::new(),
+            io_bitmap: [0xff; IO_BITMAP_SIZE],
+        }
+    }
+
+    /// Allows or denies access to `port` when running with CPL > IOPL.
+    pub fn set_io_permission(&mut self, port: u16, allowed: bool) {
+        let (byte, bit) = (port as usize / 8, port % 8);
+        if allowed {
+            self.io_bitmap[byte] &= !(1 << bit);
+        } else {
+            self.io_bitmap[byte] |= 1 << bit;
+        }
+    }
+
+    /// Is access to `port` allowed when running with CPL > IOPL?
+    pub fn io_permission(&self, port: u16) -> bool {
+        self.io_bitmap[port as usize / 8] & (1 << (port % 8)) == 0
+    }
+}
+
+impl Default for TssWithIoBitmap {
+    fn default() -> TssWithIoBitmap {
+        TssWithIoBitmap::new()
+    }
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+    use core::ptr::addr_of;
+
+    #[test]
+    fn tss_layout() {
+        // See Intel 3a, Figure 7-11 "64-Bit TSS Format"
+        assert_eq!(size_of::<TaskStateSegment>(), 104);
+
+        let tss = TaskStateSegment::new();
+        let base = &tss as *const TaskStateSegment as usize;
+        assert_eq!(addr_of!(tss.rsp) as usize - base, 0x4);
+        assert_eq!(addr_of!(tss.ist) as usize - base, 0x24);
+        assert_eq!(addr_of!(tss.iomap_base) as usize - base, 0x66);
+    }
+
+    #[test]
+    fn tss_stacks() {
+        let mut tss = TaskStateSegment::new();
+        tss.set_rsp(Ring::Ring0, VAddr::from(0xffff_8000_0001_0000u64));
+        tss.set_ist(0, VAddr::from(0xffff_8000_0002_0000u64));
+        tss.set_ist(6, VAddr::from(0xffff_8000_0003_0000u64));
+
+        let (rsp, ist) = (tss.rsp, tss.ist);
+        assert_eq!(rsp, [0xffff_8000_0001_0000, 0, 0]);
+        assert_eq!(ist[0], 0xffff_8000_0002_0000);
+        assert_eq!(ist[6], 0xffff_8000_0003_0000);
+    }
+
+    #[test]
+    fn io_permission_bitmap() {
+        let mut tss = TssWithIoBitmap::new();
+        let base = &tss as *const TssWithIoBitmap as usize;
+        let iomap_base = tss.tss.iomap_base;
+        assert_eq!(iomap_base, 104);
+        assert_eq!(addr_of!(tss.io_bitmap) as usize - base, iomap_base as usize);
+        assert_eq!(size_of::<TssWithIoBitmap>(), 104 + 8193);
+
+        assert!(!tss.io_permission(0x3f8));
+        tss.set_io_permission(0x3f8, true);
+        assert!(tss.io_permission(0x3f8));
+        assert!(!tss.io_permission(0x3f9));
+        assert_eq!(tss.io_bitmap[0x3f8 / 8], 0xfe);
+
+        tss.set_io_permission(0xffff, true);
+        assert!(tss.io_permission(0xffff));
+        assert_eq!(tss.io_bitmap[IO_BITMAP_SIZE - 1], 0xff);
+
+        tss.set_io_permission(0x3f8, false);
+        assert!(!tss.io_permission(0x3f8));
+    }
+}
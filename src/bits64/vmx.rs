@@ -1,7 +1,8 @@
 //! Virtualize processor hardware for multiple software environments using Virtual Machine Extensions.
 
+use crate::bits64::paging::PAddr;
 use crate::bits64::rflags::{self, RFlags};
-use crate::vmx::{Result, VmFail};
+use crate::vmx::{Result, VmxError};
 use core::arch::asm;
 
 /// Helper used to extract VMX-specific Result in accordance with
@@ -12,12 +13,16 @@ use core::arch::asm;
 // see https://github.com/gz/rust-x86/pull/50.
 #[inline(always)]
 fn vmx_capture_status() -> Result<()> {
-    let flags = rflags::read();
+    vmx_status(rflags::read())
+}
 
+/// Decodes the VMsucceed, VMfailInvalid and VMfailValid conventions from `flags`.
+#[inline(always)]
+fn vmx_status(flags: RFlags) -> Result<()> {
     if flags.contains(RFlags::FLAGS_ZF) {
-        Err(VmFail::VmFailValid)
+        Err(VmxError::VmFailValid)
     } else if flags.contains(RFlags::FLAGS_CF) {
-        Err(VmFail::VmFailInvalid)
+        Err(VmxError::VmFailInvalid)
     } else {
         Ok(())
     }
@@ -30,7 +35,8 @@ fn vmx_capture_status() -> Result<()> {
 ///
 /// # Safety
 /// Needs CPL 0.
-pub unsafe fn vmxon(addr: u64) -> Result<()> {
+pub unsafe fn vmxon(addr: PAddr) -> Result<()> {
+    let addr = addr.as_u64();
     asm!("vmxon ({0})", in(reg) &addr, options(att_syntax));
     vmx_capture_status()
 }
@@ -51,7 +57,8 @@ pub unsafe fn vmxoff() -> Result<()> {
 ///
 /// # Safety
 /// Needs CPL 0.
-pub unsafe fn vmclear(addr: u64) -> Result<()> {
+pub unsafe fn vmclear(addr: PAddr) -> Result<()> {
+    let addr = addr.as_u64();
     asm!("vmclear ({0})", in(reg) &addr, options(att_syntax));
     vmx_capture_status()
 }
@@ -62,7 +69,8 @@ pub unsafe fn vmclear(addr: u64) -> Result<()> {
 ///
 /// # Safety
 /// Needs CPL 0.
-pub unsafe fn vmptrld(addr: u64) -> Result<()> {
+pub unsafe fn vmptrld(addr: PAddr) -> Result<()> {
+    let addr = addr.as_u64();
     asm!("vmptrld ({0})", in(reg) &addr, options(att_syntax));
     vmx_capture_status()
 }
@@ -117,3 +125,26 @@ pub unsafe fn vmresume() -> Result<()> {
     asm!("vmresume");
     vmx_capture_status()
 }
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn status_decoding() {
+        assert_eq!(vmx_status(RFlags::new()), Ok(()));
+        assert_eq!(
+            vmx_status(RFlags::new() | RFlags::FLAGS_CF),
+            Err(VmxError::VmFailInvalid)
+        );
+        assert_eq!(
+            vmx_status(RFlags::new() | RFlags::FLAGS_ZF),
+            Err(VmxError::VmFailValid)
+        );
+        // Other arithmetic flags are cleared by VMX instructions and ignored:
+        assert_eq!(
+            vmx_status(RFlags::FLAGS_IF | RFlags::FLAGS_SF | RFlags::FLAGS_PF),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Cache control instructions.
+
+use core::arch::asm;
+
+use crate::bits64::paging::VAddr;
+
+/// wbinvd -- Write Back and Invalidate Cache
+///
+/// Writes back all modified cache lines to main memory and invalidates
+/// the internal caches, then signals external caches to do the same.
+///
+/// # Safety
+/// Needs CPL 0. This can take a long time (interrupts are not served meanwhile).
+pub unsafe fn wbinvd() {
+    asm!("wbinvd", options(nostack, preserves_flags));
+}
+
+/// invd -- Invalidate Internal Caches
+///
+/// Invalidates the caches *without* writing back modified cache lines.
+///
+/// # Safety
+/// Needs CPL 0. Any modified data still in the caches is lost.
+pub unsafe fn invd() {
+    asm!("invd", options(nostack, preserves_flags));
+}
+
+/// clflush -- Flush Cache Line
+///
+/// Writes back (if modified) and invalidates the cache line containing `addr`
+/// in all levels of the cache hierarchy.
+///
+/// Needs CPUID.01H:EDX.CLFSH\[bit 19\].
+///
+/// # Example
+///
+/// ```no_run
+/// use x86::bits64::paging::VAddr;
+/// use x86::{cache, fence};
+///
+/// let mut buffer = [0u8; 64];
+/// buffer[0] = 0xab;
+/// // Make sure the device sees the data before handing the buffer to it:
+/// unsafe { cache::clflush(VAddr::from(buffer.as_ptr())) };
+/// fence::mfence();
+/// ```
+///
+/// # Safety
+/// `addr` must be mapped, otherwise a page fault is raised.
+pub unsafe fn clflush(addr: VAddr) {
+    asm!("clflush [{}]", in(reg) addr.as_usize(), options(nostack, preserves_flags));
+}
+
+/// clflushopt -- Flush Cache Line Optimized
+///
+/// Like [clflush] but only ordered with respect to fences, stores and
+/// flushes of the same cache line. This allows flushing several lines in parallel.
+///
+/// Needs CPUID.(EAX=07H, ECX=0H):EBX.CLFLUSHOPT\[bit 23\].
+///
+/// # Safety
+/// `addr` must be mapped, otherwise a page fault is raised.
+/// Raises #UD if CLFLUSHOPT is not supported.
+pub unsafe fn clflushopt(addr: VAddr) {
+    asm!("clflushopt [{}]", in(reg) addr.as_usize(), options(nostack, preserves_flags));
+}
+
+/// clwb -- Cache Line Write Back
+///
+/// Writes back the cache line containing `addr` if it is modified,
+/// the line may stay in the cache.
+///
+/// Needs CPUID.(EAX=07H, ECX=0H):EBX.CLWB\[bit 24\].
+///
+/// # Safety
+/// `addr` must be mapped, otherwise a page fault is raised.
+/// Raises #UD if CLWB is not supported.
+pub unsafe fn clwb(addr: VAddr) {
+    asm!("clwb [{}]", in(reg) addr.as_usize(), options(nostack, preserves_flags));
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flush_cache_lines() {
+        let mut value = 0xdead_beefu64;
+        let addr = VAddr::from(&mut value as *mut u64);
+        let features = crate::cpuid::CpuId::new().get_extended_feature_info();
+
+        unsafe {
+            clflush(addr);
+            if features.as_ref().map_or(false, |f| f.has_clflushopt()) {
+                clflushopt(addr);
+            }
+            if features.as_ref().map_or(false, |f| f.has_clwb()) {
+                clwb(addr);
+            }
+        }
+        assert_eq!(value, 0xdead_beef);
+    }
+}
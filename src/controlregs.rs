@@ -4,6 +4,7 @@
 use bitflags::*;
 
 use crate::arch::{_xgetbv, _xsetbv};
+use crate::bits64::paging::{PAddr, VAddr};
 use core::arch::asm;
 
 bitflags! {
@@ -25,16 +26,26 @@ bitflags! {
 bitflags! {
     pub struct Cr4: usize {
         /// Enables use of Protection Keys (MPK).
+        ///
+        /// Requires CPUID.(EAX=07H, ECX=0H):ECX.PKU [bit 3].
         const CR4_ENABLE_PROTECTION_KEY = 1 << 22;
         /// Enable Supervisor Mode Access Prevention.
+        ///
+        /// Requires CPUID.(EAX=07H, ECX=0H):EBX.SMAP [bit 20].
         const CR4_ENABLE_SMAP = 1 << 21;
         /// Enable Supervisor Mode Execution Protection.
+        ///
+        /// Requires CPUID.(EAX=07H, ECX=0H):EBX.SMEP [bit 7].
         const CR4_ENABLE_SMEP = 1 << 20;
         /// Enable XSAVE and Processor Extended States.
+        ///
+        /// Requires CPUID.01H:ECX.XSAVE [bit 26].
         const CR4_ENABLE_OS_XSAVE = 1 << 18;
         /// Enables process-context identifiers (PCIDs).
         const CR4_ENABLE_PCID = 1 << 17;
         /// Enables the instructions RDFSBASE, RDGSBASE, WRFSBASE, and WRGSBASE.
+        ///
+        /// Requires CPUID.(EAX=07H, ECX=0H):EBX.FSGSBASE [bit 0].
         const CR4_ENABLE_FSGSBASE = 1 << 16;
         /// Enables Safer Mode Extensions (Trusted Execution Technology (TXT)).
         const CR4_ENABLE_SMX = 1 << 14;
@@ -44,6 +55,8 @@ bitflags! {
         const CR4_ENABLE_LA57 = 1 << 12;
         /// Enable User-Mode Instruction Prevention (the SGDT, SIDT, SLDT, SMSW and STR instructions
         /// cannot be executed if CPL > 0).
+        ///
+        /// Requires CPUID.(EAX=07H, ECX=0H):ECX.UMIP [bit 2].
         const CR4_ENABLE_UMIP = 1 << 11;
         /// Enables unmasked SSE exceptions.
         const CR4_UNMASKED_SSE = 1 << 10;
@@ -75,15 +88,115 @@ bitflags! {
 }
 
 bitflags! {
+    /// Flags in CR3, only used if CR4_ENABLE_PCID is not set.
+    pub struct Cr3Flags: u64 {
+        /// Page-level Cache Disable for the PML4 table.
+        const CR3_PAGE_LEVEL_CACHE_DISABLE = 1 << 4;
+        /// Page-level Write-Through for the PML4 table.
+        const CR3_PAGE_LEVEL_WRITETHROUGH = 1 << 3;
+    }
+}
+
+/// Address mask of the page-table root in CR3.
+const CR3_ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Mask of the PCID in CR3.
+const CR3_PCID_MASK: u64 = 0xfff;
+
+/// A CR3 value, consisting of the physical address of the page-table root
+/// and either a PCID (if CR4_ENABLE_PCID is set) or the PCD and PWT flags.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct Cr3(u64);
+
+impl Cr3 {
+    /// Creates a new CR3 value for use with PCIDs (CR4_ENABLE_PCID set).
+    ///
+    /// # Arguments
+    ///
+    ///  * `pml4` - The physical address of the page-table root, must be 4 KiB aligned.
+    ///  * `pcid` - The process-context identifier, must be < 4096.
+    pub fn new(pml4: PAddr, pcid: u16) -> Cr3 {
+        assert!(pml4.as_u64() & !CR3_ADDRESS_MASK == 0);
+        assert!(pcid as u64 <= CR3_PCID_MASK, "PCID {} is too large", pcid);
+        Cr3(pml4.as_u64() | pcid as u64)
+    }
+
+    /// Creates a new CR3 value with CR4_ENABLE_PCID cleared.
+    ///
+    /// # Arguments
+    ///
+    ///  * `pml4` - The physical address of the page-table root, must be 4 KiB aligned.
+    ///  * `flags` - The PCD and PWT bits.
+    pub fn with_flags(pml4: PAddr, flags: Cr3Flags) -> Cr3 {
+        assert!(pml4.as_u64() & !CR3_ADDRESS_MASK == 0);
+        Cr3(pml4.as_u64() | flags.bits)
+    }
+
+    /// Creates a CR3 value from the raw register contents.
+    pub const fn from_raw(bits: u64) -> Cr3 {
+        Cr3(bits)
+    }
+
+    /// Returns the raw register contents.
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// The physical address of the page-table root.
+    pub fn pml4_address(self) -> PAddr {
+        PAddr::from(self.0 & CR3_ADDRESS_MASK)
+    }
+
+    /// The process-context identifier (only valid if CR4_ENABLE_PCID is set).
+    pub fn pcid(self) -> u16 {
+        (self.0 & CR3_PCID_MASK) as u16
+    }
+
+    /// The PCD and PWT flags (only valid if CR4_ENABLE_PCID is not set).
+    pub fn flags(self) -> Cr3Flags {
+        Cr3Flags::from_bits_truncate(self.0)
+    }
+
+    /// Read cr3.
+    ///
+    /// # Safety
+    /// Needs CPL 0.
+    pub unsafe fn read() -> Cr3 {
+        Cr3(cr3())
+    }
+
+    /// Write cr3, this switches the page-table root.
+    ///
+    /// # Safety
+    /// Needs CPL 0.
+    pub unsafe fn write(val: Cr3) {
+        cr3_write(val.0)
+    }
+}
+
+bitflags! {
+    /// State components enabled for XSAVE in XCR0 (see Intel Vol. 1 Section 13.3).
+    ///
+    /// The supported bits are reported by CPUID.(EAX=0DH, ECX=0H):EAX.
     pub struct Xcr0: u64 {
+        /// PKRU state (requires CR4_ENABLE_PROTECTION_KEY).
         const XCR0_PKRU_STATE = 1 << 9;
+        /// Upper ZMM registers ZMM16-ZMM31 (AVX-512).
         const XCR0_HI16_ZMM_STATE = 1 << 7;
+        /// Upper halves of ZMM0-ZMM15 (AVX-512).
         const XCR0_ZMM_HI256_STATE = 1 << 6;
+        /// Opmask registers k0-k7 (AVX-512).
         const XCR0_OPMASK_STATE = 1 << 5;
+        /// MPX bound configuration and status registers.
         const XCR0_BNDCSR_STATE = 1 << 4;
+        /// MPX bound registers BND0-BND3.
         const XCR0_BNDREG_STATE = 1 << 3;
+        /// Upper halves of YMM0-YMM15 (AVX), requires XCR0_SSE_STATE.
         const XCR0_AVX_STATE = 1 << 2;
+        /// XMM registers and MXCSR (SSE).
         const XCR0_SSE_STATE = 1 << 1;
+        /// x87 FPU/MMX state, must always be set.
         const XCR0_FPU_MMX_STATE = 1 << 0;
     }
 }
@@ -108,12 +221,26 @@ pub unsafe fn cr0_write(val: Cr0) {
 
 /// Contains page-fault linear address.
 ///
+/// Read it in the page-fault handler before interrupts are enabled again,
+/// a nested page-fault will overwrite it.
+///
+/// # Example
+///
+/// ```no_run
+/// use x86::controlregs::cr2;
+///
+/// fn page_fault_handler() {
+///     let faulting_address = unsafe { cr2() };
+///     panic!("Page fault at {}", faulting_address);
+/// }
+/// ```
+///
 /// # Safety
 /// Needs CPL 0.
-pub unsafe fn cr2() -> usize {
+pub unsafe fn cr2() -> VAddr {
     let ret: usize;
     asm!("mov %cr2, {0}", out(reg) ret, options(att_syntax));
-    ret
+    VAddr::from(ret)
 }
 
 /// Write cr2, for instance to reset cr2
@@ -171,8 +298,40 @@ pub unsafe fn cr4_write(val: Cr4) {
     asm!("mov {0}, %cr4", in(reg) val.bits, options(att_syntax));
 }
 
-/// Read Extended Control Register XCR0.
-/// Only supported if CR4_ENABLE_OS_XSAVE is set.
+/// Read the task-priority register (cr8).
+///
+/// Only bits 0..3 of cr8 are valid, they mirror bits 4..7 of the local APIC TPR.
+///
+/// # Note
+/// cr8 is only accessible in 64-bit mode.
+///
+/// # Safety
+/// Needs CPL 0.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn cr8() -> u8 {
+    let ret: u64;
+    asm!("mov %cr8, {0}", out(reg) ret, options(att_syntax));
+    (ret & 0xf) as u8
+}
+
+/// Write the task-priority register (cr8), `tpr` is masked to the lower 4 bits.
+///
+/// # Note
+/// cr8 is only accessible in 64-bit mode.
+///
+/// # Safety
+/// Needs CPL 0.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn cr8_write(tpr: u8) {
+    asm!("mov {0}, %cr8", in(reg) (tpr & 0xf) as u64, options(att_syntax));
+}
+
+// Needed for accessing user-mode pages with CR4_ENABLE_SMAP set
+pub use crate::bits32::eflags::{allow_user_access, clac, stac};
+
+/// Read Extended Control Register XCR0 (`xgetbv` with ECX = 0).
+/// Only supported if CR4_ENABLE_OS_XSAVE is set, which in turn requires
+/// CPUID.01H:ECX.XSAVE [bit 26].
 ///
 /// # Safety
 /// Needs CPL 0.
@@ -180,11 +339,173 @@ pub unsafe fn xcr0() -> Xcr0 {
     Xcr0::from_bits_truncate(_xgetbv(0))
 }
 
-/// Write to Extended Control Register XCR0.
-/// Only supported if CR4_ENABLE_OS_XSAVE is set.
+/// Write to Extended Control Register XCR0 (`xsetbv` with ECX = 0).
+/// Only supported if CR4_ENABLE_OS_XSAVE is set, which in turn requires
+/// CPUID.01H:ECX.XSAVE [bit 26].
+///
+/// # Example
+///
+/// ```no_run
+/// use x86::controlregs::*;
+/// unsafe {
+///   // Enable AVX:
+///   cr4_write(cr4() | Cr4::CR4_ENABLE_OS_XSAVE);
+///   xcr0_write(xcr0() | Xcr0::XCR0_FPU_MMX_STATE | Xcr0::XCR0_SSE_STATE | Xcr0::XCR0_AVX_STATE);
+/// }
+/// ```
 ///
 /// # Safety
 /// Needs CPL 0.
 pub unsafe fn xcr0_write(val: Xcr0) {
     _xsetbv(0, val.bits);
 }
+
+/// Read the protection key rights register (PKRU).
+///
+/// PKRU holds two bits for each of the 16 protection keys:
+/// access-disable (bit 2i) and write-disable (bit 2i+1).
+///
+/// # Safety
+/// Raises #UD if CR4.PKE is not set (see `CPUID.(EAX=07H,ECX=0H):ECX.OSPKE`).
+pub unsafe fn pkru() -> u32 {
+    let pkru: u32;
+    asm!("rdpkru", in("ecx") 0, out("eax") pkru, out("edx") _,
+         options(nomem, nostack, preserves_flags));
+    pkru
+}
+
+/// Write the protection key rights register (PKRU).
+///
+/// Unlike most other registers here, PKRU can be written in user-mode.
+///
+/// # Safety
+/// Raises #UD if CR4.PKE is not set. Revoking access to keys of pages
+/// that are still in use will fault on the next access.
+pub unsafe fn pkru_write(val: u32) {
+    asm!("wrpkru", in("eax") val, in("ecx") 0, in("edx") 0,
+         options(nostack, preserves_flags));
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pkru_round_trip() {
+        // rdpkru/wrpkru raise #UD unless the OS set CR4.PKE:
+        let ospke = crate::cpuid::CpuId::new()
+            .get_extended_feature_info()
+            .map_or(false, |f| f.has_ospke());
+        if !ospke {
+            return;
+        }
+
+        let (old, written, restored) = unsafe {
+            let old = pkru();
+            // Write-disable key 15 (there are no pages using it):
+            pkru_write(old | 1 << 31);
+            let written = pkru();
+            // Restore before checking anything so a failure can't leave it set:
+            pkru_write(old);
+            (old, written, pkru())
+        };
+        assert_eq!(written, old | 1 << 31);
+        assert_eq!(restored, old);
+    }
+
+    #[test]
+    fn cr4_flags() {
+        const PROTECTION_FLAGS: usize = Cr4::CR4_ENABLE_SMEP.bits
+            | Cr4::CR4_ENABLE_SMAP.bits
+            | Cr4::CR4_ENABLE_UMIP.bits
+            | Cr4::CR4_ENABLE_FSGSBASE.bits
+            | Cr4::CR4_ENABLE_PROTECTION_KEY.bits
+            | Cr4::CR4_ENABLE_OS_XSAVE.bits;
+        const _: () = assert!(PROTECTION_FLAGS == 0x75_0800);
+
+        let cr4 = Cr4::from_bits_truncate(PROTECTION_FLAGS);
+        assert!(cr4.contains(Cr4::CR4_ENABLE_SMEP | Cr4::CR4_ENABLE_SMAP));
+        assert_eq!(
+            cr4.bits(),
+            1 << 22 | 1 << 21 | 1 << 20 | 1 << 18 | 1 << 16 | 1 << 11
+        );
+    }
+
+    #[test]
+    fn xcr0_flags() {
+        assert_eq!(Xcr0::XCR0_FPU_MMX_STATE.bits(), 0x1);
+        assert_eq!(Xcr0::XCR0_SSE_STATE.bits(), 0x2);
+        assert_eq!(Xcr0::XCR0_AVX_STATE.bits(), 0x4);
+        assert_eq!(Xcr0::XCR0_BNDREG_STATE.bits(), 0x8);
+        assert_eq!(Xcr0::XCR0_BNDCSR_STATE.bits(), 0x10);
+        assert_eq!(Xcr0::XCR0_OPMASK_STATE.bits(), 0x20);
+        assert_eq!(Xcr0::XCR0_ZMM_HI256_STATE.bits(), 0x40);
+        assert_eq!(Xcr0::XCR0_HI16_ZMM_STATE.bits(), 0x80);
+        assert_eq!(Xcr0::XCR0_PKRU_STATE.bits(), 0x200);
+
+        // What an AVX-512 enabled OS typically sets:
+        let avx512 = Xcr0::XCR0_FPU_MMX_STATE
+            | Xcr0::XCR0_SSE_STATE
+            | Xcr0::XCR0_AVX_STATE
+            | Xcr0::XCR0_OPMASK_STATE
+            | Xcr0::XCR0_ZMM_HI256_STATE
+            | Xcr0::XCR0_HI16_ZMM_STATE;
+        assert_eq!(avx512.bits(), 0xe7);
+    }
+
+    #[test]
+    fn cr3_pcid() {
+        let cr3 = Cr3::new(PAddr::from(0x1234_5000u64), 0xabc);
+        assert_eq!(cr3.as_u64(), 0x1234_5abc);
+        assert_eq!(cr3.pml4_address(), PAddr::from(0x1234_5000u64));
+        assert_eq!(cr3.pcid(), 0xabc);
+
+        let cr3 = Cr3::new(PAddr::from(0x000f_ffff_ffff_f000u64), 0xfff);
+        assert_eq!(cr3.pml4_address(), PAddr::from(0x000f_ffff_ffff_f000u64));
+        assert_eq!(cr3.pcid(), 0xfff);
+        assert_eq!(Cr3::from_raw(cr3.as_u64()), cr3);
+    }
+
+    #[test]
+    fn cr3_flags() {
+        let cr3 = Cr3::with_flags(
+            PAddr::from(0x1000u64),
+            Cr3Flags::CR3_PAGE_LEVEL_CACHE_DISABLE | Cr3Flags::CR3_PAGE_LEVEL_WRITETHROUGH,
+        );
+        assert_eq!(cr3.as_u64(), 0x1018);
+        assert_eq!(cr3.pml4_address(), PAddr::from(0x1000u64));
+        assert_eq!(
+            cr3.flags(),
+            Cr3Flags::CR3_PAGE_LEVEL_CACHE_DISABLE | Cr3Flags::CR3_PAGE_LEVEL_WRITETHROUGH
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn cr3_pcid_too_large() {
+        Cr3::new(PAddr::from(0x1000u64), 0x1000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn cr3_unaligned() {
+        Cr3::new(PAddr::from(0x1001u64), 0);
+    }
+}
+
+#[cfg(all(test, feature = "vmtest"))]
+mod x86testing {
+    use super::*;
+    use x86test::*;
+
+    #[cfg(target_arch = "x86_64")]
+    #[x86test]
+    fn check_cr8() {
+        unsafe {
+            let tpr = cr8();
+            cr8_write(0x0f);
+            kassert!(cr8() == 0x0f, "cr8 didn't return the written priority");
+            cr8_write(tpr);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Support for the CPUID instructions.
+//!
+//! Re-exports the [raw_cpuid](https://docs.rs/raw-cpuid) crate, which decodes
+//! most leaves, and adds a couple of short-cuts for quick feature checks.
+
+use bitflags::*;
+
+pub use crate::arch::CpuidResult;
+pub use raw_cpuid::*;
+
+/// Execute CPUID for `leaf` (EAX) and `subleaf` (ECX).
+///
+/// Leaves that don't have sub-leaves ignore `subleaf`.
+pub fn cpuid(leaf: u32, subleaf: u32) -> CpuidResult {
+    // `__cpuid_count` is only safe to call on recent compilers
+    #[allow(unused_unsafe)]
+    unsafe {
+        crate::arch::__cpuid_count(leaf, subleaf)
+    }
+}
+
+bitflags! {
+    /// Frequently checked features from CPUID leaf 1 (ECX, EDX) and
+    /// leaf 7 sub-leaf 0 (EBX).
+    ///
+    /// The bit positions of this type are not the ones used by CPUID.
+    pub struct FeatureFlags: u64 {
+        // Leaf 1 EDX
+        const TSC = bit!(0);
+        const MSR = bit!(1);
+        const PAE = bit!(2);
+        const APIC = bit!(3);
+        const FXSR = bit!(4);
+        const SSE = bit!(5);
+        const SSE2 = bit!(6);
+        // Leaf 1 ECX
+        const SSE3 = bit!(16);
+        const SSSE3 = bit!(17);
+        const FMA = bit!(18);
+        const PCID = bit!(19);
+        const SSE4_1 = bit!(20);
+        const SSE4_2 = bit!(21);
+        const X2APIC = bit!(22);
+        const POPCNT = bit!(23);
+        const AES = bit!(24);
+        const XSAVE = bit!(25);
+        const OSXSAVE = bit!(26);
+        const AVX = bit!(27);
+        const RDRAND = bit!(28);
+        const HYPERVISOR = bit!(29);
+        // Leaf 7 EBX
+        const FSGSBASE = bit!(32);
+        const BMI1 = bit!(33);
+        const AVX2 = bit!(34);
+        const SMEP = bit!(35);
+        const BMI2 = bit!(36);
+        const INVPCID = bit!(37);
+        const AVX512F = bit!(38);
+        const RDSEED = bit!(39);
+        const ADX = bit!(40);
+        const SMAP = bit!(41);
+    }
+}
+
+impl FeatureFlags {
+    /// Decode the flags from the register values of leaf 1 and leaf 7.
+    pub fn from_leaves(leaf1: CpuidResult, leaf7: CpuidResult) -> FeatureFlags {
+        const LEAF1_EDX: [(u32, FeatureFlags); 7] = [
+            (4, FeatureFlags::TSC),
+            (5, FeatureFlags::MSR),
+            (6, FeatureFlags::PAE),
+            (9, FeatureFlags::APIC),
+            (24, FeatureFlags::FXSR),
+            (25, FeatureFlags::SSE),
+            (26, FeatureFlags::SSE2),
+        ];
+        const LEAF1_ECX: [(u32, FeatureFlags); 14] = [
+            (0, FeatureFlags::SSE3),
+            (9, FeatureFlags::SSSE3),
+            (12, FeatureFlags::FMA),
+            (17, FeatureFlags::PCID),
+            (19, FeatureFlags::SSE4_1),
+            (20, FeatureFlags::SSE4_2),
+            (21, FeatureFlags::X2APIC),
+            (23, FeatureFlags::POPCNT),
+            (25, FeatureFlags::AES),
+            (26, FeatureFlags::XSAVE),
+            (27, FeatureFlags::OSXSAVE),
+            (28, FeatureFlags::AVX),
+            (30, FeatureFlags::RDRAND),
+            (31, FeatureFlags::HYPERVISOR),
+        ];
+        const LEAF7_EBX: [(u32, FeatureFlags); 10] = [
+            (0, FeatureFlags::FSGSBASE),
+            (3, FeatureFlags::BMI1),
+            (5, FeatureFlags::AVX2),
+            (7, FeatureFlags::SMEP),
+            (8, FeatureFlags::BMI2),
+            (10, FeatureFlags::INVPCID),
+            (16, FeatureFlags::AVX512F),
+            (18, FeatureFlags::RDSEED),
+            (19, FeatureFlags::ADX),
+            (20, FeatureFlags::SMAP),
+        ];
+
+        let mut flags = FeatureFlags::empty();
+        for (reg, table) in [
+            (leaf1.edx, &LEAF1_EDX[..]),
+            (leaf1.ecx, &LEAF1_ECX[..]),
+            (leaf7.ebx, &LEAF7_EBX[..]),
+        ] {
+            for (bit, flag) in table {
+                if reg & (1 << bit) != 0 {
+                    flags |= *flag;
+                }
+            }
+        }
+        flags
+    }
+}
+
+/// Returns the features supported by the current CPU.
+pub fn features() -> FeatureFlags {
+    let max_leaf = cpuid(0, 0).eax;
+    let leaf1 = cpuid(1, 0);
+    let leaf7 = if max_leaf >= 7 {
+        cpuid(7, 0)
+    } else {
+        CpuidResult {
+            eax: 0,
+            ebx: 0,
+            ecx: 0,
+            edx: 0,
+        }
+    };
+    FeatureFlags::from_leaves(leaf1, leaf7)
+}
+
+/// Properties of one cache as reported by CPUID leaf 4
+/// (Deterministic Cache Parameters).
+///
+/// Named differently from [CacheInfo] which describes the leaf 2 descriptors.
+#[derive(Debug, Eq, PartialEq)]
+pub struct CacheDescription {
+    /// Cache level, starting at 1.
+    pub level: u8,
+    /// Data, instruction or unified cache.
+    pub cache_type: CacheType,
+    /// Size of a cache line in bytes.
+    pub line_size: u32,
+    /// Number of ways of associativity.
+    pub ways: u32,
+    /// Number of physical line partitions.
+    pub partitions: u32,
+    /// Number of sets.
+    pub sets: u32,
+}
+
+impl CacheDescription {
+    /// Decode the register values of a leaf 4 sub-leaf.
+    ///
+    /// Returns `None` for the null type which terminates the list of caches.
+    pub fn from_leaf4(regs: CpuidResult) -> Option<CacheDescription> {
+        let cache_type = match regs.eax & 0x1f {
+            0 => return None,
+            1 => CacheType::Data,
+            2 => CacheType::Instruction,
+            3 => CacheType::Unified,
+            _ => CacheType::Reserved,
+        };
+
+        Some(CacheDescription {
+            level: ((regs.eax >> 5) & 0b111) as u8,
+            cache_type,
+            line_size: (regs.ebx & 0xfff) + 1,
+            partitions: ((regs.ebx >> 12) & 0x3ff) + 1,
+            ways: (regs.ebx >> 22) + 1,
+            sets: regs.ecx + 1,
+        })
+    }
+
+    /// Total size of the cache in bytes.
+    pub fn size(&self) -> usize {
+        self.line_size as usize * self.partitions as usize * self.ways as usize * self.sets as usize
+    }
+}
+
+/// Iterates over the caches of the current CPU using CPUID leaf 4.
+///
+/// Leaf 4 is Intel specific: the iterator is empty for processors that don't
+/// support it.
+pub fn cache_topology() -> impl Iterator<Item = CacheDescription> {
+    let supported = cpuid(0, 0).eax >= 4;
+    (0..)
+        .take_while(move |_| supported)
+        .map(|subleaf| CacheDescription::from_leaf4(cpuid(4, subleaf)))
+        .take_while(|cache| cache.is_some())
+        .flatten()
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    fn regs(ebx: u32, ecx: u32, edx: u32) -> CpuidResult {
+        CpuidResult {
+            eax: 0,
+            ebx,
+            ecx,
+            edx,
+        }
+    }
+
+    #[test]
+    fn decode_feature_flags() {
+        let leaf1 = regs(0, 1 << 28 | 1 << 26 | 1 << 0, 1 << 26 | 1 << 25);
+        let leaf7 = regs(1 << 20 | 1 << 7 | 1 << 5, 0, 0);
+        assert_eq!(
+            FeatureFlags::from_leaves(leaf1, leaf7),
+            FeatureFlags::SSE
+                | FeatureFlags::SSE2
+                | FeatureFlags::SSE3
+                | FeatureFlags::XSAVE
+                | FeatureFlags::AVX
+                | FeatureFlags::AVX2
+                | FeatureFlags::SMEP
+                | FeatureFlags::SMAP
+        );
+        assert!(FeatureFlags::from_leaves(regs(0, 0, 0), regs(0, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn cpuid_matches_raw_cpuid() {
+        let leaf0 = cpuid(0, 0);
+        let vendor = CpuId::new().get_vendor_info().unwrap();
+        let mut name = [0u8; 12];
+        name[..4].copy_from_slice(&leaf0.ebx.to_le_bytes());
+        name[4..8].copy_from_slice(&leaf0.edx.to_le_bytes());
+        name[8..].copy_from_slice(&leaf0.ecx.to_le_bytes());
+        assert_eq!(&name[..], vendor.as_str().as_bytes());
+    }
+
+    #[test]
+    fn decode_cache_description() {
+        // 48 KiB L1 data cache: 12 ways, 64 sets, 64 byte lines
+        let l1d = CpuidResult {
+            eax: 0x1c00_4121,
+            ebx: 0x02c0_003f,
+            ecx: 0x0000_003f,
+            edx: 0,
+        };
+        let cache = CacheDescription::from_leaf4(l1d).unwrap();
+        assert_eq!(cache.level, 1);
+        assert_eq!(cache.cache_type, CacheType::Data);
+        assert_eq!(cache.line_size, 64);
+        assert_eq!(cache.ways, 12);
+        assert_eq!(cache.partitions, 1);
+        assert_eq!(cache.sets, 64);
+        assert_eq!(cache.size(), 48 * 1024);
+
+        assert_eq!(CacheDescription::from_leaf4(regs(0, 0, 0)), None);
+    }
+
+    #[test]
+    #[ignore]
+    fn host_l1d_line_size() {
+        // Depends on the host CPU, but 64 byte lines are what everyone uses.
+        let l1d = cache_topology()
+            .find(|cache| cache.level == 1 && cache.cache_type == CacheType::Data)
+            .expect("No L1 data cache found");
+        assert_eq!(l1d.line_size, 64);
+    }
+
+    #[test]
+    #[ignore]
+    fn host_has_sse2() {
+        // Every x86-64 CPU has SSE2, we only might run into trouble on ancient 32-bit hosts.
+        assert!(features().contains(FeatureFlags::SSE | FeatureFlags::SSE2));
+    }
+}
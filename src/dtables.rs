@@ -49,11 +49,31 @@ impl<T> DescriptorTablePointer<T> {
     }
 }
 
+impl<T> DescriptorTablePointer<T> {
+    /// Number of (complete) `T` entries covered by the limit.
+    pub fn entries(&self) -> usize {
+        match size_of::<T>() {
+            0 => 0,
+            size => (self.limit as usize + 1) / size,
+        }
+    }
+}
+
+impl<T> fmt::Display for DescriptorTablePointer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "base={:#x} limit={:#x} ({} entries)",
+            { self.base } as usize,
+            { self.limit },
+            self.entries()
+        )
+    }
+}
+
 impl<T> fmt::Debug for DescriptorTablePointer<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "DescriptorTablePointer ({} {:?})", { self.limit }, {
-            self.base
-        })
+        write!(f, "DescriptorTablePointer({})", self)
     }
 }
 
@@ -68,9 +88,17 @@ pub unsafe fn lgdt<T>(gdt: &DescriptorTablePointer<T>) {
 /// Retrieve base and limit from the GDTR register.
 ///
 /// # Safety
-/// Needs CPL 0.
-pub unsafe fn sgdt<T>(idt: &mut DescriptorTablePointer<T>) {
-    asm!("sgdt ({0})", in(reg) idt as *mut DescriptorTablePointer<T>, options(att_syntax));
+/// `sgdt` is not a privileged instruction and can be executed in user-mode,
+/// unless the OS enabled `CR4.UMIP` in which case it raises a #GP.
+pub unsafe fn sgdt<T>() -> DescriptorTablePointer<T> {
+    let mut gdtr: DescriptorTablePointer<T> = Default::default();
+    store_gdtr(&mut gdtr);
+    gdtr
+}
+
+/// Stores the GDTR register to `gdtr`.
+unsafe fn store_gdtr<T>(gdtr: &mut DescriptorTablePointer<T>) {
+    asm!("sgdt ({0})", in(reg) gdtr as *mut DescriptorTablePointer<T>, options(att_syntax));
 }
 
 /// Loads the segment selector into the selector field of the local
@@ -83,10 +111,19 @@ pub unsafe fn sgdt<T>(idt: &mut DescriptorTablePointer<T>) {
 ///
 /// # Safety
 /// Needs CPL 0.
-pub unsafe fn load_ldtr(selector: SegmentSelector) {
+pub unsafe fn lldt(selector: SegmentSelector) {
     asm!("lldt {0:x}", in(reg) selector.bits(), options(att_syntax));
 }
 
+/// Loads the segment selector into the selector field of the local
+/// descriptor table register (LDTR), same as [lldt].
+///
+/// # Safety
+/// Needs CPL 0.
+pub unsafe fn load_ldtr(selector: SegmentSelector) {
+    lldt(selector);
+}
+
 /// Returns the segment selector from the local descriptor table register (LDTR).
 ///
 /// The returned segment selector points to the segment descriptor
@@ -111,22 +148,81 @@ pub unsafe fn lidt<T>(idt: &DescriptorTablePointer<T>) {
 /// Retrieve base and limit from the IDTR register.
 ///
 /// # Safety
-/// Needs CPL 0.
-pub unsafe fn sidt<T>(idt: &mut DescriptorTablePointer<T>) {
-    asm!("sidt ({0})", in(reg) idt as *mut DescriptorTablePointer<T>, options(att_syntax));
+/// `sidt` is not a privileged instruction and can be executed in user-mode,
+/// unless the OS enabled `CR4.UMIP` in which case it raises a #GP.
+pub unsafe fn sidt<T>() -> DescriptorTablePointer<T> {
+    let mut idtr: DescriptorTablePointer<T> = Default::default();
+    store_idtr(&mut idtr);
+    idtr
+}
+
+/// Stores the IDTR register to `idtr`.
+unsafe fn store_idtr<T>(idtr: &mut DescriptorTablePointer<T>) {
+    asm!("sidt ({0})", in(reg) idtr as *mut DescriptorTablePointer<T>, options(att_syntax));
 }
 
 #[cfg(all(test, feature = "utest"))]
 mod test {
     use super::*;
 
+    #[test]
+    fn pointer_from_slice() {
+        let table = [0u64; 16];
+        let ptr = DescriptorTablePointer::new_from_slice(&table);
+        let (limit, base) = (ptr.limit, ptr.base);
+        assert_eq!(limit, 16 * 8 - 1);
+        assert_eq!(base, table.as_ptr());
+
+        let ptr = DescriptorTablePointer::new_from_slice(&table[..1]);
+        let limit = ptr.limit;
+        assert_eq!(limit, 7);
+    }
+
+    #[test]
+    fn pointer_from_table() {
+        let table = [0u128; 256];
+        let ptr = DescriptorTablePointer::new(&table);
+        let (limit, base) = (ptr.limit, ptr.base);
+        assert_eq!(limit, 0xfff);
+        assert_eq!(base, &table as *const [u128; 256]);
+    }
+
+    #[test]
+    fn pointer_format() {
+        extern crate std;
+        use crate::bits64::segmentation::Descriptor64;
+        use std::format;
+
+        let mut ptr = DescriptorTablePointer::new_from_slice(&[Descriptor64::NULL; 3]);
+        assert_eq!(ptr.entries(), 3);
+        ptr.base = 0xffff_8000_0010_0000 as *const Descriptor64;
+        assert_eq!(
+            format!("{}", ptr),
+            "base=0xffff800000100000 limit=0x2f (3 entries)"
+        );
+        assert_eq!(
+            format!("{:?}", ptr),
+            "DescriptorTablePointer(base=0xffff800000100000 limit=0x2f (3 entries))"
+        );
+
+        let empty: DescriptorTablePointer<u64> = Default::default();
+        assert_eq!(format!("{}", empty), "base=0x0 limit=0x0 (0 entries)");
+    }
+
+    #[test]
+    #[should_panic]
+    fn pointer_too_large() {
+        let table = [0u64; 8193];
+        DescriptorTablePointer::new_from_slice(&table);
+    }
+
     #[test]
     fn check_sgdt() {
         let mut gdtr: super::DescriptorTablePointer<u64> = Default::default();
         gdtr.limit = 0xdead;
         gdtr.base = 0xbadc0de as *mut u64;
         unsafe {
-            sgdt(&mut gdtr);
+            store_gdtr(&mut gdtr);
         }
         let base = gdtr.base;
         let limit = gdtr.limit;
@@ -141,7 +237,7 @@ mod test {
         gdtr.limit = 0xdead;
         gdtr.base = 0xbadc0de as *mut u64;
         unsafe {
-            sidt(&mut gdtr);
+            store_idtr(&mut gdtr);
         }
         let base = gdtr.base;
         let limit = gdtr.limit;
@@ -149,4 +245,16 @@ mod test {
         assert_ne!(limit, 0xdead);
         assert_ne!(base as u64, 0xbadc0de);
     }
+
+    #[test]
+    #[ignore]
+    fn sgdt_sidt_host() {
+        // Needs a host where UMIP is disabled: with UMIP enabled Linux emulates
+        // sgdt/sidt for user-space and reports dummy tables with a zero limit.
+        let gdtr: DescriptorTablePointer<u64> = unsafe { sgdt() };
+        let idtr: DescriptorTablePointer<u64> = unsafe { sidt() };
+        let (gdt_limit, idt_limit) = (gdtr.limit, idtr.limit);
+        assert_ne!(gdt_limit, 0);
+        assert_ne!(idt_limit, 0);
+    }
 }
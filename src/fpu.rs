@@ -0,0 +1,256 @@
+//! Saving and restoring the floating point and extended processor state.
+//!
+//! See Intel 1, Section 10.5 "FXSAVE and FXRSTOR Instructions" and
+//! Chapter 13 "Managing State Using the XSAVE Feature Set".
+
+#[cfg(target_arch = "x86")]
+use crate::arch::{_fxrstor, _fxsave, _xrstor, _xsave};
+#[cfg(target_arch = "x86_64")]
+use crate::arch::{
+    _fxrstor64 as _fxrstor, _fxsave64 as _fxsave, _xrstor64 as _xrstor, _xsave64 as _xsave,
+};
+
+/// The 512-byte legacy x87 FPU, MMX and SSE state as saved by [fxsave].
+///
+/// Uses the 64-bit layout (`fip` and `fdp` are 64-bit offsets); on 32-bit
+/// the upper halves contain the CS and DS selectors instead.
+///
+/// See Intel 1, Section 10.5.1 "FXSAVE Area".
+#[derive(Clone, Copy, Debug)]
+#[repr(C, align(16))]
+pub struct FxsaveArea {
+    /// x87 FPU control word.
+    pub fcw: u16,
+    /// x87 FPU status word.
+    pub fsw: u16,
+    /// Abridged x87 FPU tag word.
+    pub ftw: u8,
+    pub reserved: u8,
+    /// Opcode of the last x87 instruction.
+    pub fop: u16,
+    /// Instruction pointer of the last x87 instruction.
+    pub fip: u64,
+    /// Data pointer of the last x87 instruction.
+    pub fdp: u64,
+    /// MXCSR control and status register.
+    pub mxcsr: u32,
+    /// Bits of MXCSR supported by the processor.
+    pub mxcsr_mask: u32,
+    /// ST0-ST7 / MM0-MM7 (80 bits used each).
+    pub st: [u128; 8],
+    /// XMM0-XMM15 (XMM0-XMM7 on 32-bit).
+    pub xmm: [u128; 16],
+    pub reserved2: [u8; 48],
+    /// Available to software, ignored by the processor.
+    pub available: [u8; 48],
+}
+
+impl FxsaveArea {
+    /// x87 control word after `fninit`: all exceptions masked,
+    /// round to nearest, 64-bit precision.
+    pub const DEFAULT_FCW: u16 = 0x037f;
+
+    /// MXCSR after reset: all exceptions masked, round to nearest.
+    pub const DEFAULT_MXCSR: u32 = 0x1f80;
+
+    /// Creates an area holding the initial FPU and SSE state.
+    pub const fn new() -> FxsaveArea {
+        FxsaveArea {
+            fcw: FxsaveArea::DEFAULT_FCW,
+            fsw: 0,
+            ftw: 0,
+            reserved: 0,
+            fop: 0,
+            fip: 0,
+            fdp: 0,
+            mxcsr: FxsaveArea::DEFAULT_MXCSR,
+            mxcsr_mask: 0,
+            st: [0; 8],
+            xmm: [0; 16],
+            reserved2: [0; 48],
+            available: [0; 48],
+        }
+    }
+
+    /// Returns the saved MXCSR register.
+    pub fn mxcsr(&self) -> u32 {
+        self.mxcsr
+    }
+
+    /// Sets the MXCSR value to be restored by [fxrstor].
+    ///
+    /// Note that setting bits not in `mxcsr_mask` makes [fxrstor] raise a #GP.
+    pub fn set_mxcsr(&mut self, mxcsr: u32) {
+        self.mxcsr = mxcsr;
+    }
+
+    /// Returns the saved x87 FPU control word.
+    pub fn fpu_control_word(&self) -> u16 {
+        self.fcw
+    }
+
+    /// Sets the x87 FPU control word to be restored by [fxrstor].
+    pub fn set_fpu_control_word(&mut self, fcw: u16) {
+        self.fcw = fcw;
+    }
+}
+
+impl Default for FxsaveArea {
+    fn default() -> FxsaveArea {
+        FxsaveArea::new()
+    }
+}
+
+/// Saves the x87 FPU, MMX and SSE state to `area`.
+///
+/// # Safety
+/// Needs CPUID.01H:EDX.FXSR\[bit 24\]. Only saves the SSE state if CR4.OSFXSR is set.
+pub unsafe fn fxsave(area: &mut FxsaveArea) {
+    _fxsave(area as *mut FxsaveArea as *mut u8);
+}
+
+/// Restores the x87 FPU, MMX and SSE state from `area`.
+///
+/// # Safety
+/// Needs CPUID.01H:EDX.FXSR\[bit 24\]. Raises #GP if reserved MXCSR bits are set.
+pub unsafe fn fxrstor(area: &FxsaveArea) {
+    _fxrstor(area as *const FxsaveArea as *const u8);
+}
+
+/// Required alignment of an XSAVE area.
+pub const XSAVE_AREA_ALIGNMENT: usize = 64;
+
+/// Size of the legacy region (the FXSAVE layout) plus the XSAVE header.
+const XSAVE_MIN_SIZE: usize = 512 + 64;
+
+/// Returns the size of an XSAVE area for the features currently
+/// enabled in XCR0, rounded up to a multiple of 64 bytes.
+///
+/// From CPUID.(EAX=0DH, ECX=0H):EBX, returns the minimal size
+/// (legacy region and header) in case leaf 0xD is not supported.
+pub fn xsave_area_size() -> usize {
+    let size = if crate::cpuid::cpuid(0, 0).eax >= 0xd {
+        crate::cpuid::cpuid(0xd, 0).ebx as usize
+    } else {
+        0
+    };
+    let size = (size + XSAVE_AREA_ALIGNMENT - 1) & !(XSAVE_AREA_ALIGNMENT - 1);
+    size.max(XSAVE_MIN_SIZE)
+}
+
+/// Saves the state components selected by `mask` (and enabled in XCR0) to `area`.
+///
+/// # Panics
+/// In case `area` is not 64-byte aligned or smaller than [xsave_area_size].
+///
+/// # Safety
+/// Needs CR4.OSXSAVE set (#UD otherwise).
+pub unsafe fn xsave(area: &mut [u8], mask: u64) {
+    assert_eq!(
+        area.as_ptr() as usize % XSAVE_AREA_ALIGNMENT,
+        0,
+        "XSAVE area must be 64-byte aligned"
+    );
+    assert!(area.len() >= xsave_area_size(), "XSAVE area too small");
+    _xsave(area.as_mut_ptr(), mask);
+}
+
+/// Restores the state components selected by `mask` from `area`.
+///
+/// Components selected by `mask` but not present in the XSTATE_BV
+/// field of the header are set to their initial state.
+///
+/// # Panics
+/// In case `area` is not 64-byte aligned or smaller than [xsave_area_size].
+///
+/// # Safety
+/// Needs CR4.OSXSAVE set (#UD otherwise). `area` must contain valid state
+/// as written by [xsave], a malformed header raises #GP.
+pub unsafe fn xrstor(area: &[u8], mask: u64) {
+    assert_eq!(
+        area.as_ptr() as usize % XSAVE_AREA_ALIGNMENT,
+        0,
+        "XSAVE area must be 64-byte aligned"
+    );
+    assert!(area.len() >= xsave_area_size(), "XSAVE area too small");
+    _xrstor(area.as_ptr(), mask);
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+    use crate::cpuid::{features, FeatureFlags};
+    use core::convert::TryInto;
+
+    #[repr(align(64))]
+    struct Aligned<T>(T);
+
+    #[test]
+    fn fxsave_area_layout() {
+        use core::mem::{align_of, size_of};
+        use core::ptr::addr_of;
+
+        assert_eq!(size_of::<FxsaveArea>(), 512);
+        assert_eq!(align_of::<FxsaveArea>(), 16);
+
+        let area = FxsaveArea::new();
+        let base = &area as *const FxsaveArea as usize;
+        assert_eq!(addr_of!(area.mxcsr) as usize - base, 24);
+        assert_eq!(addr_of!(area.st) as usize - base, 32);
+        assert_eq!(addr_of!(area.xmm) as usize - base, 160);
+        assert_eq!(addr_of!(area.available) as usize - base, 464);
+    }
+
+    #[test]
+    fn fxsave_round_trip() {
+        let mut area = FxsaveArea::new();
+        unsafe {
+            fxsave(&mut area);
+            let current = area.mxcsr();
+            assert_ne!(area.mxcsr_mask, 0);
+
+            // Toggle flush-to-zero and check it is restored:
+            area.set_mxcsr(current ^ (1 << 15));
+            fxrstor(&area);
+            let mut check = FxsaveArea::new();
+            fxsave(&mut check);
+            assert_eq!(check.mxcsr(), current ^ (1 << 15));
+            assert_eq!(check.fpu_control_word(), area.fpu_control_word());
+
+            // Put the original state back:
+            area.set_mxcsr(current);
+            fxrstor(&area);
+        }
+    }
+
+    #[test]
+    fn area_size() {
+        let size = xsave_area_size();
+        assert_eq!(size % 64, 0);
+        assert!(size >= 512);
+    }
+
+    #[test]
+    fn save_and_restore() {
+        if !features().contains(FeatureFlags::OSXSAVE) || xsave_area_size() > 16384 {
+            return;
+        }
+
+        let mut area = Aligned([0u8; 16384]);
+        unsafe {
+            // x87 and SSE state
+            xsave(&mut area.0, 0b11);
+            // XSTATE_BV (first 8 bytes of the header) only contains saved components:
+            let xstate_bv = u64::from_le_bytes(area.0[512..520].try_into().unwrap());
+            assert_eq!(xstate_bv & !0b11, 0);
+            xrstor(&area.0, 0b11);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "aligned")]
+    fn misaligned_area() {
+        let mut area = Aligned([0u8; 16384 + 8]);
+        unsafe { xsave(&mut area.0[8..], 0b11) };
+    }
+}
@@ -1,6 +1,7 @@
 //! I/O port functionality.
 
 use core::arch::asm;
+use core::marker::PhantomData;
 
 /// Write 8 bits to port
 ///
@@ -62,6 +63,238 @@ pub unsafe fn inl(port: u16) -> u32 {
     ret
 }
 
+/// Read `buf.len()` times 8 bits from port into `buf` (`rep insb`).
+///
+/// # Safety
+/// Needs IO privileges.
+#[inline]
+pub unsafe fn insb(port: u16, buf: &mut [u8]) {
+    if buf.is_empty() {
+        return;
+    }
+    asm!("rep insb",
+         in("dx") port,
+         inout("edi") buf.as_mut_ptr() => _,
+         inout("ecx") buf.len() => _,
+         options(att_syntax, nostack, preserves_flags));
+}
+
+/// Write `buf` to port, 8 bits at a time (`rep outsb`).
+///
+/// # Safety
+/// Needs IO privileges.
+#[inline]
+pub unsafe fn outsb(port: u16, buf: &[u8]) {
+    if buf.is_empty() {
+        return;
+    }
+    asm!("rep outsb",
+         in("dx") port,
+         inout("esi") buf.as_ptr() => _,
+         inout("ecx") buf.len() => _,
+         options(att_syntax, nostack, preserves_flags, readonly));
+}
+
+/// Read `buf.len()` times 16 bits from port into `buf` (`rep insw`).
+///
+/// # Safety
+/// Needs IO privileges.
+#[inline]
+pub unsafe fn insw(port: u16, buf: &mut [u16]) {
+    if buf.is_empty() {
+        return;
+    }
+    asm!("rep insw",
+         in("dx") port,
+         inout("edi") buf.as_mut_ptr() => _,
+         inout("ecx") buf.len() => _,
+         options(att_syntax, nostack, preserves_flags));
+}
+
+/// Write `buf` to port, 16 bits at a time (`rep outsw`).
+///
+/// # Safety
+/// Needs IO privileges.
+#[inline]
+pub unsafe fn outsw(port: u16, buf: &[u16]) {
+    if buf.is_empty() {
+        return;
+    }
+    asm!("rep outsw",
+         in("dx") port,
+         inout("esi") buf.as_ptr() => _,
+         inout("ecx") buf.len() => _,
+         options(att_syntax, nostack, preserves_flags, readonly));
+}
+
+/// Read `buf.len()` times 32 bits from port into `buf` (`rep insl`).
+///
+/// # Safety
+/// Needs IO privileges.
+#[inline]
+pub unsafe fn insl(port: u16, buf: &mut [u32]) {
+    if buf.is_empty() {
+        return;
+    }
+    asm!("rep insl",
+         in("dx") port,
+         inout("edi") buf.as_mut_ptr() => _,
+         inout("ecx") buf.len() => _,
+         options(att_syntax, nostack, preserves_flags));
+}
+
+/// Write `buf` to port, 32 bits at a time (`rep outsl`).
+///
+/// # Safety
+/// Needs IO privileges.
+#[inline]
+pub unsafe fn outsl(port: u16, buf: &[u32]) {
+    if buf.is_empty() {
+        return;
+    }
+    asm!("rep outsl",
+         in("dx") port,
+         inout("esi") buf.as_ptr() => _,
+         inout("ecx") buf.len() => _,
+         options(att_syntax, nostack, preserves_flags, readonly));
+}
+
+/// Types that can be read from an I/O port (`u8`, `u16` and `u32`).
+pub trait PortRead {
+    /// Read a value from `port`.
+    ///
+    /// # Safety
+    /// Needs IO privileges.
+    unsafe fn read_from_port(port: u16) -> Self;
+}
+
+/// Types that can be written to an I/O port (`u8`, `u16` and `u32`).
+pub trait PortWrite {
+    /// Write `value` to `port`.
+    ///
+    /// # Safety
+    /// Needs IO privileges.
+    unsafe fn write_to_port(port: u16, value: Self);
+}
+
+impl PortRead for u8 {
+    unsafe fn read_from_port(port: u16) -> u8 {
+        inb(port)
+    }
+}
+
+impl PortRead for u16 {
+    unsafe fn read_from_port(port: u16) -> u16 {
+        inw(port)
+    }
+}
+
+impl PortRead for u32 {
+    unsafe fn read_from_port(port: u16) -> u32 {
+        inl(port)
+    }
+}
+
+impl PortWrite for u8 {
+    unsafe fn write_to_port(port: u16, value: u8) {
+        outb(port, value)
+    }
+}
+
+impl PortWrite for u16 {
+    unsafe fn write_to_port(port: u16, value: u16) {
+        outw(port, value)
+    }
+}
+
+impl PortWrite for u32 {
+    unsafe fn write_to_port(port: u16, value: u32) {
+        outl(port, value)
+    }
+}
+
+/// An I/O port that is accessed with a fixed width `T`.
+///
+/// # Example
+///
+/// ```no_run
+/// use x86::io::Port;
+///
+/// // PS/2 controller data port
+/// let data: Port<u8> = Port::new(0x60);
+/// let scancode = unsafe { data.read() };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Port<T> {
+    port: u16,
+    width: PhantomData<T>,
+}
+
+impl<T> Port<T> {
+    /// Create a new port for the given I/O address.
+    pub const fn new(port: u16) -> Port<T> {
+        Port {
+            port,
+            width: PhantomData,
+        }
+    }
+
+    /// Returns the I/O address of the port.
+    pub const fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl<T: PortRead> Port<T> {
+    /// Read a value from the port.
+    ///
+    /// # Safety
+    /// Needs IO privileges.
+    #[inline]
+    pub unsafe fn read(&self) -> T {
+        T::read_from_port(self.port)
+    }
+}
+
+impl<T: PortWrite> Port<T> {
+    /// Write a value to the port.
+    ///
+    /// # Safety
+    /// Needs IO privileges.
+    #[inline]
+    pub unsafe fn write(&mut self, value: T) {
+        T::write_to_port(self.port, value)
+    }
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn port_new() {
+        const KEYBOARD: Port<u8> = Port::new(0x60);
+        let pci_config: Port<u32> = Port::new(0xcf8);
+
+        assert_eq!(KEYBOARD.port(), 0x60);
+        assert_eq!(pci_config.port(), 0xcf8);
+        assert_eq!(Port::<u16>::new(0x1f0), Port::new(0x1f0));
+    }
+
+    #[test]
+    fn string_io_empty() {
+        // Empty buffers don't touch the port, so this works without IO privileges.
+        unsafe {
+            insb(0x1f0, &mut []);
+            insw(0x1f0, &mut []);
+            insl(0x1f0, &mut []);
+            outsb(0x1f0, &[]);
+            outsw(0x1f0, &[]);
+            outsl(0x1f0, &[]);
+        }
+    }
+}
+
 #[cfg(all(test, feature = "vmtest"))]
 mod x86testing {
     use super::*;
@@ -130,6 +363,25 @@ mod x86testing {
         }
     }
 
+    #[x86test(ioport(0x1, 0xad))]
+    fn check_port_read() {
+        let port: Port<u8> = Port::new(0x1);
+        unsafe {
+            kassert!(
+                port.read() == 0xad,
+                "`Port::read` didn't read the correct value"
+            );
+        }
+    }
+
+    #[x86test(ioport(0x5, 0xbeefaaaa))]
+    fn check_port_write() {
+        let mut port: Port<u32> = Port::new(0x5);
+        unsafe {
+            port.write(0xbeefaaaa);
+        }
+    }
+
     #[x86test(ioport(0x4, 0xdeadbeef))]
     fn check_inl() {
         unsafe {
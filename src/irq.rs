@@ -312,6 +312,79 @@ bitflags! {
         /// 0: The fault was not by protection keys.
         /// 1: There was a protection key violation.
         const PK = bit!(5);
+
+        /// 0: The fault was not caused by a shadow-stack access.
+        /// 1: The fault was caused by a shadow-stack access.
+        const SS = bit!(6);
+
+        /// 0: The fault is not related to SGX.
+        /// 1: The fault resulted from violation of SGX-specific access-control requirements.
+        const SGX = bit!(15);
+    }
+}
+
+impl PageFaultError {
+    /// The fault was caused by a page-level protection violation (P).
+    pub const PRESENT: PageFaultError = PageFaultError::P;
+    /// The access causing the fault was a write (W/R).
+    pub const WRITE: PageFaultError = PageFaultError::WR;
+    /// The access causing the fault originated in user mode (U/S).
+    pub const USER: PageFaultError = PageFaultError::US;
+    /// A reserved bit was set in a paging-structure entry (RSVD).
+    ///
+    /// Also available as [Self::RESERVED_WRITE], a misleading name: the bit
+    /// has nothing to do with the access being a write (see [Self::WRITE]).
+    pub const RESERVED_BIT: PageFaultError = PageFaultError::RSVD;
+    /// Same as [Self::RESERVED_BIT].
+    #[doc(alias = "RESERVED_BIT")]
+    pub const RESERVED_WRITE: PageFaultError = PageFaultError::RSVD;
+    /// The fault was caused by an instruction fetch (I/D).
+    pub const INSTRUCTION_FETCH: PageFaultError = PageFaultError::ID;
+    /// There was a protection-key violation (PK).
+    pub const PROTECTION_KEY: PageFaultError = PageFaultError::PK;
+    /// The fault was caused by a shadow-stack access (SS).
+    pub const SHADOW_STACK: PageFaultError = PageFaultError::SS;
+
+    /// A short, human-readable description of the cause of the fault,
+    /// e.g. "user-mode write to present page" for error code 0b111.
+    ///
+    /// Faults caused by reserved bits, SGX, protection keys or shadow-stack
+    /// accesses are described by that cause only.
+    pub fn describe(&self) -> &'static str {
+        if self.contains(PageFaultError::RSVD) {
+            return "reserved bit set in paging-structure entry";
+        }
+        if self.contains(PageFaultError::SGX) {
+            return "SGX access-control violation";
+        }
+        if self.contains(PageFaultError::PK) {
+            return "protection-key violation";
+        }
+        if self.contains(PageFaultError::SS) {
+            return "shadow-stack access violation";
+        }
+
+        let fetch = self.contains(PageFaultError::ID);
+        let write = self.contains(PageFaultError::WR);
+        match (
+            self.contains(PageFaultError::US),
+            fetch,
+            write,
+            self.contains(PageFaultError::P),
+        ) {
+            (false, true, _, false) => "supervisor-mode instruction fetch from non-present page",
+            (false, true, _, true) => "supervisor-mode instruction fetch from present page",
+            (false, false, true, false) => "supervisor-mode write to non-present page",
+            (false, false, true, true) => "supervisor-mode write to present page",
+            (false, false, false, false) => "supervisor-mode read from non-present page",
+            (false, false, false, true) => "supervisor-mode read from present page",
+            (true, true, _, false) => "user-mode instruction fetch from non-present page",
+            (true, true, _, true) => "user-mode instruction fetch from present page",
+            (true, false, true, false) => "user-mode write to non-present page",
+            (true, false, true, true) => "user-mode write to present page",
+            (true, false, false, false) => "user-mode read from non-present page",
+            (true, false, false, true) => "user-mode read from present page",
+        }
     }
 }
 
@@ -364,6 +437,50 @@ pub unsafe fn disable() {
     asm!("cli");
 }
 
+/// Is the interrupt flag (IF) set?
+fn interrupts_enabled() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        crate::bits64::irq::are_enabled()
+    }
+    #[cfg(target_arch = "x86")]
+    {
+        use crate::bits32::eflags::{self, EFlags};
+        unsafe { eflags::read() }.contains(EFlags::FLAGS_IF)
+    }
+}
+
+/// Re-enables interrupts on drop if they were enabled when it was created.
+struct InterruptGuard {
+    was_enabled: bool,
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        if self.was_enabled {
+            unsafe { enable() };
+        }
+    }
+}
+
+/// Run `f` with interrupts disabled.
+///
+/// The previous state of the interrupt flag is restored afterwards,
+/// also if `f` panics and unwinds. Calls can be nested.
+///
+/// # Safety
+/// Only allowed if we have IO privileges for the current operating level in RFlags.
+pub unsafe fn without_interrupts<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let _guard = InterruptGuard {
+        was_enabled: interrupts_enabled(),
+    };
+    disable();
+    f()
+}
+
 /// Generate a software interrupt.
 /// This is a macro argument needs to be an immediate.
 #[macro_export]
@@ -385,4 +502,46 @@ mod test {
         assert!(PageFaultError::WR.bits() == 0b10);
         assert!(PageFaultError::P.bits() == 0b1);
     }
+
+    #[test]
+    fn shadow_stack_and_sgx_bits() {
+        assert!(PageFaultError::SS.bits() == 0b1000000);
+        assert!(PageFaultError::SGX.bits() == 1 << 15);
+    }
+
+    #[test]
+    #[ignore]
+    fn without_interrupts_restores_on_panic() {
+        // Needs IOPL 3 (e.g., iopl(3) as root) otherwise cli/sti raise a #GP.
+        extern crate std;
+
+        assert!(interrupts_enabled());
+        let result = std::panic::catch_unwind(|| unsafe {
+            without_interrupts(|| {
+                assert!(!interrupts_enabled());
+                panic!("in critical section");
+            })
+        });
+        assert!(result.is_err());
+        assert!(interrupts_enabled());
+    }
+}
+
+#[cfg(all(test, feature = "vmtest"))]
+mod x86testing {
+    use super::*;
+    use x86test::*;
+
+    #[x86test]
+    fn check_without_interrupts() {
+        unsafe {
+            let before = interrupts_enabled();
+            let r = without_interrupts(|| {
+                kassert!(!interrupts_enabled(), "IF set in closure");
+                without_interrupts(|| 42)
+            });
+            kassert!(r == 42, "Wrong return value");
+            kassert!(interrupts_enabled() == before, "IF not restored");
+        }
+    }
 }
@@ -21,10 +21,13 @@ pub mod bits32;
 pub mod bits64;
 
 pub mod apic;
+pub mod cache;
 pub mod controlregs;
+pub mod cpuid;
 pub mod debugregs;
 pub mod dtables;
 pub mod fence;
+pub mod fpu;
 pub mod io;
 pub mod irq;
 pub mod msr;
@@ -35,7 +38,7 @@ pub mod time;
 pub mod tlb;
 pub mod vmx;
 
-#[cfg(feature = "performance-counter")]
+#[cfg(any(feature = "performance-counter", feature = "no-phf"))]
 pub mod perfcnt;
 
 /// A short-cut to the architecture (bits32 or bits64) this crate was compiled for.
@@ -46,11 +49,6 @@ pub mod current {
     pub use crate::bits64::*;
 }
 
-/// Support for the CPUID instructions.
-pub mod cpuid {
-    pub use raw_cpuid::*;
-}
-
 #[cfg(not(test))]
 mod std {
     pub use core::fmt;
@@ -92,6 +90,98 @@ pub unsafe fn halt() {
     asm!("hlt", options(att_syntax, nomem, nostack)); // check if preserves_flags
 }
 
+/// Improves the performance of spin-wait loops.
+///
+/// `pause` hints the processor that the code is a busy-wait loop: this avoids
+/// the memory order violation (and pipeline flush) when the loop exits and
+/// reduces power consumption as well as contention with a sibling hyper-thread
+/// while spinning.
+#[inline(always)]
+pub fn pause() {
+    unsafe { asm!("pause", options(nomem, nostack, preserves_flags)) };
+}
+
+/// Alias for [pause], to be called in the body of a spin loop.
+#[inline(always)]
+pub fn spin_loop_hint() {
+    pause()
+}
+
+/// Does the processor support `monitor` and `mwait` (CPUID.01H:ECX.MONITOR\[bit 3\])?
+pub fn has_monitor_mwait() -> bool {
+    cpuid::CpuId::new()
+        .get_feature_info()
+        .is_some_and(|finfo| finfo.has_monitor_mwait())
+}
+
+/// Sets up `addr` as the address range monitored by a subsequent [mwait].
+///
+/// `extensions` is passed in ECX and `hints` in EDX (no extensions or hints
+/// are defined currently, pass 0).
+///
+/// # Example
+///
+/// ```no_run
+/// use core::sync::atomic::{AtomicBool, Ordering};
+/// use x86::bits64::paging::VAddr;
+///
+/// static WORK_PENDING: AtomicBool = AtomicBool::new(false);
+///
+/// # unsafe fn idle() {
+/// assert!(x86::has_monitor_mwait());
+/// while !WORK_PENDING.load(Ordering::Acquire) {
+///     x86::monitor(VAddr::from(WORK_PENDING.as_ptr()), 0, 0);
+///     // Re-check to not miss a write that happened before `monitor`.
+///     if !WORK_PENDING.load(Ordering::Acquire) {
+///         // Sleep in C1 until the flag is written (or an interrupt arrives):
+///         x86::mwait(0, 0);
+///     }
+/// }
+/// # }
+/// ```
+///
+/// # Safety
+/// Raises #UD if MONITOR/MWAIT is unsupported (see [has_monitor_mwait]) or
+/// if used outside of ring 0 (unless enabled for ring 3 by the OS).
+#[inline(always)]
+pub unsafe fn monitor(addr: bits64::paging::VAddr, extensions: u32, hints: u32) {
+    #[cfg(target_arch = "x86_64")]
+    asm!(
+        "monitor",
+        in("rax") addr.as_usize(),
+        in("ecx") extensions,
+        in("edx") hints,
+        options(nostack, preserves_flags)
+    );
+    #[cfg(target_arch = "x86")]
+    asm!(
+        "monitor",
+        in("eax") addr.as_usize(),
+        in("ecx") extensions,
+        in("edx") hints,
+        options(nostack, preserves_flags)
+    );
+}
+
+/// Waits until the address range set up by [monitor] is written to or an
+/// interrupt (or other event) occurs.
+///
+/// `hints` (EAX) selects the target C-state, `extensions` (ECX) bit 0
+/// treats interrupts as break events even if they are masked.
+///
+/// # Safety
+/// Raises #UD if MONITOR/MWAIT is unsupported (see [has_monitor_mwait]) or
+/// if used outside of ring 0 (unless enabled for ring 3 by the OS).
+#[inline(always)]
+pub unsafe fn mwait(hints: u32, extensions: u32) {
+    asm!(
+        "mwait",
+        in("eax") hints,
+        in("ecx") extensions,
+        options(nostack, preserves_flags)
+    );
+}
+
 #[cfg(all(test, feature = "vmtest"))]
 mod x86testing {
     use super::*;
@@ -127,6 +217,17 @@ pub unsafe fn rdpid() -> u64 {
 mod test {
     use super::*;
 
+    #[test]
+    fn spin_with_pause() {
+        let mut iterations = 0;
+        while iterations < 1000 {
+            pause();
+            spin_loop_hint();
+            iterations += 1;
+        }
+        assert_eq!(iterations, 1000);
+    }
+
     #[test]
     fn test_rdpid() {
         let rdpid_support = cpuid::CpuId::new()
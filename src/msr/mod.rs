@@ -1,6 +1,13 @@
 //! MSR value list and function to read and write them.
 
+use bitflags::*;
+
 use core::arch::asm;
+use core::marker::PhantomData;
+
+use crate::bits64::paging::PAddr;
+
+pub mod mtrr;
 
 /// Write 64 bits to msr register.
 ///
@@ -23,6 +30,437 @@ pub unsafe fn rdmsr(msr: u32) -> u64 {
     ((high as u64) << 32) | (low as u64)
 }
 
+/// A value with a known layout that lives in a specific MSR.
+pub trait MsrValue: Sized {
+    /// Index of the MSR holding this value.
+    const INDEX: u32;
+
+    /// Bits of the MSR not covered by this type. They are read back and
+    /// preserved when the value is written.
+    const RESERVED: u64 = 0;
+
+    /// Decode the raw MSR contents.
+    fn from_raw(raw: u64) -> Self;
+
+    /// Encode the value, only the bits outside of `RESERVED` are used.
+    fn to_raw(&self) -> u64;
+}
+
+/// A model specific register.
+///
+/// `Msr` (i.e., `Msr<u64>`) is an untyped register given by its index,
+/// `Msr<T>` reads and writes the [`MsrValue`] `T`:
+///
+/// ```no_run
+/// use x86::msr::{Efer, Msr, IA32_TSC_AUX};
+///
+/// unsafe {
+///     let efer = Msr::<Efer>::read();
+///     Msr::<Efer>::write(efer | Efer::NXE);
+///
+///     Msr::new(IA32_TSC_AUX).write(1);
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Msr<T = u64> {
+    index: u32,
+    value: PhantomData<T>,
+}
+
+impl Msr {
+    /// Refer to the MSR `index`.
+    pub const fn new(index: u32) -> Msr {
+        Msr {
+            index,
+            value: PhantomData,
+        }
+    }
+
+    /// The index of this MSR.
+    pub const fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Read the MSR (see [`rdmsr`]).
+    ///
+    /// # Safety
+    /// Needs CPL 0.
+    pub unsafe fn read(&self) -> u64 {
+        rdmsr(self.index)
+    }
+
+    /// Write the MSR (see [`wrmsr`]).
+    ///
+    /// # Safety
+    /// Needs CPL 0.
+    pub unsafe fn write(&self, value: u64) {
+        wrmsr(self.index, value)
+    }
+}
+
+impl<T: MsrValue> Msr<T> {
+    /// Read and decode `T` from its MSR.
+    ///
+    /// # Safety
+    /// Needs CPL 0.
+    pub unsafe fn read() -> T {
+        T::from_raw(rdmsr(T::INDEX))
+    }
+
+    /// Write `T` to its MSR, keeping the `T::RESERVED` bits intact.
+    ///
+    /// # Safety
+    /// Needs CPL 0.
+    pub unsafe fn write(value: T) {
+        let current = if T::RESERVED != 0 { rdmsr(T::INDEX) } else { 0 };
+        wrmsr(T::INDEX, compose(current, &value));
+    }
+}
+
+/// Merge `value` into the `current` MSR contents.
+fn compose<T: MsrValue>(current: u64, value: &T) -> u64 {
+    (current & T::RESERVED) | (value.to_raw() & !T::RESERVED)
+}
+
+bitflags! {
+    /// Extended Feature Enable Register (IA32_EFER).
+    ///
+    /// See Intel 3a, Section 2.2.1 "Extended Feature Enable Register".
+    pub struct Efer: u64 {
+        /// SYSCALL/SYSRET Enable
+        const SCE = bit!(0);
+        /// IA-32e Mode Enable
+        const LME = bit!(8);
+        /// IA-32e Mode Active (read-only, set by the processor)
+        const LMA = bit!(10);
+        /// Execute Disable Bit Enable
+        const NXE = bit!(11);
+        /// Secure Virtual Machine Enable (AMD only)
+        const SVME = bit!(12);
+    }
+}
+
+impl MsrValue for Efer {
+    const INDEX: u32 = IA32_EFER;
+    const RESERVED: u64 = !Efer::all().bits;
+
+    fn from_raw(raw: u64) -> Efer {
+        Efer::from_bits_truncate(raw)
+    }
+
+    fn to_raw(&self) -> u64 {
+        self.bits
+    }
+}
+
+impl Efer {
+    /// Read IA32_EFER.
+    ///
+    /// # Safety
+    /// Needs CPL 0.
+    pub unsafe fn read() -> Efer {
+        Msr::<Efer>::read()
+    }
+
+    /// Write IA32_EFER, bits not covered by `Efer` keep their current value.
+    ///
+    /// # Safety
+    /// Needs CPL 0. Clearing `LME` or `NXE` while they are in use
+    /// will take down the system.
+    pub unsafe fn write(self) {
+        Msr::<Efer>::write(self)
+    }
+}
+
+/// The APIC base register (IA32_APIC_BASE).
+///
+/// See Intel 3a, Section 10.4.4 "Local APIC Status and Location"
+/// and Section 10.12.1 "Detecting and Enabling x2APIC Mode".
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ApicBase(pub u64);
+
+impl ApicBase {
+    /// Processor is the bootstrap processor.
+    const BSP: u64 = bit!(8);
+    /// x2APIC mode enable.
+    const EXTD: u64 = bit!(10);
+    /// APIC global enable.
+    const EN: u64 = bit!(11);
+    /// Physical base address of the APIC registers (bits 12 to MAXPHYADDR).
+    const BASE_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+    /// Physical address of the xAPIC register page.
+    pub fn base(&self) -> PAddr {
+        PAddr::from(self.0 & ApicBase::BASE_MASK)
+    }
+
+    /// Move the xAPIC register page to `base`.
+    ///
+    /// # Panics
+    /// If `base` is not 4 KiB aligned.
+    pub fn set_base(&mut self, base: PAddr) {
+        assert!(
+            base.is_base_page_aligned(),
+            "APIC base must be 4 KiB aligned"
+        );
+        self.0 = (self.0 & !ApicBase::BASE_MASK) | (base.as_u64() & ApicBase::BASE_MASK);
+    }
+
+    /// Is this the bootstrap processor?
+    pub fn is_bsp(&self) -> bool {
+        self.0 & ApicBase::BSP != 0
+    }
+
+    /// Is the APIC in x2APIC mode?
+    pub fn x2apic_enabled(&self) -> bool {
+        self.0 & ApicBase::EXTD != 0
+    }
+
+    /// Enable/disable x2APIC mode (the APIC must be enabled for x2APIC mode).
+    pub fn set_x2apic_enabled(&mut self, enabled: bool) {
+        self.set(ApicBase::EXTD, enabled)
+    }
+
+    /// Is the APIC globally enabled?
+    pub fn apic_enabled(&self) -> bool {
+        self.0 & ApicBase::EN != 0
+    }
+
+    /// Enable/disable the APIC.
+    pub fn set_apic_enabled(&mut self, enabled: bool) {
+        self.set(ApicBase::EN, enabled)
+    }
+
+    fn set(&mut self, bit: u64, enabled: bool) {
+        if enabled {
+            self.0 |= bit;
+        } else {
+            self.0 &= !bit;
+        }
+    }
+
+    /// Read IA32_APIC_BASE.
+    ///
+    /// # Safety
+    /// Needs CPL 0.
+    pub unsafe fn read() -> ApicBase {
+        Msr::<ApicBase>::read()
+    }
+
+    /// Write IA32_APIC_BASE, reserved bits keep their current value.
+    ///
+    /// # Safety
+    /// Needs CPL 0. Not all mode transitions are valid
+    /// (e.g., x2APIC to xAPIC mode without disabling the APIC first).
+    pub unsafe fn write(self) {
+        Msr::<ApicBase>::write(self)
+    }
+}
+
+impl MsrValue for ApicBase {
+    const INDEX: u32 = IA32_APIC_BASE;
+    const RESERVED: u64 = !(ApicBase::BSP | ApicBase::EXTD | ApicBase::EN | ApicBase::BASE_MASK);
+
+    fn from_raw(raw: u64) -> ApicBase {
+        ApicBase(raw)
+    }
+
+    fn to_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The global performance counter control register (IA32_PERF_GLOBAL_CTRL).
+///
+/// A counter only counts if it is enabled here as well as in its
+/// IA32_PERFEVTSELx (or IA32_FIXED_CTR_CTRL) register.
+///
+/// See Intel 3b, Section 18.2.2 "Architectural Performance Monitoring Version 2".
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct GlobalCtrl(pub u64);
+
+impl GlobalCtrl {
+    /// Bit position of the enable bit for fixed counter 0.
+    const FIXED_SHIFT: u8 = 32;
+
+    /// Enable programmable counter `n` (IA32_PMCn).
+    pub fn enable_programmable(&mut self, n: u8) {
+        assert!(n < GlobalCtrl::FIXED_SHIFT, "Invalid counter index.");
+        self.0 |= 1 << n;
+    }
+
+    /// Enable fixed counter `n` (IA32_FIXED_CTRn).
+    pub fn enable_fixed(&mut self, n: u8) {
+        assert!(n < 64 - GlobalCtrl::FIXED_SHIFT, "Invalid counter index.");
+        self.0 |= 1 << (GlobalCtrl::FIXED_SHIFT + n);
+    }
+
+    /// Is programmable counter `n` enabled?
+    pub fn programmable_enabled(&self, n: u8) -> bool {
+        n < GlobalCtrl::FIXED_SHIFT && self.0 & (1 << n) != 0
+    }
+
+    /// Is fixed counter `n` enabled?
+    pub fn fixed_enabled(&self, n: u8) -> bool {
+        n < 64 - GlobalCtrl::FIXED_SHIFT && self.0 & (1 << (GlobalCtrl::FIXED_SHIFT + n)) != 0
+    }
+
+    /// Disable all counters.
+    pub fn disable_all(&mut self) {
+        self.0 = 0;
+    }
+
+    /// Read IA32_PERF_GLOBAL_CTRL.
+    ///
+    /// # Safety
+    /// Needs CPL 0 and architectural performance monitoring version 2 or newer.
+    pub unsafe fn read() -> GlobalCtrl {
+        Msr::<GlobalCtrl>::read()
+    }
+
+    /// Write IA32_PERF_GLOBAL_CTRL.
+    ///
+    /// # Safety
+    /// Needs CPL 0. Enabling counters that don't exist raises a #GP.
+    pub unsafe fn write(self) {
+        Msr::<GlobalCtrl>::write(self)
+    }
+
+    /// Read IA32_PERF_GLOBAL_STATUS.
+    ///
+    /// Uses the same layout as IA32_PERF_GLOBAL_CTRL: bit n is set if
+    /// IA32_PMCn overflowed, bit 32 + n if IA32_FIXED_CTRn overflowed.
+    ///
+    /// # Safety
+    /// Needs CPL 0 and architectural performance monitoring version 2 or newer.
+    pub unsafe fn overflow_status() -> u64 {
+        rdmsr(IA32_PERF_GLOBAL_STATUS)
+    }
+}
+
+impl MsrValue for GlobalCtrl {
+    const INDEX: u32 = IA32_PERF_GLOBAL_CTRL;
+
+    fn from_raw(raw: u64) -> GlobalCtrl {
+        GlobalCtrl(raw)
+    }
+
+    fn to_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A memory type as used in the PAT and the MTRRs (see [`mtrr`]).
+///
+/// See Intel 3a, Section 11.3 "Methods of Caching Available".
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum MemoryType {
+    /// Strong uncacheable (UC).
+    Uncacheable = 0,
+    /// Write combining (WC).
+    WriteCombining = 1,
+    /// Write-through (WT).
+    WriteThrough = 4,
+    /// Write protected (WP).
+    WriteProtected = 5,
+    /// Write-back (WB).
+    WriteBack = 6,
+    /// Uncacheable, can be overridden by WC in the MTRRs (UC-, PAT only).
+    UncacheableMinus = 7,
+}
+
+impl MemoryType {
+    /// Decode a memory type encoding, `None` for the reserved encodings.
+    pub fn from_u8(value: u8) -> Option<MemoryType> {
+        match value {
+            0 => Some(MemoryType::Uncacheable),
+            1 => Some(MemoryType::WriteCombining),
+            4 => Some(MemoryType::WriteThrough),
+            5 => Some(MemoryType::WriteProtected),
+            6 => Some(MemoryType::WriteBack),
+            7 => Some(MemoryType::UncacheableMinus),
+            _ => None,
+        }
+    }
+}
+
+/// The page attribute table (IA32_PAT).
+///
+/// Holds eight entries of one byte each, a page-table entry selects one
+/// of them with its PAT, PCD and PWT bits (see
+/// [`PageCacheType`](crate::bits64::paging::PageCacheType)).
+///
+/// For write-combining pages, entry 4 has to be changed to WC:
+///
+/// ```
+/// use x86::msr::{MemoryType, Pat};
+///
+/// let mut pat = Pat::default();
+/// pat.set_entry(4, MemoryType::WriteCombining);
+/// assert_eq!(pat.0, 0x0007_0401_0007_0406);
+/// ```
+///
+/// See Intel 3a, Section 11.12 "Page Attribute Table (PAT)".
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Pat(pub u64);
+
+impl Pat {
+    /// Value after reset: WB, WT, UC-, UC, WB, WT, UC-, UC.
+    pub const RESET: Pat = Pat(0x0007_0406_0007_0406);
+
+    /// The memory type of entry `index`, `None` if it holds a reserved encoding.
+    pub fn entry(&self, index: u8) -> Option<MemoryType> {
+        assert!(index < 8, "The PAT has 8 entries.");
+        MemoryType::from_u8((self.0 >> (index * 8)) as u8)
+    }
+
+    /// Set entry `index` to `memory_type`.
+    pub fn set_entry(&mut self, index: u8, memory_type: MemoryType) {
+        assert!(index < 8, "The PAT has 8 entries.");
+        let shift = index * 8;
+        self.0 = (self.0 & !(0xff << shift)) | (memory_type as u64) << shift;
+    }
+
+    /// Read IA32_PAT.
+    ///
+    /// # Safety
+    /// Needs CPL 0 and PAT support (CPUID.01H:EDX.PAT\[bit 16\]).
+    pub unsafe fn read() -> Pat {
+        Msr::<Pat>::read()
+    }
+
+    /// Write IA32_PAT.
+    ///
+    /// Existing mappings using a changed entry must be flushed from the
+    /// TLBs and caches of all processors (see Intel 3a, Section 11.12.4).
+    ///
+    /// # Safety
+    /// Needs CPL 0 and PAT support. Changing the memory type of memory
+    /// that is currently mapped and accessed can lead to inconsistent caches.
+    pub unsafe fn write(self) {
+        Msr::<Pat>::write(self)
+    }
+}
+
+impl Default for Pat {
+    fn default() -> Pat {
+        Pat::RESET
+    }
+}
+
+impl MsrValue for Pat {
+    const INDEX: u32 = IA32_PAT;
+
+    fn from_raw(raw: u64) -> Pat {
+        Pat(raw)
+    }
+
+    fn to_raw(&self) -> u64 {
+        self.0
+    }
+}
+
 // What follows is a long list of all MSR register taken from Intel's manual.
 // Some of the register values appear duplicated as they may be
 // called differently for different architectures or they just have
@@ -750,6 +1188,9 @@ pub const MSR_PERF_GLOBAL_STAUS: u32 = 0x38e;
 /// See Table 35-2. See Section 18.4.2, Global Counter Control  Facilities.
 pub const IA32_PERF_GLOBAL_STAUS: u32 = 0x38e;
 
+/// See Table 35-2. See Section 18.4.2, Global Counter Control  Facilities.
+pub const IA32_PERF_GLOBAL_STATUS: u32 = 0x38e;
+
 /// See Section 18.4.2, Global Counter Control Facilities.
 pub const MSR_PERF_GLOBAL_CTRL: u32 = 0x38f;
 
@@ -3195,5 +3636,150 @@ pub const IA32_GS_BASE: u32 = 0xc0000101;
 /// Swap Target of BASE Address of GS (R/W) See Table 35-2.
 pub const IA32_KERNEL_GSBASE: u32 = 0xc0000102;
 
+/// Swap Target of BASE Address of GS (R/W), same as `IA32_KERNEL_GSBASE`.
+pub const IA32_KERNEL_GS_BASE: u32 = IA32_KERNEL_GSBASE;
+
 /// AUXILIARY TSC Signature. (R/W) See Table 35-2 and Section  17.13.2, IA32_TSC_AUX Register and RDTSCP Support.
 pub const IA32_TSC_AUX: u32 = 0xc0000103;
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn efer_bits() {
+        assert_eq!(Efer::SCE.bits(), 1 << 0);
+        assert_eq!(Efer::LME.bits(), 1 << 8);
+        assert_eq!(Efer::LMA.bits(), 1 << 10);
+        assert_eq!(Efer::NXE.bits(), 1 << 11);
+        assert_eq!(Efer::SVME.bits(), 1 << 12);
+        // Typical value in long mode with syscall and NX enabled:
+        assert_eq!(
+            Efer::from_bits_truncate(0xd01),
+            Efer::SCE | Efer::LME | Efer::LMA | Efer::NXE
+        );
+    }
+
+    #[test]
+    fn efer_msr_value() {
+        assert_eq!(<Efer as MsrValue>::INDEX, IA32_EFER);
+        assert_eq!(Efer::from_raw(0xd01).to_raw(), 0xd01);
+        // Unknown bits are dropped when decoding...
+        assert_eq!(Efer::from_raw(0xffff_0d01), Efer::from_raw(0xd01));
+        // ...and taken from the current register contents when encoding:
+        assert_eq!(compose(0xffff_0000, &Efer::SCE), 0xffff_0001);
+        assert_eq!(compose(0xffff_0d01, &Efer::LME), 0xffff_0100);
+        assert_eq!(compose(0, &Efer::all()), 0x1d01);
+    }
+
+    #[test]
+    fn apic_base() {
+        // Default after reset: BSP, enabled, base at 0xfee0_0000
+        let mut apic = ApicBase(0xfee0_0900);
+        assert!(apic.is_bsp());
+        assert!(apic.apic_enabled());
+        assert!(!apic.x2apic_enabled());
+        assert_eq!(apic.base(), PAddr::from(0xfee0_0000u64));
+
+        apic.set_x2apic_enabled(true);
+        assert_eq!(apic.0, 0xfee0_0d00);
+        assert!(apic.x2apic_enabled());
+
+        apic.set_apic_enabled(false);
+        apic.set_x2apic_enabled(false);
+        assert_eq!(apic.0, 0xfee0_0100);
+
+        apic.set_base(PAddr::from(0x1_0000_0000u64));
+        assert_eq!(apic.base(), PAddr::from(0x1_0000_0000u64));
+        assert_eq!(apic.0, 0x1_0000_0100);
+
+        // Reserved bits are taken from the register:
+        assert_eq!(compose(0xff, &apic), 0x1_0000_01ff);
+    }
+
+    #[test]
+    #[should_panic]
+    fn apic_base_misaligned() {
+        ApicBase(0).set_base(PAddr::from(0xfee0_0010u64));
+    }
+
+    #[test]
+    fn global_ctrl() {
+        let mut ctrl = GlobalCtrl::default();
+        ctrl.enable_programmable(0);
+        ctrl.enable_programmable(3);
+        ctrl.enable_fixed(0);
+        ctrl.enable_fixed(2);
+        assert_eq!(ctrl.0, 0x0000_0005_0000_0009);
+
+        assert!(ctrl.programmable_enabled(3));
+        assert!(!ctrl.programmable_enabled(1));
+        assert!(ctrl.fixed_enabled(2));
+        assert!(!ctrl.fixed_enabled(1));
+        assert_eq!(compose(u64::max_value(), &ctrl), ctrl.0);
+
+        ctrl.disable_all();
+        assert_eq!(ctrl, GlobalCtrl(0));
+        assert_eq!(<GlobalCtrl as MsrValue>::INDEX, 0x38f);
+        assert_eq!(IA32_PERF_GLOBAL_STATUS, 0x38e);
+    }
+
+    #[test]
+    #[should_panic]
+    fn global_ctrl_invalid_counter() {
+        GlobalCtrl::default().enable_programmable(32);
+    }
+
+    #[test]
+    fn pat_entries() {
+        let pat = Pat::default();
+        assert_eq!(pat.0, 0x0007_0406_0007_0406);
+        let reset = [
+            MemoryType::WriteBack,
+            MemoryType::WriteThrough,
+            MemoryType::UncacheableMinus,
+            MemoryType::Uncacheable,
+        ];
+        for index in 0..8 {
+            assert_eq!(pat.entry(index), Some(reset[index as usize % 4]));
+        }
+
+        // Each entry is one byte:
+        let mut pat = Pat(0);
+        pat.set_entry(0, MemoryType::WriteBack);
+        pat.set_entry(4, MemoryType::WriteCombining);
+        pat.set_entry(7, MemoryType::WriteProtected);
+        assert_eq!(pat.0, 0x0500_0001_0000_0006);
+        pat.set_entry(4, MemoryType::UncacheableMinus);
+        assert_eq!(pat.0, 0x0500_0007_0000_0006);
+        assert_eq!(pat.entry(1), Some(MemoryType::Uncacheable));
+        assert_eq!(Pat(0x2).entry(0), None);
+
+        assert_eq!(<Pat as MsrValue>::INDEX, 0x277);
+    }
+
+    #[test]
+    #[should_panic]
+    fn pat_invalid_entry() {
+        Pat::default().set_entry(8, MemoryType::WriteBack);
+    }
+
+    #[test]
+    fn raw_msr() {
+        let msr = Msr::new(IA32_EFER);
+        assert_eq!(msr.index(), 0xc000_0080);
+        assert_eq!(msr, Msr::new(IA32_EFER));
+    }
+
+    #[test]
+    fn msr_indices() {
+        assert_eq!(IA32_EFER, 0xc000_0080);
+        assert_eq!(IA32_STAR, 0xc000_0081);
+        assert_eq!(IA32_LSTAR, 0xc000_0082);
+        assert_eq!(IA32_FMASK, 0xc000_0084);
+        assert_eq!(IA32_FS_BASE, 0xc000_0100);
+        assert_eq!(IA32_GS_BASE, 0xc000_0101);
+        assert_eq!(IA32_KERNEL_GS_BASE, 0xc000_0102);
+        assert_eq!(IA32_APIC_BASE, 0x1b);
+    }
+}
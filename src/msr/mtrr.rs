@@ -0,0 +1,215 @@
+//! Memory type range registers (MTRRs).
+//!
+//! The MTRRs assign memory types to ranges of physical memory, the memory
+//! type of a page is a combination of its MTRR and PAT type.
+//!
+//! See Intel 3a, Section 11.11 "Memory Type Range Registers (MTRRs)".
+
+use super::{rdmsr, MemoryType, Msr, MsrValue, IA32_MTRRCAP, IA32_MTRR_DEF_TYPE};
+use super::{IA32_MTRR_PHYSBASE0, IA32_MTRR_PHYSMASK0};
+use crate::bits64::paging::PAddr;
+
+/// Bits 12 to 51 of MTRR base and mask registers hold (part of) an address.
+const ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Decode an MTRR memory type, UC- only exists in the PAT.
+fn memory_type(value: u8) -> Option<MemoryType> {
+    MemoryType::from_u8(value).filter(|t| *t != MemoryType::UncacheableMinus)
+}
+
+/// The MTRR capabilities (IA32_MTRRCAP).
+///
+/// See Intel 3a, Section 11.11.1 "MTRR Feature Identification".
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MtrrCap(pub u64);
+
+impl MtrrCap {
+    /// Number of variable-range MTRRs (VCNT).
+    pub fn variable_count(&self) -> u8 {
+        self.0 as u8
+    }
+
+    /// Are the fixed-range MTRRs supported (FIX)?
+    pub fn has_fixed_ranges(&self) -> bool {
+        self.0 & bit!(8) != 0
+    }
+
+    /// Is the write-combining memory type supported (WC)?
+    pub fn has_write_combining(&self) -> bool {
+        self.0 & bit!(10) != 0
+    }
+
+    /// Is the system-management range register supported (SMRR)?
+    pub fn has_smrr(&self) -> bool {
+        self.0 & bit!(11) != 0
+    }
+
+    /// Read IA32_MTRRCAP.
+    ///
+    /// # Safety
+    /// Needs CPL 0 and MTRR support (CPUID.01H:EDX.MTRR\[bit 12\]).
+    pub unsafe fn read() -> MtrrCap {
+        Msr::<MtrrCap>::read()
+    }
+}
+
+impl MsrValue for MtrrCap {
+    const INDEX: u32 = IA32_MTRRCAP;
+
+    fn from_raw(raw: u64) -> MtrrCap {
+        MtrrCap(raw)
+    }
+
+    fn to_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The memory type of physical memory not covered by an MTRR (IA32_MTRR_DEF_TYPE).
+///
+/// See Intel 3a, Section 11.11.2.1 "IA32_MTRR_DEF_TYPE MSR".
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MtrrDefType(pub u64);
+
+impl MtrrDefType {
+    /// The default memory type, `None` for a reserved encoding.
+    pub fn default_type(&self) -> Option<MemoryType> {
+        memory_type(self.0 as u8)
+    }
+
+    /// Are the fixed-range MTRRs enabled (FE)?
+    pub fn fixed_ranges_enabled(&self) -> bool {
+        self.0 & bit!(10) != 0
+    }
+
+    /// Are the MTRRs enabled (E)? If clear, all of physical memory is UC.
+    pub fn enabled(&self) -> bool {
+        self.0 & bit!(11) != 0
+    }
+
+    /// Read IA32_MTRR_DEF_TYPE.
+    ///
+    /// # Safety
+    /// Needs CPL 0 and MTRR support (CPUID.01H:EDX.MTRR\[bit 12\]).
+    pub unsafe fn read() -> MtrrDefType {
+        Msr::<MtrrDefType>::read()
+    }
+}
+
+impl MsrValue for MtrrDefType {
+    const INDEX: u32 = IA32_MTRR_DEF_TYPE;
+
+    fn from_raw(raw: u64) -> MtrrDefType {
+        MtrrDefType(raw)
+    }
+
+    fn to_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A variable-range MTRR, decoded from an IA32_MTRR_PHYSBASEn and
+/// IA32_MTRR_PHYSMASKn pair.
+///
+/// See Intel 3a, Section 11.11.2.3 "Variable Range MTRRs".
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VariableRange {
+    /// Start of the range.
+    pub base: PAddr,
+    /// Size of the range in bytes (a power of two).
+    pub size: u64,
+    /// Memory type of the range, `None` for a reserved encoding.
+    pub memory_type: Option<MemoryType>,
+    /// Is the range enabled (V)?
+    pub valid: bool,
+}
+
+impl VariableRange {
+    /// Decode the contents of a PHYSBASE and PHYSMASK register.
+    ///
+    /// The size is given by the lowest bit set in the mask, a range always
+    /// covers a naturally aligned power of two (ranges with a discontiguous
+    /// mask are not supported and decoded as if the mask was contiguous).
+    pub fn new(physbase: u64, physmask: u64) -> VariableRange {
+        let mask = physmask & ADDRESS_MASK;
+        VariableRange {
+            base: PAddr::from(physbase & ADDRESS_MASK),
+            size: if mask != 0 {
+                1 << mask.trailing_zeros()
+            } else {
+                0
+            },
+            memory_type: memory_type(physbase as u8),
+            valid: physmask & bit!(11) != 0,
+        }
+    }
+
+    /// Does the range contain `addr`?
+    pub fn contains(&self, addr: PAddr) -> bool {
+        self.valid && addr >= self.base && addr.as_u64() - self.base.as_u64() < self.size
+    }
+}
+
+/// Iterate over all variable-range MTRRs (including the disabled ones).
+///
+/// # Safety
+/// Needs CPL 0 and MTRR support (CPUID.01H:EDX.MTRR\[bit 12\]).
+pub unsafe fn variable_ranges() -> impl Iterator<Item = VariableRange> {
+    (0..MtrrCap::read().variable_count() as u32).map(|n| unsafe {
+        VariableRange::new(
+            rdmsr(IA32_MTRR_PHYSBASE0 + 2 * n),
+            rdmsr(IA32_MTRR_PHYSMASK0 + 2 * n),
+        )
+    })
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn variable_range_decoding() {
+        // 1 GiB uncacheable PCI hole on a 40-bit physical address machine
+        let hole = VariableRange::new(0x0000_0000_c000_0000, 0x0000_00ff_c000_0800);
+        assert_eq!(hole.base, PAddr::from(0xc000_0000u64));
+        assert_eq!(hole.size, 1 << 30);
+        assert_eq!(hole.memory_type, Some(MemoryType::Uncacheable));
+        assert!(hole.valid);
+        assert!(hole.contains(PAddr::from(0xffff_ffffu64)));
+        assert!(!hole.contains(PAddr::from(0x1_0000_0000u64)));
+        assert!(!hole.contains(PAddr::from(0xbfff_ffffu64)));
+
+        // 2 GiB write-back at 0 (46-bit physical addresses)
+        let ram = VariableRange::new(0x0000_0000_0000_0006, 0x0000_3fff_8000_0800);
+        assert_eq!(ram.base, PAddr::from(0u64));
+        assert_eq!(ram.size, 2 << 30);
+        assert_eq!(ram.memory_type, Some(MemoryType::WriteBack));
+
+        // Disabled range:
+        let unused = VariableRange::new(0, 0);
+        assert!(!unused.valid);
+        assert!(!unused.contains(PAddr::from(0u64)));
+        // UC- is not a valid MTRR type:
+        assert_eq!(VariableRange::new(0x7, 0x800).memory_type, None);
+    }
+
+    #[test]
+    fn capabilities() {
+        // 10 variable ranges, fixed ranges, WC and SMRR
+        let cap = MtrrCap(0xd0a);
+        assert_eq!(cap.variable_count(), 10);
+        assert!(cap.has_fixed_ranges());
+        assert!(cap.has_write_combining());
+        assert!(cap.has_smrr());
+
+        let def = MtrrDefType(0xc00);
+        assert!(def.enabled());
+        assert!(def.fixed_ranges_enabled());
+        assert_eq!(def.default_type(), Some(MemoryType::Uncacheable));
+        assert_eq!(MtrrDefType(0x6).default_type(), Some(MemoryType::WriteBack));
+        assert!(!MtrrDefType(0x6).enabled());
+
+        assert_eq!(<MtrrCap as MsrValue>::INDEX, 0xfe);
+        assert_eq!(<MtrrDefType as MsrValue>::INDEX, 0x2ff);
+    }
+}
@@ -0,0 +1,33 @@
+#[derive(Debug, Eq, PartialEq)]
+pub struct EventDescription<'a> {
+    /// The 12-bit event select, split across the EventSelect\[7:0\] and
+    /// EventSelect\[11:8\] fields of the PERF_CTLx MSRs.
+    pub event_code: u16,
+
+    /// Unit mask, selects the sub-events to count (PERF_CTLx\[15:8\]).
+    ///
+    /// 0 if the event description doesn't specify one.
+    pub umask: u8,
+
+    /// A string name of the event, as found in the PPR.
+    pub event_name: &'a str,
+
+    /// Event description.
+    pub brief_description: &'a str,
+}
+
+impl<'a> EventDescription<'a> {
+    pub fn new(
+        event_code: u16,
+        umask: u8,
+        event_name: &'a str,
+        brief_description: &'a str,
+    ) -> EventDescription<'a> {
+        EventDescription {
+            event_code,
+            umask,
+            event_name,
+            brief_description,
+        }
+    }
+}
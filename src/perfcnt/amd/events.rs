@@ -0,0 +1,7 @@
+//! Performance counter for AMD architectures.
+use super::description::EventDescription;
+/// The content of this file is automatically generated by `build.rs`
+/// from the data in `x86data/amd`.
+use phf;
+
+include!(concat!(env!("OUT_DIR"), "/amd_counters.rs"));
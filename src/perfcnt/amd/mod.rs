@@ -0,0 +1,80 @@
+//! Information about AMD's performance events.
+//!
+//! AMD processors count core events with the PERF_CTLx/PERF_CTRx MSR pairs
+//! (see the Processor Programming Reference (PPR) of the respective family).
+pub mod events;
+// The types need to be in a spearate file so we don't get circular
+// dependencies with build.rs include:
+mod description;
+pub use self::description::EventDescription;
+
+use super::cpu_key;
+use crate::cpuid;
+use phf;
+
+impl<'a> EventDescription<'a> {
+    /// Encode the event into the layout of the PERF_CTLx MSRs.
+    ///
+    /// Sets the event select (bits 7:0 and 35:32), unit mask, USR and OS
+    /// fields. The En (bit 22) and Int (bit 20) bits are left for the caller
+    /// to set once the counter is programmed.
+    pub fn perfevtsel_bits(&self) -> u64 {
+        let event = self.event_code as u64;
+        let mut value = (event & 0xff) | (event >> 8 & 0xf) << 32;
+        value |= (self.umask as u64) << 8;
+        // USR and OS
+        value | 1 << 16 | 1 << 17
+    }
+}
+
+/// Return all performance events for the CPU we're currently running on.
+///
+/// The lookup key (e.g., `AuthenticAMD-17-31`) is computed from CPUID leaf 1
+/// with the extended family and model fields taken into account.
+/// Returns `None` on non-AMD CPUs or if we don't know about the model.
+pub fn events_for_cpu() -> Option<&'static phf::Map<&'static str, EventDescription<'static>>> {
+    let cpuid = cpuid::CpuId::new();
+    let vf = cpuid.get_vendor_info()?;
+    if vf.as_str() != "AuthenticAMD" {
+        return None;
+    }
+    let fi = cpuid.get_feature_info()?;
+
+    let key = cpu_key(vf.as_str(), fi.family_id(), fi.model_id())?;
+    events::COUNTER_MAP.get(key.as_str()).copied()
+}
+
+#[test]
+fn zen_events() {
+    let zen2 = events::COUNTER_MAP.get("AuthenticAMD-17-31").unwrap();
+    let ret_instr = zen2.get("ex_ret_instr").unwrap();
+    assert_eq!(ret_instr.event_code, 0xc0);
+    assert_eq!(ret_instr.umask, 0);
+    assert_eq!(ret_instr.perfevtsel_bits(), 0x3_00c0);
+
+    let ld = zen2.get("ls_dispatch.ld_dispatch").unwrap();
+    assert_eq!(ld.perfevtsel_bits(), 0x3_0129);
+
+    // Zen 1 and Zen+ share the same table:
+    let zen1 = events::COUNTER_MAP.get("AuthenticAMD-17-01").unwrap();
+    let zen_plus = events::COUNTER_MAP.get("AuthenticAMD-17-08").unwrap();
+    assert!(core::ptr::eq(*zen1, *zen_plus));
+    assert!(zen1.get("ex_tagged_ibs_ops.ibs_tagged_ops").is_none());
+}
+
+#[test]
+fn extended_event_select() {
+    let zen2 = events::COUNTER_MAP.get("AuthenticAMD-17-31").unwrap();
+    let ibs = zen2.get("ex_tagged_ibs_ops.ibs_tagged_ops").unwrap();
+    assert_eq!(ibs.event_code, 0x1cf);
+    // EventSelect[11:8] lives in bits 35:32
+    assert_eq!(ibs.perfevtsel_bits(), 0x1_0003_01cf);
+}
+
+#[test]
+fn events_for_cpu_test() {
+    // Note: This will silently pass in case we don't know about the host CPU.
+    if let Some(events) = events_for_cpu() {
+        assert!(events.get("ex_ret_instr").is_some());
+    }
+}
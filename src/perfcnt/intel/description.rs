@@ -70,6 +70,147 @@ impl fmt::Debug for Counter {
     }
 }
 
+/// The uncore unit (box) which counts an uncore event.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum UncoreUnit {
+    /// Caching agent (CBo)
+    Cbo,
+    /// Caching and home agent (CHA)
+    Cha,
+    /// Home agent
+    Ha,
+    /// Integrated memory controller
+    Imc,
+    /// Integrated memory controller (DCLK domain)
+    ImcDclk,
+    /// Integrated memory controller (UCLK domain)
+    ImcUclk,
+    /// Embedded DRAM controller (ECLK domain)
+    EdcEclk,
+    /// Embedded DRAM controller (UCLK domain)
+    EdcUclk,
+    /// Integrated IO
+    Iio,
+    /// IIO coherency (IRP)
+    Irp,
+    /// Mesh to memory
+    M2m,
+    /// Mesh to PCIe
+    M2Pcie,
+    /// Mesh to UPI
+    M3Upi,
+    /// Power control unit
+    Pcu,
+    /// U-Box
+    Ubox,
+    /// UPI link layer
+    UpiLl,
+    /// QPI link layer
+    QpiLl,
+    /// Ring to PCIe
+    R2Pcie,
+    /// Ring to QPI
+    R3Qpi,
+    /// S-Box
+    Sbo,
+    /// System agent arbiter
+    Arb,
+    /// Non-core unit
+    Ncu,
+    /// Integrated memory/PCIe hub (iMPH-U)
+    ImphU,
+}
+
+impl fmt::Debug for UncoreUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            UncoreUnit::Cbo => "Cbo",
+            UncoreUnit::Cha => "Cha",
+            UncoreUnit::Ha => "Ha",
+            UncoreUnit::Imc => "Imc",
+            UncoreUnit::ImcDclk => "ImcDclk",
+            UncoreUnit::ImcUclk => "ImcUclk",
+            UncoreUnit::EdcEclk => "EdcEclk",
+            UncoreUnit::EdcUclk => "EdcUclk",
+            UncoreUnit::Iio => "Iio",
+            UncoreUnit::Irp => "Irp",
+            UncoreUnit::M2m => "M2m",
+            UncoreUnit::M2Pcie => "M2Pcie",
+            UncoreUnit::M3Upi => "M3Upi",
+            UncoreUnit::Pcu => "Pcu",
+            UncoreUnit::Ubox => "Ubox",
+            UncoreUnit::UpiLl => "UpiLl",
+            UncoreUnit::QpiLl => "QpiLl",
+            UncoreUnit::R2Pcie => "R2Pcie",
+            UncoreUnit::R3Qpi => "R3Qpi",
+            UncoreUnit::Sbo => "Sbo",
+            UncoreUnit::Arb => "Arb",
+            UncoreUnit::Ncu => "Ncu",
+            UncoreUnit::ImphU => "ImphU",
+        };
+        write!(f, "UncoreUnit::{}", name)
+    }
+}
+
+/// The kind of uncore counter an event is counted with.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum UncoreCounterType {
+    /// One of the general purpose counters of the unit (`PGMABLE`).
+    Programmable,
+    /// The fixed counter of the unit (e.g., the iMC DCLK counter).
+    Fixed,
+}
+
+impl fmt::Debug for UncoreCounterType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            UncoreCounterType::Programmable => "Programmable",
+            UncoreCounterType::Fixed => "Fixed",
+        };
+        write!(f, "UncoreCounterType::{}", name)
+    }
+}
+
+/// Additional information required to program an uncore event.
+#[derive(Debug, Eq, PartialEq)]
+pub struct UncoreSpec {
+    /// The unit (box) that counts the event.
+    pub unit: UncoreUnit,
+
+    /// FC Mask
+    pub fc_mask: u8,
+
+    /// Port Mask
+    pub port_mask: u8,
+
+    /// Extended unit mask bits.
+    pub umask_ext: u8,
+
+    /// Is the event counted with a fixed or a programmable counter?
+    pub counter_type: UncoreCounterType,
+}
+
+/// Describes one bit of the FP_ARITH_INST_RETIRED unit mask.
+///
+/// Used to compute FLOPs from the FP_ARITH_INST_RETIRED events:
+/// Each counted instruction performs `flops_multiplier` operations.
+// The build script only writes the fields out through `Debug`.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct FpArithMetric<'a> {
+    /// Name of the unit mask bit (e.g., `SCALAR_DOUBLE`).
+    pub bit_name: &'a str,
+
+    /// Position of the bit in the unit mask.
+    pub bit_index: u8,
+
+    /// Floating-point operations per counted instruction.
+    pub flops_multiplier: u8,
+
+    /// Description of the instructions counted by this bit.
+    pub description: &'a str,
+}
+
 #[derive(Debug)]
 pub struct EventDescription<'a> {
     /// This field maps to the Event Select field in the IA32_PERFEVTSELx[7:0]MSRs.
@@ -77,13 +218,17 @@ pub struct EventDescription<'a> {
     /// The set of values for this field is defined architecturally.
     /// Each value corresponds to an event logic unit and should be used with a unit
     /// mask value to obtain an architectural performance event.
-    pub event_code: Tuple,
+    ///
+    /// `None` in case the event description left this field empty.
+    pub event_code: Option<Tuple>,
 
     /// This field maps to the Unit Mask filed in the IA32_PERFEVTSELx[15:8] MSRs.
     ///
     /// It further qualifies the event logic unit selected in the event select
     /// field to detect a specific micro-architectural condition.
-    pub umask: Tuple,
+    ///
+    /// `None` in case the event description left this field empty.
+    pub umask: Option<Tuple>,
 
     /// It is a string of characters to identify the programming of an event.
     pub event_name: &'a str,
@@ -187,6 +332,10 @@ pub struct EventDescription<'a> {
     /// replacement or extension of the precise store facility in SNB.
     pub data_la: bool,
 
+    /// The counter(s) supporting precise distribution (PDIR) of PEBS records
+    /// for this event, `None` if the event doesn't use PDIR.
+    pub pdir_counter: Option<&'a str>,
+
     /// A '1' in this field means that when the event is configured as a PEBS event,
     /// the DCU hit field of the PEBS record is set to 1 when the store hits in the
     /// L1 cache and 0 when it misses.
@@ -225,25 +374,18 @@ pub struct EventDescription<'a> {
     /// Event status
     pub event_status: u64,
 
-    /// FC Mask
-    pub fc_mask: u8,
-
     /// Filter value
     pub filter_value: u64,
 
-    /// Port Mask
-    pub port_mask: u8,
-
-    /// This field maps to the Unit Mask filed in the IA32_PERFEVTSELx[15:8] MSRs.
-    /// It further qualifies the event logic unit selected in the event select
-    /// field to detect a specific micro-architectural condition.
-    pub umask_ext: u8,
+    /// Unit, FC mask, port mask and extended unit mask of uncore events
+    /// (`None` for core events).
+    pub uncore_spec: Option<UncoreSpec>,
 }
 
 impl<'a> EventDescription<'a> {
     pub fn new(
-        event_code: Tuple,
-        umask: Tuple,
+        event_code: Option<Tuple>,
+        umask: Option<Tuple>,
         event_name: &'a str,
         brief_description: &'a str,
         public_description: Option<&'a str>,
@@ -262,6 +404,7 @@ impl<'a> EventDescription<'a> {
         precise_store: bool,
         collect_pebs_record: Option<u64>,
         data_la: bool,
+        pdir_counter: Option<&'a str>,
         l1_hit_indication: bool,
         errata: Option<&'a str>,
         offcore: bool,
@@ -271,10 +414,8 @@ impl<'a> EventDescription<'a> {
         uncore: bool,
         deprecated: bool,
         event_status: u64,
-        fc_mask: u8,
         filter_value: u64,
-        port_mask: u8,
-        umask_ext: u8,
+        uncore_spec: Option<UncoreSpec>,
     ) -> EventDescription<'a> {
         EventDescription {
             event_code: event_code,
@@ -297,6 +438,7 @@ impl<'a> EventDescription<'a> {
             precise_store: precise_store,
             collect_pebs_record: collect_pebs_record,
             data_la: data_la,
+            pdir_counter,
             l1_hit_indication: l1_hit_indication,
             errata: errata,
             offcore: offcore,
@@ -306,10 +448,8 @@ impl<'a> EventDescription<'a> {
             uncore: uncore,
             deprecated: deprecated,
             event_status: event_status,
-            fc_mask: fc_mask,
             filter_value: filter_value,
-            port_mask: port_mask,
-            umask_ext: umask_ext,
+            uncore_spec: uncore_spec,
         }
     }
 }
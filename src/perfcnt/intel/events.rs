@@ -1,7 +1,12 @@
 //! Performance counter for all Intel architectures.
-use super::description::{Counter, EventDescription, MSRIndex, PebsType, Tuple};
+#[cfg(feature = "performance-counter")]
+use super::description::FpArithMetric;
+use super::description::{
+    Counter, EventDescription, MSRIndex, PebsType, Tuple, UncoreCounterType, UncoreSpec, UncoreUnit,
+};
 /// The content of this file is automatically generated by `build.rs`
 /// from the data in `x86data/perfmon_data`.
+#[cfg(feature = "performance-counter")]
 use phf;
 
 include!(concat!(env!("OUT_DIR"), "/counters.rs"));
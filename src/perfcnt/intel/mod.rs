@@ -3,42 +3,22 @@ pub mod events;
 // The types need to be in a spearate file so we don't get circular
 // dependencies with build.rs include:
 mod description;
-pub use self::description::{Counter, EventDescription, MSRIndex, PebsType, Tuple};
+pub use self::description::{
+    Counter, EventDescription, FpArithMetric, MSRIndex, PebsType, Tuple, UncoreCounterType,
+    UncoreSpec, UncoreUnit,
+};
 
+#[cfg(feature = "performance-counter")]
+use super::{cpu_key, ModelWriter};
+#[cfg(feature = "performance-counter")]
 use crate::cpuid;
-use core::fmt::{Error, Result, Write};
-use core::str;
+#[cfg(feature = "performance-counter")]
+use core::fmt::Write;
+#[cfg(feature = "performance-counter")]
 use phf;
 
-const MODEL_LEN: usize = 30;
-
-#[derive(Default)]
-struct ModelWriter {
-    buffer: [u8; MODEL_LEN],
-    index: usize,
-}
-
-impl ModelWriter {
-    fn as_str(&self) -> &str {
-        str::from_utf8(&self.buffer[..self.index]).unwrap()
-    }
-}
-
-impl Write for ModelWriter {
-    fn write_str(&mut self, s: &str) -> Result {
-        // TODO: There exists probably a more efficient way of doing this:
-        for c in s.chars() {
-            if self.index >= self.buffer.len() {
-                return Err(Error);
-            }
-            self.buffer[self.index] = c as u8;
-            self.index += 1;
-        }
-        Ok(())
-    }
-}
-
 // Format must be a string literal
+#[cfg(feature = "performance-counter")]
 macro_rules! get_events {
     ($format:expr) => {{
         let cpuid = cpuid::CpuId::new();
@@ -57,24 +37,716 @@ macro_rules! get_events {
                 write!(writer, $format, vendor, family, extended_model, model).unwrap();
                 let key = writer.as_str();
 
-                events::COUNTER_MAP.get(key)
+                events::COUNTER_MAP.get(key).copied()
             })
         })
     }};
 }
 
 /// Return all core performance events for the running micro-architecture.
+#[cfg(feature = "performance-counter")]
 pub fn events() -> Option<&'static phf::Map<&'static str, EventDescription<'static>>> {
     // Should be something like: GenuineIntel-6-2C
     get_events!("{}-{}-{:X}{:X}")
 }
 
+/// Sampling period used for events without a recommended sample-after value.
+///
+/// This is the period most of Intel's cycle and instruction events
+/// suggest (a prime, to avoid sampling in lock-step with loops).
+pub const DEFAULT_SAMPLE_PERIOD: u64 = 2_000_003;
+
+impl Tuple {
+    /// The first (primary) value.
+    pub fn primary(&self) -> u8 {
+        match *self {
+            Tuple::One(a) | Tuple::Two(a, _) => a,
+        }
+    }
+
+    /// The second value, if there is one.
+    pub fn secondary(&self) -> Option<u8> {
+        match *self {
+            Tuple::One(_) => None,
+            Tuple::Two(_, b) => Some(b),
+        }
+    }
+}
+
+impl<'a> EventDescription<'a> {
+    /// Encode the event into the layout of the IA32_PERFEVTSELx MSRs.
+    ///
+    /// Sets the event select, unit mask, USR, OS, edge detect, any thread,
+    /// invert and counter mask fields. The EN (bit 22) and INT (bit 20) bits
+    /// are left for the caller to set once the counter is programmed.
+    ///
+    /// For events that list two event codes or unit masks
+    /// only the first one is encoded (see `secondary_event_code`
+    /// and `secondary_umask`).
+    pub fn perfevtsel_bits(&self) -> u64 {
+        fn first(t: &Option<Tuple>) -> u64 {
+            t.as_ref().map_or(0, |t| t.primary() as u64)
+        }
+
+        let mut value = first(&self.event_code) | first(&self.umask) << 8;
+        // USR and OS
+        value |= 1 << 16 | 1 << 17;
+        if self.edge_detect {
+            value |= 1 << 18;
+        }
+        if self.any_thread {
+            value |= 1 << 21;
+        }
+        if self.invert {
+            value |= 1 << 23;
+        }
+        value | (self.counter_mask as u64) << 24
+    }
+
+    /// A short description of what the event counts.
+    pub fn brief_description(&self) -> &'a str {
+        self.brief_description
+    }
+
+    /// A more detailed description of the event,
+    /// `None` if there is nothing beyond the brief description.
+    pub fn public_description(&self) -> Option<&'a str> {
+        self.public_description
+            .filter(|d| !d.is_empty() && *d != "null")
+    }
+
+    /// The second event code for events that list two of them.
+    ///
+    /// E.g., the OFFCORE_RESPONSE events can be counted with event 0xB7
+    /// (using MSR_OFFCORE_RSP_0) or 0xBB (using MSR_OFFCORE_RSP_1).
+    pub fn secondary_event_code(&self) -> Option<u8> {
+        self.event_code.as_ref().and_then(Tuple::secondary)
+    }
+
+    /// The second unit mask for events that list two of them.
+    pub fn secondary_umask(&self) -> Option<u8> {
+        self.umask.as_ref().and_then(Tuple::secondary)
+    }
+
+    /// The counters that can be used to count the event.
+    pub fn counter(&self) -> Counter {
+        self.counter
+    }
+
+    /// The (0-based) fixed counter (IA32_FIXED_CTRx) that counts this event,
+    /// `None` for events counted by programmable counters.
+    pub fn fixed_counter_index(&self) -> Option<u8> {
+        match self.counter {
+            Counter::Fixed(mask) if mask != 0 => Some(mask.trailing_zeros() as u8),
+            _ => None,
+        }
+    }
+
+    /// Can the event be sampled with PEBS?
+    pub fn supports_pebs(&self) -> bool {
+        self.pebs != PebsType::Regular
+    }
+
+    /// Can the event only be counted as a PEBS event?
+    pub fn pebs_only(&self) -> bool {
+        self.pebs == PebsType::PebsOnly
+    }
+
+    /// Does the PEBS record of this event contain the data linear address
+    /// of the sampled load or store (`Data_LA`)?
+    pub fn collects_data_linear_address(&self) -> bool {
+        self.data_la
+    }
+
+    /// Does the event use the precise store facility
+    /// (SandyBridge and IvyBridge, `PRECISE_STORE`)?
+    pub fn is_precise_store(&self) -> bool {
+        self.precise_store
+    }
+
+    /// Does the PEBS record of this (store) event indicate whether the
+    /// store hit the L1 cache (`L1_Hit_Indication`)?
+    pub fn has_l1_hit_indication(&self) -> bool {
+        self.l1_hit_indication
+    }
+
+    /// Whether PEBS collection is recommended for the event (`CollectPEBSRecord`,
+    /// only present for Goldmont and later Atoms): 0 if the event can't collect
+    /// PEBS records, 1 if it can (with caution) and 2 if PEBS is preferred.
+    pub fn collect_pebs_record(&self) -> Option<u64> {
+        self.collect_pebs_record
+    }
+
+    /// The recommended number of events between two PMU interrupts
+    /// when sampling this event.
+    ///
+    /// Falls back to [`DEFAULT_SAMPLE_PERIOD`] for events that don't
+    /// specify a sample-after value (e.g., uncore events).
+    pub fn default_sample_period(&self) -> u64 {
+        match self.sample_after_value {
+            0 => DEFAULT_SAMPLE_PERIOD,
+            sav => sav,
+        }
+    }
+
+    /// Errata affecting this event (e.g., `"SKL091, SKL044"`),
+    /// `None` if there are no known errata.
+    pub fn errata(&self) -> Option<&'a str> {
+        self.errata
+            .filter(|e| !e.is_empty() && *e != "0" && *e != "null")
+    }
+
+    /// Does the event have to be scheduled alone (`TakenAlone`)?
+    ///
+    /// Such events can't share the PMU with other events,
+    /// so they shouldn't be multiplexed.
+    pub fn requires_exclusive_counter(&self) -> bool {
+        self.taken_alone
+    }
+
+    /// The filter register(s) used by this (uncore) event,
+    /// `None` if the event doesn't use a filter.
+    pub fn filter(&self) -> Option<&'a str> {
+        self.filter
+            .filter(|f| !f.is_empty() && *f != "na" && *f != "null")
+    }
+
+    /// Is the event deprecated (i.e., it may be removed in future
+    /// versions of Intel's event lists)?
+    ///
+    /// Deprecated events are not included in the tables if the crate
+    /// is built with the `skip-deprecated` feature.
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    /// The uncore unit (box) counting this event, `None` for core events.
+    pub fn uncore_unit(&self) -> Option<UncoreUnit> {
+        self.uncore_spec.as_ref().map(|spec| spec.unit)
+    }
+
+    /// Whether the uncore event is counted with a fixed or a programmable
+    /// counter, `None` for core events.
+    pub fn uncore_counter_type(&self) -> Option<UncoreCounterType> {
+        self.uncore_spec.as_ref().map(|spec| spec.counter_type)
+    }
+}
+
+/// Find all events in `events` whose name contains `pattern` (ignoring case).
+///
+/// For example, `find_events(events, "tor_occupancy")` returns all
+/// UNC_CHA_TOR_OCCUPANCY.* events of Skylake-X. The order is unspecified.
+#[cfg(feature = "performance-counter")]
+pub fn find_events<'a>(
+    events: &'a phf::Map<&'static str, EventDescription<'static>>,
+    pattern: &'a str,
+) -> impl Iterator<Item = &'a EventDescription<'static>> + 'a {
+    events
+        .values()
+        .filter(move |ed| contains_ignore_case(ed.event_name, pattern))
+}
+
+#[cfg(feature = "performance-counter")]
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    let (haystack, needle) = (haystack.as_bytes(), needle.as_bytes());
+    needle.is_empty()
+        || haystack
+            .windows(needle.len())
+            .any(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// The names of all micro-architectures we have event tables for
+/// (e.g., `SKYLAKE`), in alphabetical order.
+pub fn architectures() -> impl Iterator<Item = &'static str> {
+    events::ARCHITECTURES.iter().copied()
+}
+
+/// Look up event `name` of micro-architecture `arch` (one of [`architectures`])
+/// without the phf hash-tables.
+///
+/// The tables are plain slices sorted by event name which are scanned
+/// linearly: a lookup compares the name against every event of the
+/// architecture (a few thousand for server parts, the uncore events
+/// are included) instead of hashing it once. This is fine for setting
+/// up a handful of counters but use `COUNTER_MAP` (`performance-counter`)
+/// for anything that looks up events in a hot path.
+#[cfg(feature = "no-phf")]
+pub fn events_linear(arch: &str, name: &str) -> Option<&'static EventDescription<'static>> {
+    let (_, table) = events::LINEAR_TABLES.iter().find(|(a, _)| *a == arch)?;
+    table
+        .iter()
+        .find(|(event_name, _)| *event_name == name)
+        .map(|(_, ed)| ed)
+}
+
+/// All CPU keys (e.g., `GenuineIntel-6-55`) that can be looked up in `COUNTER_MAP`.
+#[cfg(feature = "performance-counter")]
+pub fn cpu_keys() -> impl Iterator<Item = &'static str> {
+    events::COUNTER_MAP.keys().copied()
+}
+
+/// Return all performance events for the CPU we're currently running on.
+///
+/// The lookup key (e.g., `GenuineIntel-6-55`) is computed from CPUID leaf 1
+/// with the extended family and model fields taken into account.
+/// Returns `None` on non-Intel CPUs or if we don't know about the model.
+#[cfg(feature = "performance-counter")]
+pub fn events_for_cpu() -> Option<&'static phf::Map<&'static str, EventDescription<'static>>> {
+    let cpuid = cpuid::CpuId::new();
+    let vf = cpuid.get_vendor_info()?;
+    if vf.as_str() != "GenuineIntel" {
+        return None;
+    }
+    let fi = cpuid.get_feature_info()?;
+
+    let key = cpu_key(vf.as_str(), fi.family_id(), fi.model_id())?;
+    events::COUNTER_MAP.get(key.as_str()).copied()
+}
+
 #[test]
-fn events_test() {
-    // Note: This will silently fail in case the counter is not available.
-    events().map(|cc| {
-        cc.get("INST_RETIRED.ANY").map(|p| {
-            assert!(p.event_name == "INST_RETIRED.ANY");
+fn mapfile_parsing() {
+    // The matrix row is skipped:
+    let (data_files, fparith_files) = events::TEST_MAPFILE;
+    assert_eq!(
+        data_files,
+        &[
+            "/SKX/skylakex_core_v1.12.json",
+            "/SKX/skylakex_uncore_v1.12.json"
+        ]
+    );
+    assert_eq!(fparith_files, &["/SKX/skylakex_fp_arith_inst_v1.12.json"]);
+}
+
+#[test]
+#[cfg(feature = "no-phf")]
+fn linear_lookup() {
+    let inst = events_linear("SKYLAKEX", "INST_RETIRED.ANY").unwrap();
+    assert_eq!(inst.event_name, "INST_RETIRED.ANY");
+    assert!(events_linear("SKYLAKEX", "NO_SUCH_EVENT").is_none());
+    assert!(events_linear("NO_SUCH_ARCH", "INST_RETIRED.ANY").is_none());
+}
+
+#[cfg(all(test, feature = "performance-counter"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn events_test() {
+        // Note: This will silently fail in case the counter is not available.
+        events().map(|cc| {
+            cc.get("INST_RETIRED.ANY").map(|p| {
+                assert!(p.event_name == "INST_RETIRED.ANY");
+            });
         });
-    });
+    }
+
+    #[test]
+    fn empty_event_code_and_umask() {
+        let empty = events::TEST_FIXTURE.get("UNC_TEST_EMPTY_FIELDS").unwrap();
+        assert_eq!(empty.event_code, None);
+        assert_eq!(empty.umask, None);
+        assert_eq!(empty.msr_value, 0);
+        assert_eq!(empty.uncore_spec.as_ref().unwrap().port_mask, 0);
+        assert_eq!(empty.uncore_spec.as_ref().unwrap().umask_ext, 0);
+        assert!(empty.uncore);
+
+        let regular = events::TEST_FIXTURE.get("UNC_TEST_REGULAR_FIELDS").unwrap();
+        assert_eq!(regular.event_code, Some(Tuple::One(0x04)));
+        assert_eq!(regular.umask, Some(Tuple::One(0x03)));
+    }
+
+    #[test]
+    fn skylakex_events() {
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        assert!(!skx.is_empty());
+
+        // Fixed-function counters:
+        for name in &[
+            "INST_RETIRED.ANY",
+            "CPU_CLK_UNHALTED.THREAD",
+            "CPU_CLK_UNHALTED.REF_TSC",
+        ] {
+            let ed = skx.get(name).unwrap();
+            assert_eq!(ed.event_name, *name);
+            assert!(!ed.uncore);
+            match ed.counter {
+                Counter::Fixed(_) => {}
+                Counter::Programmable(_) => panic!("{} should use a fixed counter", name),
+            }
+        }
+
+        // Uncore events end up in the same table:
+        assert!(skx.get("UNC_CHA_CLOCKTICKS").unwrap().uncore);
+    }
+
+    #[test]
+    fn cpu_key_format() {
+        assert_eq!(
+            cpu_key("GenuineIntel", 6, 0x55).unwrap().as_str(),
+            "GenuineIntel-6-55"
+        );
+        assert_eq!(
+            cpu_key("GenuineIntel", 6, 0x0f).unwrap().as_str(),
+            "GenuineIntel-6-0F"
+        );
+        assert_eq!(
+            cpu_key("GenuineIntel", 0x10, 0x1).unwrap().as_str(),
+            "GenuineIntel-10-01"
+        );
+    }
+
+    #[test]
+    fn events_for_cpu_test() {
+        // Either we don't know the host CPU or it has the architectural events:
+        if let Some(cc) = events_for_cpu() {
+            assert!(cc.get("INST_RETIRED.ANY").is_some());
+        }
+    }
+
+    #[test]
+    fn perfevtsel_encoding() {
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+
+        // UnHalted Core Cycles
+        let cycles = skx.get("CPU_CLK_UNHALTED.THREAD_P").unwrap();
+        assert_eq!(cycles.perfevtsel_bits(), 0x3003c);
+        let cycles_any = skx.get("CPU_CLK_UNHALTED.THREAD_P_ANY").unwrap();
+        assert_eq!(cycles_any.perfevtsel_bits(), 0x23003c);
+
+        // Edge detect, invert and counter mask
+        let rs_empty_end = skx.get("RS_EVENTS.EMPTY_END").unwrap();
+        assert_eq!(rs_empty_end.perfevtsel_bits(), 0x187015e);
+
+        // PEBS event
+        let loads = skx.get("MEM_INST_RETIRED.ALL_LOADS").unwrap();
+        assert_eq!(loads.pebs, PebsType::PebsOrRegular);
+        assert_eq!(loads.perfevtsel_bits(), 0x381d0);
+    }
+
+    #[test]
+    fn offcore_events() {
+        let glm = events::COUNTER_MAP.get("GenuineIntel-6-5C").unwrap();
+        let ed = glm.get("OFFCORE_RESPONSE.COREWB.L2_MISS.ANY").unwrap();
+        assert!(ed.offcore);
+        assert_eq!(ed.msr_index, MSRIndex::One(0x1a6));
+        assert_eq!(ed.msr_value, 0x3600000008);
+
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        let ed = skx
+            .get("OFFCORE_RESPONSE.ALL_DATA_RD.L3_HIT.HIT_OTHER_CORE_NO_FWD")
+            .unwrap();
+        assert!(ed.offcore);
+        assert_eq!(ed.msr_index, MSRIndex::Two(0x1a6, 0x1a7));
+    }
+
+    #[test]
+    fn uncore_units() {
+        // Cascade Lake shares the Skylake-X uncore which is what the mapfile uses for it:
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        let ed = skx.get("UNC_CHA_TOR_OCCUPANCY.IA_MISS").unwrap();
+        assert_eq!(ed.uncore_unit(), Some(UncoreUnit::Cha));
+        assert_eq!(ed.event_code, Some(Tuple::One(0x36)));
+        assert_eq!(ed.umask, Some(Tuple::One(0x21)));
+
+        let spec = ed.uncore_spec.as_ref().unwrap();
+        assert_eq!(spec.fc_mask, 0);
+        assert_eq!(spec.port_mask, 0);
+        assert_eq!(spec.umask_ext, 0);
+
+        assert_eq!(skx.get("INST_RETIRED.ANY").unwrap().uncore_unit(), None);
+    }
+
+    #[test]
+    fn uncore_counter_types() {
+        // The Cascade Lake iMC (same as Skylake-X) has a fixed DCLK counter
+        // in addition to the programmable ones:
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        let clockticks = skx.get("UNC_M_CLOCKTICKS").unwrap();
+        assert_eq!(clockticks.uncore_unit(), Some(UncoreUnit::Imc));
+        assert_eq!(
+            clockticks.uncore_counter_type(),
+            Some(UncoreCounterType::Programmable)
+        );
+        let fixed = skx.get("UNC_M_CLOCKTICKS_F").unwrap();
+        assert_eq!(fixed.uncore_counter_type(), Some(UncoreCounterType::Fixed));
+        assert_eq!(
+            skx.get("INST_RETIRED.ANY").unwrap().uncore_counter_type(),
+            None
+        );
+
+        // An explicit CounterType field:
+        let explicit = events::TEST_FIXTURE.get("UNC_TEST_COUNTER_TYPE").unwrap();
+        assert_eq!(
+            explicit.uncore_counter_type(),
+            Some(UncoreCounterType::Programmable)
+        );
+        assert_eq!(explicit.pdir_counter, Some("0"));
+        assert_eq!(clockticks.pdir_counter, None);
+    }
+
+    #[test]
+    fn msr_index_parsing() {
+        let msr_index = |name| &events::TEST_FIXTURE.get(name).unwrap().msr_index;
+
+        assert_eq!(*msr_index("UNC_TEST_REGULAR_FIELDS"), MSRIndex::None);
+        assert_eq!(*msr_index("UNC_TEST_MSR_INDEX_NONE"), MSRIndex::None);
+        assert_eq!(*msr_index("UNC_TEST_MSR_INDEX_EMPTY"), MSRIndex::None);
+        assert_eq!(*msr_index("UNC_TEST_MSR_INDEX_ONE"), MSRIndex::One(0x1a6));
+        assert_eq!(
+            *msr_index("UNC_TEST_MSR_INDEX_TWO"),
+            MSRIndex::Two(0x3f6, 0x1a6)
+        );
+        assert_eq!(
+            *msr_index("UNC_TEST_MSR_INDEX_TWO_SPACES"),
+            MSRIndex::Two(0x1a6, 0x1a7)
+        );
+    }
+
+    #[test]
+    fn pebs_types() {
+        let skl = events::COUNTER_MAP.get("GenuineIntel-6-5E").unwrap();
+
+        let prec_dist = skl.get("INST_RETIRED.PREC_DIST").unwrap();
+        assert_eq!(prec_dist.pebs, PebsType::PebsOnly);
+        assert!(prec_dist.supports_pebs());
+        assert!(prec_dist.pebs_only());
+
+        let loads = skl.get("MEM_INST_RETIRED.ALL_LOADS").unwrap();
+        assert!(loads.supports_pebs());
+        assert!(!loads.pebs_only());
+
+        let cycles = skl.get("CPU_CLK_UNHALTED.THREAD_P").unwrap();
+        assert!(!cycles.supports_pebs());
+        assert!(!cycles.pebs_only());
+    }
+
+    #[test]
+    fn pebs_record_metadata() {
+        let skl = events::COUNTER_MAP.get("GenuineIntel-6-5E").unwrap();
+        let loads = skl.get("MEM_INST_RETIRED.ALL_LOADS").unwrap();
+        assert!(loads.collects_data_linear_address());
+        assert!(!loads.has_l1_hit_indication());
+        assert!(!loads.is_precise_store());
+        assert_eq!(loads.collect_pebs_record(), None);
+
+        let stores = skl.get("MEM_INST_RETIRED.ALL_STORES").unwrap();
+        assert!(stores.collects_data_linear_address());
+        assert!(stores.has_l1_hit_indication());
+
+        let cycles = skl.get("CPU_CLK_UNHALTED.THREAD_P").unwrap();
+        assert!(!cycles.collects_data_linear_address());
+
+        let ivb = events::COUNTER_MAP.get("GenuineIntel-6-3A").unwrap();
+        assert!(ivb
+            .get("MEM_TRANS_RETIRED.PRECISE_STORE")
+            .unwrap()
+            .is_precise_store());
+
+        let glm = events::COUNTER_MAP.get("GenuineIntel-6-5C").unwrap();
+        let loads = glm.get("MEM_UOPS_RETIRED.ALL_LOADS").unwrap();
+        assert!(loads.collects_data_linear_address());
+        assert_eq!(loads.collect_pebs_record(), Some(2));
+        let inst = glm.get("INST_RETIRED.ANY").unwrap();
+        assert_eq!(inst.collect_pebs_record(), Some(0));
+    }
+
+    #[test]
+    fn fixed_counter_indices() {
+        let skl = events::COUNTER_MAP.get("GenuineIntel-6-5E").unwrap();
+        let index = |name| skl.get(name).unwrap().fixed_counter_index();
+
+        assert_eq!(index("INST_RETIRED.ANY"), Some(0));
+        assert_eq!(index("CPU_CLK_UNHALTED.THREAD"), Some(1));
+        assert_eq!(index("CPU_CLK_UNHALTED.REF_TSC"), Some(2));
+        assert_eq!(index("CPU_CLK_UNHALTED.THREAD_P"), None);
+
+        let inst = skl.get("INST_RETIRED.ANY").unwrap();
+        assert_eq!(inst.counter(), Counter::Fixed(0b1));
+    }
+
+    #[test]
+    fn sample_periods() {
+        let skl = events::COUNTER_MAP.get("GenuineIntel-6-5E").unwrap();
+        let period = |name| skl.get(name).unwrap().default_sample_period();
+
+        assert_eq!(period("INST_RETIRED.ANY"), 2_000_003);
+        assert_eq!(period("BR_MISP_RETIRED.ALL_BRANCHES"), 400_009);
+        assert_eq!(period("MEM_LOAD_RETIRED.L3_MISS"), 100_007);
+
+        // Uncore events come without a sample-after value:
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        let clockticks = skx.get("UNC_CHA_CLOCKTICKS").unwrap();
+        assert_eq!(clockticks.sample_after_value, 0);
+        assert_eq!(clockticks.default_sample_period(), DEFAULT_SAMPLE_PERIOD);
+    }
+
+    #[test]
+    fn event_metadata() {
+        let skl = events::COUNTER_MAP.get("GenuineIntel-6-5E").unwrap();
+
+        let llc_miss = skl.get("LONGEST_LAT_CACHE.MISS").unwrap();
+        assert_eq!(llc_miss.errata(), Some("SKL057"));
+        assert!(!llc_miss.requires_exclusive_counter());
+        assert_eq!(llc_miss.filter(), None);
+
+        let prec_dist = skl.get("INST_RETIRED.PREC_DIST").unwrap();
+        assert_eq!(prec_dist.errata(), Some("SKL091, SKL044"));
+        // Errata is "0" or "null" for unaffected events:
+        assert_eq!(skl.get("INST_RETIRED.ANY").unwrap().errata(), None);
+        assert_eq!(skl.get("CPU_CLK_UNHALTED.THREAD_P").unwrap().errata(), None);
+
+        let dsb_miss = skl.get("FRONTEND_RETIRED.DSB_MISS").unwrap();
+        assert!(dsb_miss.requires_exclusive_counter());
+
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        let tor_inserts = skx.get("UNC_CHA_TOR_INSERTS.IA_MISS").unwrap();
+        assert_eq!(tor_inserts.filter(), Some("CHAFilter1[31:0]"));
+        // "na" means there is no filter:
+        assert_eq!(skx.get("UNC_CHA_CLOCKTICKS").unwrap().filter(), None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "skip-deprecated"))]
+    fn deprecated_events() {
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        assert!(skx
+            .get("L2_LINES_OUT.USELESS_PREF")
+            .unwrap()
+            .is_deprecated());
+        assert!(skx.get("UNC_C_CLOCKTICKS").unwrap().is_deprecated());
+        assert!(!skx.get("UNC_CHA_CLOCKTICKS").unwrap().is_deprecated());
+    }
+
+    #[test]
+    #[cfg(feature = "skip-deprecated")]
+    fn deprecated_events_skipped() {
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        assert!(skx.get("L2_LINES_OUT.USELESS_PREF").is_none());
+        assert!(skx.get("UNC_C_CLOCKTICKS").is_none());
+        assert!(skx.values().all(|ed| !ed.is_deprecated()));
+    }
+
+    #[test]
+    fn fp_arith_metrics() {
+        let skx = events::METRIC_MAP.get("GenuineIntel-6-55").unwrap();
+        assert_eq!(skx.len(), 8);
+        assert_eq!(skx[0].bit_name, "SCALAR_DOUBLE");
+        assert_eq!(skx[0].flops_multiplier, 1);
+
+        let avx512 = skx.iter().find(|m| m.bit_index == 7).unwrap();
+        assert_eq!(avx512.bit_name, "512BIT_PACKED_SINGLE");
+        assert_eq!(avx512.flops_multiplier, 16);
+
+        let skl = events::METRIC_MAP.get("GenuineIntel-6-5E").unwrap();
+        assert_eq!(skl.len(), 6);
+        assert!(events::METRIC_MAP.get("GenuineIntel-6-2A").is_none());
+    }
+
+    #[test]
+    fn shared_tables() {
+        // Skylake client models all use the same data file:
+        let skl = events::COUNTER_MAP.get("GenuineIntel-6-4E").unwrap();
+        for key in &[
+            "GenuineIntel-6-5E",
+            "GenuineIntel-6-8E",
+            "GenuineIntel-6-9E",
+        ] {
+            assert!(core::ptr::eq(*skl, *events::COUNTER_MAP.get(key).unwrap()));
+        }
+
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        assert!(!core::ptr::eq(*skl, *skx));
+    }
+
+    #[test]
+    fn two_value_fields() {
+        let skl = events::COUNTER_MAP.get("GenuineIntel-6-5E").unwrap();
+        let offcore = skl.get("OFFCORE_RESPONSE").unwrap();
+        assert_eq!(offcore.event_code, Some(Tuple::Two(0xb7, 0xbb)));
+        assert_eq!(offcore.secondary_event_code(), Some(0xbb));
+        assert_eq!(offcore.umask, Some(Tuple::One(0x01)));
+        assert_eq!(offcore.secondary_umask(), None);
+        // Only the primary values end up in the event select register:
+        assert_eq!(offcore.perfevtsel_bits() & 0xffff, 0x01b7);
+
+        let glm = events::COUNTER_MAP.get("GenuineIntel-6-5C").unwrap();
+        let offcore = glm.get("OFFCORE_RESPONSE").unwrap();
+        assert_eq!(offcore.secondary_event_code(), None);
+        assert_eq!(offcore.umask, Some(Tuple::Two(0x01, 0x02)));
+        assert_eq!(offcore.secondary_umask(), Some(0x02));
+        assert_eq!(offcore.perfevtsel_bits() & 0xffff, 0x01b7);
+
+        let inst = skl.get("INST_RETIRED.ANY_P").unwrap();
+        assert_eq!(inst.event_code.as_ref().map(Tuple::primary), Some(0xc0));
+        assert_eq!(inst.secondary_event_code(), None);
+    }
+
+    #[test]
+    fn enumerate_tables() {
+        assert!(architectures().count() > 0);
+        assert!(architectures().any(|arch| arch == "SKYLAKEX"));
+        assert!(!architectures().any(|arch| arch == "TEST_FIXTURE"));
+
+        assert!(cpu_keys().count() >= architectures().count());
+        for key in cpu_keys() {
+            assert!(key.starts_with("GenuineIntel-"));
+            assert!(!events::COUNTER_MAP.get(key).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn search_events() {
+        // Cascade Lake (GenuineIntel-6-55) uses the Skylake-X tables:
+        let clx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        let hits = find_events(clx, "TOR_OCCUPANCY");
+        assert!(hits.count() > 1);
+        assert!(find_events(clx, "tor_occupancy").all(|ed| ed.event_name.contains("TOR_OCCUPANCY")));
+        assert!(find_events(clx, "tor_occupancy.ia_miss").count() > 0);
+
+        assert_eq!(find_events(clx, "NO_SUCH_EVENT").count(), 0);
+        assert_eq!(find_events(clx, "").count(), clx.len());
+        assert!(contains_ignore_case("INST_RETIRED.ANY", "retired.any"));
+        assert!(!contains_ignore_case("INST", "INST_RETIRED"));
+    }
+
+    #[test]
+    fn event_descriptions() {
+        let skl = events::COUNTER_MAP.get("GenuineIntel-6-5E").unwrap();
+        let llc_miss = skl.get("LONGEST_LAT_CACHE.MISS").unwrap();
+        assert_eq!(
+            llc_miss.brief_description(),
+            "Core-originated cacheable demand requests missed L3"
+        );
+        assert!(llc_miss
+            .public_description()
+            .unwrap()
+            .starts_with("Counts core-originated cacheable requests that miss the L3 cache"));
+
+        let inst = skl.get("INST_RETIRED.ANY").unwrap();
+        assert_eq!(
+            inst.brief_description(),
+            "Instructions retired from execution."
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "no-phf")]
+    fn linear_lookup_matches_phf() {
+        extern crate std;
+        use std::format;
+
+        let skx = events::COUNTER_MAP.get("GenuineIntel-6-55").unwrap();
+        for name in &[
+            "INST_RETIRED.ANY",
+            "MEM_INST_RETIRED.ALL_LOADS",
+            "UNC_CHA_TOR_OCCUPANCY.IA_MISS",
+        ] {
+            let linear = events_linear("SKYLAKEX", name).unwrap();
+            assert_eq!(
+                format!("{:?}", linear),
+                format!("{:?}", skx.get(name).unwrap())
+            );
+        }
+        assert_eq!(events::SKYLAKEX_EVENTS.len(), skx.len());
+    }
 }
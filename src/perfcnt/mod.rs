@@ -1 +1,81 @@
+//! Information about performance events.
+#[cfg(feature = "performance-counter")]
+pub mod amd;
 pub mod intel;
+
+use core::arch::asm;
+#[cfg(feature = "performance-counter")]
+use core::fmt::{Error, Result, Write};
+#[cfg(feature = "performance-counter")]
+use core::str;
+
+#[cfg(feature = "performance-counter")]
+const MODEL_LEN: usize = 30;
+
+#[cfg(feature = "performance-counter")]
+#[derive(Default)]
+pub(crate) struct ModelWriter {
+    buffer: [u8; MODEL_LEN],
+    index: usize,
+}
+
+#[cfg(feature = "performance-counter")]
+impl ModelWriter {
+    pub(crate) fn as_str(&self) -> &str {
+        str::from_utf8(&self.buffer[..self.index]).unwrap()
+    }
+}
+
+#[cfg(feature = "performance-counter")]
+impl Write for ModelWriter {
+    fn write_str(&mut self, s: &str) -> Result {
+        // TODO: There exists probably a more efficient way of doing this:
+        for c in s.chars() {
+            if self.index >= self.buffer.len() {
+                return Err(Error);
+            }
+            self.buffer[self.index] = c as u8;
+            self.index += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Format the `<vendor>-<family>-<model>` key used to index the `COUNTER_MAP`s.
+#[cfg(feature = "performance-counter")]
+pub(crate) fn cpu_key(vendor: &str, family: u8, model: u8) -> Option<ModelWriter> {
+    let mut writer: ModelWriter = Default::default();
+    write!(writer, "{}-{:X}-{:02X}", vendor, family, model).ok()?;
+    Some(writer)
+}
+
+/// Counter index selecting the fixed-function counters for `rdpmc`
+/// (i.e., `rdpmc(FIXED_COUNTERS | n)` reads IA32_FIXED_CTRn).
+pub const FIXED_COUNTERS: u32 = 1 << 30;
+
+/// Read performance monitoring counter `counter`.
+///
+/// `counter` is the index of a programmable counter (IA32_PMCx) or
+/// `FIXED_COUNTERS | n` for fixed counter `n`. The result contains as
+/// many bits as the counter is wide.
+///
+/// # Safety
+/// Raises #GP if `counter` is invalid, or if CPL > 0 and CR4.PCE is clear
+/// (on Linux, see `/sys/bus/event_source/devices/cpu/rdpmc`).
+pub unsafe fn rdpmc(counter: u32) -> u64 {
+    let (high, low): (u32, u32);
+    asm!("rdpmc", in("ecx") counter, out("eax") low, out("edx") high, options(nomem, nostack));
+    ((high as u64) << 32) | (low as u64)
+}
+
+#[test]
+#[ignore]
+fn rdpmc_fixed_counter() {
+    // Needs CR4.PCE set for user-space and fixed counter 0
+    // (instructions retired) enabled in IA32_FIXED_CTR_CTRL.
+    unsafe {
+        let first = rdpmc(FIXED_COUNTERS);
+        let second = rdpmc(FIXED_COUNTERS);
+        assert!(second >= first);
+    }
+}
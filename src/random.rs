@@ -207,6 +207,56 @@ pub unsafe fn rdseed_slice<T: RdSeed>(buffer: &mut [T]) -> bool {
     worked
 }
 
+/// Number of attempts before [rdrand64_retry] gives up.
+///
+/// Intel recommends 10 retries for RDRAND: failing that often indicates
+/// a hardware problem rather than a temporarily exhausted entropy source.
+pub const RDRAND_RETRIES: usize = 10;
+
+/// Number of attempts before [rdseed64_retry] gives up.
+///
+/// RDSEED runs out of entropy much faster than RDRAND under load,
+/// hence the larger number of attempts (with a `pause` in between).
+pub const RDSEED_RETRIES: usize = 100;
+
+/// Returns a 64-bit random value from RDRAND.
+///
+/// Retries up to [RDRAND_RETRIES] times, returns `None` if all attempts
+/// failed or RDRAND is not supported (CPUID.01H:ECX.RDRAND\[bit 30\]).
+#[cfg(target_arch = "x86_64")]
+pub fn rdrand64_retry() -> Option<u64> {
+    if !crate::cpuid::features().contains(crate::cpuid::FeatureFlags::RDRAND) {
+        return None;
+    }
+
+    let mut value = 0;
+    (0..RDRAND_RETRIES)
+        .any(|_| unsafe { rdrand64(&mut value) })
+        .then_some(value)
+}
+
+/// Returns a 64-bit random seed from RDSEED.
+///
+/// Retries up to [RDSEED_RETRIES] times, returns `None` if all attempts
+/// failed or RDSEED is not supported (CPUID.(EAX=07H, ECX=0H):EBX.RDSEED\[bit 18\]).
+#[cfg(target_arch = "x86_64")]
+pub fn rdseed64_retry() -> Option<u64> {
+    if !crate::cpuid::features().contains(crate::cpuid::FeatureFlags::RDSEED) {
+        return None;
+    }
+
+    let mut value = 0;
+    (0..RDSEED_RETRIES)
+        .any(|_| {
+            let success = unsafe { rdseed64(&mut value) };
+            if !success {
+                crate::pause();
+            }
+            success
+        })
+        .then_some(value)
+}
+
 #[cfg(all(test, feature = "utest"))]
 mod test {
     use super::*;
@@ -360,4 +410,13 @@ mod test {
             assert!(buf[0] > 0 || buf[1] > 0 || buf[2] > 0 || buf[3] > 0);
         }
     }
+
+    #[test]
+    #[ignore]
+    fn retry_on_capable_host() {
+        // Needs a host with RDRAND and RDSEED
+        let first = rdrand64_retry().expect("RDRAND failed");
+        assert_ne!(Some(first), rdrand64_retry());
+        assert!(rdseed64_retry().is_some());
+    }
 }
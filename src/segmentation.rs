@@ -27,15 +27,26 @@ bitflags! {
     }
 }
 
+/// Descriptor table a segment selector refers to (the TI bit).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum SegmentTable {
+    /// Global Descriptor Table
+    Gdt = 0,
+    /// Local Descriptor Table
+    Ldt = 1,
+}
+
 impl SegmentSelector {
     /// Create a new SegmentSelector
     ///
     /// # Arguments
     ///  * `index` - index in GDT or LDT array.
     ///  * `rpl` - Requested privilege level of the selector
-    pub const fn new(index: u16, rpl: Ring) -> SegmentSelector {
+    ///  * `table` - Descriptor table the index refers to
+    pub const fn new(index: u16, rpl: Ring, table: SegmentTable) -> SegmentSelector {
         SegmentSelector {
-            bits: index << 3 | (rpl as u16),
+            bits: index << 3 | (table as u16) << 2 | (rpl as u16),
         }
     }
 
@@ -44,6 +55,25 @@ impl SegmentSelector {
         self.bits >> 3
     }
 
+    /// Returns the requested privilege level of the selector.
+    pub fn rpl(&self) -> Ring {
+        match self.bits & 0b11 {
+            0b00 => Ring::Ring0,
+            0b01 => Ring::Ring1,
+            0b10 => Ring::Ring2,
+            _ => Ring::Ring3,
+        }
+    }
+
+    /// Returns the descriptor table the selector refers to.
+    pub fn table(&self) -> SegmentTable {
+        if self.contains(SegmentSelector::TI_LDT) {
+            SegmentTable::Ldt
+        } else {
+            SegmentTable::Gdt
+        }
+    }
+
     /// Make a new segment selector from a untyped u16 value.
     pub const fn from_raw(bits: u16) -> SegmentSelector {
         SegmentSelector { bits }
@@ -181,8 +211,8 @@ pub enum CodeSegmentType {
 }
 
 /// Helper enum type to differentiate between the different descriptor types that all end up written in the same field.
-#[derive(Debug, Eq, PartialEq)]
-pub(crate) enum DescriptorType {
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DescriptorType {
     System64(SystemDescriptorTypes64),
     System32(SystemDescriptorTypes32),
     Data(DataSegmentType),
@@ -414,6 +444,17 @@ impl BuildDescriptor<Descriptor> for DescriptorBuilder {
     }
 }
 
+/// The default operand size of a code segment.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CodeBits {
+    /// 16-bit code (D and L clear).
+    Bits16,
+    /// 32-bit code (D set, L clear).
+    Bits32,
+    /// 64-bit code (L set, D clear), IA-32e mode only.
+    Bits64,
+}
+
 /// Entry for IDT, GDT or LDT. Provides size and location of a segment.
 ///
 /// See Intel 3a, Section 3.4.5 "Segment Descriptors", and Section 3.5.2
@@ -437,6 +478,112 @@ impl Descriptor {
         (self.upper as u64) << 32 | self.lower as u64
     }
 
+    /// Returns the base address of the segment.
+    pub fn base(&self) -> u32 {
+        let (lower, upper) = (self.lower, self.upper);
+        (lower >> 16) | ((upper & 0xff) << 16) | (upper & 0xff00_0000)
+    }
+
+    /// Returns the limit of the segment in bytes (scaled to 4 KiB units if G is set).
+    pub fn limit(&self) -> u32 {
+        let (lower, upper) = (self.lower, self.upper);
+        let limit = (lower & 0xffff) | (upper & 0x000f_0000);
+        if upper & bit!(23) != 0 {
+            (limit << 12) | 0xfff
+        } else {
+            limit
+        }
+    }
+
+    /// Decodes the type field (bits 8-11) together with the S flag.
+    ///
+    /// System descriptors are interpreted with the 32-bit types,
+    /// returns `None` for the reserved system types.
+    pub fn descriptor_type(&self) -> Option<DescriptorType> {
+        let upper = self.upper;
+        let typ = ((upper >> 8) & 0x0f) as u8;
+        if upper & bit!(12) == 0 {
+            return system_descriptor_type32(typ).map(DescriptorType::System32);
+        }
+
+        Some(match typ {
+            0b0000 => DescriptorType::Data(DataSegmentType::ReadOnly),
+            0b0001 => DescriptorType::Data(DataSegmentType::ReadOnlyAccessed),
+            0b0010 => DescriptorType::Data(DataSegmentType::ReadWrite),
+            0b0011 => DescriptorType::Data(DataSegmentType::ReadWriteAccessed),
+            0b0100 => DescriptorType::Data(DataSegmentType::ReadExpand),
+            0b0101 => DescriptorType::Data(DataSegmentType::ReadExpandAccessed),
+            0b0110 => DescriptorType::Data(DataSegmentType::ReadWriteExpand),
+            0b0111 => DescriptorType::Data(DataSegmentType::ReadWriteExpandAccessed),
+            0b1000 => DescriptorType::Code(CodeSegmentType::Execute),
+            0b1001 => DescriptorType::Code(CodeSegmentType::ExecuteAccessed),
+            0b1010 => DescriptorType::Code(CodeSegmentType::ExecuteRead),
+            0b1011 => DescriptorType::Code(CodeSegmentType::ExecuteReadAccessed),
+            0b1100 => DescriptorType::Code(CodeSegmentType::ExecuteConforming),
+            0b1101 => DescriptorType::Code(CodeSegmentType::ExecuteConformingAccessed),
+            0b1110 => DescriptorType::Code(CodeSegmentType::ExecuteReadConforming),
+            _ => DescriptorType::Code(CodeSegmentType::ExecuteReadConformingAccessed),
+        })
+    }
+
+    /// Returns the privilege level of the segment.
+    pub fn dpl(&self) -> Ring {
+        match (self.upper >> 13) & 0b11 {
+            0b00 => Ring::Ring0,
+            0b01 => Ring::Ring1,
+            0b10 => Ring::Ring2,
+            _ => Ring::Ring3,
+        }
+    }
+
+    /// Is the P bit set?
+    pub fn is_present(&self) -> bool {
+        self.upper & bit!(15) != 0
+    }
+
+    /// Is the L bit set (a 64-bit code segment)?
+    pub fn is_long_mode(&self) -> bool {
+        self.upper & bit!(21) != 0
+    }
+
+    /// Creates a present, flat code segment (execute/read, base 0, limit 4 GiB)
+    /// with privilege level `dpl`.
+    ///
+    /// `bits` selects the default operand size with the D and L flags.
+    pub fn flat_code_segment(dpl: Ring, bits: CodeBits) -> Descriptor {
+        let builder = <DescriptorBuilder as SegmentDescriptorBuilder<u32>>::code_descriptor(
+            0,
+            0xFFFFF,
+            CodeSegmentType::ExecuteRead,
+        )
+        .present()
+        .dpl(dpl)
+        .limit_granularity_4kb();
+        match bits {
+            CodeBits::Bits16 => builder,
+            CodeBits::Bits32 => builder.db(),
+            CodeBits::Bits64 => builder.l(),
+        }
+        .finish()
+    }
+
+    /// Creates a present, flat data segment (read/write, base 0, limit 4 GiB)
+    /// with privilege level `dpl`.
+    ///
+    /// D/B is set (32-bit stack pointer for `ss`), it is ignored in 64-bit mode.
+    pub fn flat_data_segment(dpl: Ring) -> Descriptor {
+        <DescriptorBuilder as SegmentDescriptorBuilder<u32>>::data_descriptor(
+            0,
+            0xFFFFF,
+            DataSegmentType::ReadWrite,
+        )
+        .present()
+        .dpl(dpl)
+        .limit_granularity_4kb()
+        .db()
+        .finish()
+    }
+
     pub(crate) fn apply_builder_settings(&mut self, builder: &DescriptorBuilder) {
         if let Some(ring) = builder.dpl {
             self.set_dpl(ring)
@@ -557,6 +704,26 @@ impl Descriptor {
     }
 }
 
+/// Maps the type field of a system descriptor to the 32-bit system types.
+fn system_descriptor_type32(typ: u8) -> Option<SystemDescriptorTypes32> {
+    use SystemDescriptorTypes32::*;
+    Some(match typ {
+        0b0001 => TSSAvailable16,
+        0b0010 => LDT,
+        0b0011 => TSSBusy16,
+        0b0100 => CallGate16,
+        0b0101 => TaskGate,
+        0b0110 => InterruptGate16,
+        0b0111 => TrapGate16,
+        0b1001 => TssAvailable32,
+        0b1011 => TssBusy32,
+        0b1100 => CallGate32,
+        0b1110 => InterruptGate32,
+        0b1111 => TrapGate32,
+        _ => return None,
+    })
+}
+
 /// Reload stack segment register.
 ///
 /// # Safety
@@ -708,4 +875,95 @@ mod test {
             (stack_user.as_u64() ^ 0x00CFF2000000FFFFu64).trailing_zeros()
         );
     }
+
+    #[test]
+    fn flat_segments() {
+        let db = |desc: Descriptor| desc.upper & bit!(22) != 0;
+
+        let code64 = Descriptor::flat_code_segment(Ring::Ring0, CodeBits::Bits64);
+        assert!(code64.is_long_mode());
+        assert!(!db(code64));
+        assert_eq!(code64.as_u64(), 0x00af_9a00_0000_ffff);
+
+        let code32 = Descriptor::flat_code_segment(Ring::Ring3, CodeBits::Bits32);
+        assert!(!code32.is_long_mode());
+        assert!(db(code32));
+        assert_eq!(code32.dpl(), Ring::Ring3);
+        assert_eq!(code32.as_u64(), 0x00cf_fa00_0000_ffff);
+
+        let code16 = Descriptor::flat_code_segment(Ring::Ring0, CodeBits::Bits16);
+        assert!(!code16.is_long_mode());
+        assert!(!db(code16));
+
+        let data = Descriptor::flat_data_segment(Ring::Ring0);
+        assert_eq!((data.base(), data.limit()), (0, 0xffff_ffff));
+        assert_eq!(
+            data.descriptor_type(),
+            Some(DescriptorType::Data(DataSegmentType::ReadWrite))
+        );
+        assert_eq!(data.as_u64(), 0x00cf_9200_0000_ffff);
+    }
+
+    #[test]
+    fn descriptor_decoding() {
+        // The 64-bit code segment a VMM typically sets up for its guest:
+        // base 0, limit 4 GiB, type 0xb, S, P, DPL 0, L and G.
+        let code = Descriptor {
+            lower: 0x0000_ffff,
+            upper: 0x00af_9b00,
+        };
+        assert_eq!(code.base(), 0);
+        assert_eq!(code.limit(), 0xffff_ffff);
+        assert_eq!(
+            code.descriptor_type(),
+            Some(DescriptorType::Code(CodeSegmentType::ExecuteReadAccessed))
+        );
+        assert_eq!(code.dpl(), Ring::Ring0);
+        assert!(code.is_present());
+        assert!(code.is_long_mode());
+
+        let data: Descriptor =
+            DescriptorBuilder::data_descriptor(0x1234_5678, 0xabcd, DataSegmentType::ReadWrite)
+                .present()
+                .dpl(Ring::Ring3)
+                .finish();
+        assert_eq!(data.base(), 0x1234_5678);
+        assert_eq!(data.limit(), 0xabcd);
+        assert_eq!(
+            data.descriptor_type(),
+            Some(DescriptorType::Data(DataSegmentType::ReadWrite))
+        );
+        assert_eq!(data.dpl(), Ring::Ring3);
+        assert!(!data.is_long_mode());
+
+        let tss: Descriptor =
+            <DescriptorBuilder as GateDescriptorBuilder<u32>>::tss_descriptor(0x1000, 0x67, true)
+                .finish();
+        assert_eq!(
+            tss.descriptor_type(),
+            Some(DescriptorType::System32(
+                SystemDescriptorTypes32::TssAvailable32
+            ))
+        );
+        assert!(!tss.is_present());
+        assert_eq!(Descriptor::NULL.descriptor_type(), None);
+    }
+
+    #[test]
+    fn segment_selector() {
+        let kcode = SegmentSelector::new(1, Ring::Ring0, SegmentTable::Gdt);
+        assert_eq!(kcode.bits(), 0x8);
+        assert_eq!(kcode.index(), 1);
+        assert_eq!(kcode.rpl(), Ring::Ring0);
+        assert_eq!(kcode.table(), SegmentTable::Gdt);
+
+        let user = SegmentSelector::new(2, Ring::Ring3, SegmentTable::Ldt);
+        assert_eq!(user.bits(), 0x17);
+        assert_eq!(user.index(), 2);
+        assert_eq!(user.rpl(), Ring::Ring3);
+        assert_eq!(user.table(), SegmentTable::Ldt);
+
+        assert_eq!(SegmentSelector::from_raw(0x2b).rpl(), Ring::Ring3);
+        assert_eq!(SegmentSelector::from_raw(0x2b).index(), 5);
+    }
 }
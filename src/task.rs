@@ -18,8 +18,35 @@ pub unsafe fn tr() -> segmentation::SegmentSelector {
 
 /// Loads the task register.
 ///
+/// The processor marks the referenced TSS descriptor as busy,
+/// so the GDT must be writable. Use [tr] to read the task register.
+///
+/// # Example
+///
+/// ```no_run
+/// use x86::bits64::segmentation::GdtBuilder;
+/// use x86::bits64::task::TaskStateSegment;
+/// use x86::segmentation::Descriptor;
+/// use x86::{dtables, task, Ring};
+///
+/// static mut TSS: TaskStateSegment = TaskStateSegment::new();
+/// static mut GDT: [Descriptor; 5] = [Descriptor::NULL; 5];
+///
+/// unsafe {
+///     let mut builder = GdtBuilder::new(&mut *core::ptr::addr_of_mut!(GDT));
+///     builder.add_code_segment(Ring::Ring0);
+///     builder.add_data_segment(Ring::Ring0);
+///     let tss = builder.add_tss(&*core::ptr::addr_of!(TSS));
+///     let (_, gdtr) = builder.finalize();
+///
+///     dtables::lgdt(&gdtr);
+///     task::load_tr(tss);
+///     assert_eq!(task::tr(), tss);
+/// }
+/// ```
+///
 /// # Safety
-/// Needs CPL 0.
+/// Needs CPL 0. `sel` must point to an available TSS descriptor.
 pub unsafe fn load_tr(sel: segmentation::SegmentSelector) {
     asm!("ltr {0:x}",
         in(reg) sel.bits(),
@@ -1,6 +1,6 @@
 //! Functions to read time stamp counters on x86.
 
-use crate::arch::{__rdtscp, _rdtsc};
+use crate::arch::{__rdtscp, _mm_lfence, _mm_mfence, _rdtsc};
 
 /// Read the time stamp counter.
 ///
@@ -19,22 +19,47 @@ pub unsafe fn rdtsc() -> u64 {
     _rdtsc() as u64
 }
 
-/// Read the time stamp counter.
+/// Read the time stamp counter and the IA32_TSC_AUX MSR.
 ///
 /// The RDTSCP instruction waits until all previous instructions
 /// have been executed before reading the counter.
 /// However, subsequent instructions may begin execution
 /// before the read operation is performed.
 ///
+/// The second value is the content of IA32_TSC_AUX, which operating
+/// systems typically initialize with the current CPU (and node) number.
+/// This allows to detect if two readings were taken on different cores.
+///
 /// Volatile is used here because the function may be used to act as
 /// an instruction barrier.
 ///
 /// # Safety
 /// * Causes a GP fault if the TSD flag in register CR4 is set and the
 ///   CPL is greater than 0.
-pub unsafe fn rdtscp() -> u64 {
-    let mut _aux = 0;
-    __rdtscp(&mut _aux)
+pub unsafe fn rdtscp() -> (u64, u32) {
+    let mut aux = 0;
+    let tsc = __rdtscp(&mut aux);
+    (tsc, aux)
+}
+
+/// Read the time stamp counter without reordering it with surrounding code.
+///
+/// The sequence MFENCE;LFENCE;RDTSC;LFENCE ensures all previous
+/// loads and stores are globally visible before the counter is read
+/// and that later instructions don't start executing before the read.
+/// Use this at the start and end of a measured region, [rdtsc]
+/// when a rough timestamp is enough, and [rdtscp] if the CPU the
+/// reading was taken on should be known as well.
+///
+/// # Safety
+/// * Causes a GP fault if the TSD flag in register CR4 is set and the
+///   CPL is greater than 0.
+pub unsafe fn rdtsc_serialized() -> u64 {
+    _mm_mfence();
+    _mm_lfence();
+    let tsc = _rdtsc() as u64;
+    _mm_lfence();
+    tsc
 }
 
 #[cfg(all(test, feature = "utest"))]
@@ -64,7 +89,44 @@ mod test {
 
         if has_rdtscp {
             unsafe {
-                assert!(rdtscp() > 0, "rdtscp returned 0, unlikely!");
+                assert!(rdtscp().0 > 0, "rdtscp returned 0, unlikely!");
+            }
+        }
+    }
+
+    #[test]
+    fn check_rdtscp_monotonic() {
+        let cpuid = crate::cpuid::CpuId::new();
+        let has_rdtscp = cpuid
+            .get_extended_processor_and_feature_identifiers()
+            .map_or(false, |einfo| einfo.has_rdtscp());
+
+        if has_rdtscp {
+            unsafe {
+                let (t0, aux0) = rdtscp();
+                let (t1, aux1) = rdtscp();
+                // The TSC is only synchronized across cores on most systems,
+                // so only compare readings taken on the same CPU.
+                if aux0 == aux1 {
+                    assert!(t1 > t0, "rdtscp went backwards: {} -> {}", t0, t1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_rdtsc_serialized() {
+        let cpuid = crate::cpuid::CpuId::new();
+        let has_tsc = cpuid
+            .get_feature_info()
+            .map_or(false, |finfo| finfo.has_tsc());
+
+        if has_tsc {
+            unsafe {
+                let t0 = rdtsc_serialized();
+                let t1 = rdtsc_serialized();
+                assert!(t0 > 0, "rdtsc_serialized returned 0, unlikely!");
+                assert!(t1 >= t0);
             }
         }
     }
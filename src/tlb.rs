@@ -2,17 +2,39 @@
 
 use core::arch::asm;
 
+use crate::bits64::paging::VAddr;
+
 /// Invalidate the given address in the TLB using the `invlpg` instruction.
 ///
+/// Needs to be called after modifying or removing the mapping of `addr`
+/// in the currently active page-tables.
+///
+/// # Example
+///
+/// ```no_run
+/// use x86::bits64::paging::{FrameAllocator, Mapper, PAddr, PTFlags, VAddr, PML4, BASE_PAGE_SIZE};
+/// use x86::tlb;
+///
+/// # unsafe fn map_page(pml4: &mut PML4, alloc: &mut impl FrameAllocator) {
+/// let page = VAddr::from(0xdead_b000u64);
+/// let mut mapper = Mapper::new(pml4);
+/// mapper.map_range(page, PAddr::from(0x20_0000u64), BASE_PAGE_SIZE, PTFlags::P | PTFlags::RW, alloc);
+/// tlb::flush(page);
+/// # }
+/// ```
+///
 /// # Safety
 /// This function is unsafe as it causes a general protection fault (GP) if the current privilege
 /// level is not 0.
-pub unsafe fn flush(addr: usize) {
-    asm!("invlpg ({})", in(reg) addr, options(att_syntax, nostack, preserves_flags));
+pub unsafe fn flush(addr: VAddr) {
+    asm!("invlpg ({})", in(reg) addr.as_usize(), options(att_syntax, nostack, preserves_flags));
 }
 
 /// Invalidate the TLB completely by reloading the CR3 register.
 ///
+/// Global pages (PTEs with the G flag set) are not invalidated
+/// by this.
+///
 /// # Safety
 /// This function is unsafe as it causes a general protection fault (GP) if the current privilege
 /// level is not 0.
@@ -21,6 +43,40 @@ pub unsafe fn flush_all() {
     cr3_write(cr3())
 }
 
+/// Invalidate the TLB entries for `addr` tagged with `pcid` using the
+/// `invpcid` instruction (individual-address invalidation).
+///
+/// Unlike [flush] this also removes the entry from other address spaces
+/// than the currently active one.
+///
+/// # Safety
+/// This function is unsafe as it causes a general protection fault (GP) if the current privilege
+/// level is not 0, and an invalid opcode exception if INVPCID is not supported
+/// (CPUID.(EAX=07H, ECX=0H):EBX.INVPCID\[bit 10\]).
+/// `pcid` must be zero unless CR4.PCIDE is set (GP otherwise).
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn flush_pcid(pcid: u16, addr: VAddr) {
+    assert!(pcid <= 0xfff, "PCID is a 12-bit value");
+    let descriptor: [u64; 2] = [pcid as u64, addr.as_u64()];
+    asm!("invpcid ({0}), {1}",
+         in(reg) &descriptor,
+         in(reg) 0u64,
+         options(att_syntax, nostack, preserves_flags, readonly));
+}
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "12-bit")]
+    #[cfg(target_arch = "x86_64")]
+    fn flush_pcid_out_of_range() {
+        // Checked before executing invpcid, so this is fine in user-mode:
+        unsafe { flush_pcid(0x1000, VAddr::zero()) };
+    }
+}
+
 #[cfg(all(test, feature = "vmtest"))]
 mod x86testing {
     use super::*;
@@ -38,7 +94,7 @@ mod x86testing {
         // A better test would be:
         // map page, read page, unmap page, read page, flush, read page -> pfault
         unsafe {
-            flush(0xdeadbeef);
+            flush(VAddr::from(0xdeadbeefu64));
         }
     }
 }
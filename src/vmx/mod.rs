@@ -6,12 +6,12 @@ pub mod vmcs;
 ///
 /// This type closely replicates VMX instruction conventions described in
 /// Intel SDM, Volume 3C, Section 30.2.
-pub type Result<T> = core::result::Result<T, VmFail>;
+pub type Result<T> = core::result::Result<T, VmxError>;
 
 /// Possible outcomes of VMfail pseudo-function used to convey VMX operation errors.
 ///
 /// Definitions of all these pseudo-functions can be found in Intel SDM, Volume 3C, Section 30.2.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum VmFail {
     /// VMCS pointer is valid, but some other error was encountered. Read
     /// VM-instruction error field of VMCS for more details.
@@ -19,3 +19,6 @@ pub enum VmFail {
     /// VMCS pointer is not valid.
     VmFailInvalid,
 }
+
+/// Error reported by a failed VMX instruction.
+pub type VmxError = VmFail;
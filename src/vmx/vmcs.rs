@@ -641,3 +641,40 @@ pub mod ro {
     /// Guest-linear address.
     pub const GUEST_LINEAR_ADDR: u32 = 0x640A;
 }
+
+#[cfg(all(test, feature = "utest"))]
+mod test {
+    use super::*;
+
+    /// Width of a field (bits 14:13 of the encoding), see Intel 3C, Table 24-17.
+    fn width(field: u32) -> u32 {
+        (field >> 13) & 0b11
+    }
+
+    /// Type of a field (bits 11:10 of the encoding).
+    fn field_type(field: u32) -> u32 {
+        (field >> 10) & 0b11
+    }
+
+    #[test]
+    fn field_encodings() {
+        // Natural-width guest and host state
+        assert_eq!((width(guest::RIP), field_type(guest::RIP)), (3, 2));
+        assert_eq!((width(host::RIP), field_type(host::RIP)), (3, 3));
+        assert_eq!(host::RSP, host::RIP - 2);
+        // 32-bit read-only data
+        assert_eq!(
+            (
+                width(ro::VM_INSTRUCTION_ERROR),
+                field_type(ro::VM_INSTRUCTION_ERROR)
+            ),
+            (2, 1)
+        );
+        // 64-bit control
+        assert_eq!(
+            (width(control::EPTP_FULL), field_type(control::EPTP_FULL)),
+            (1, 0)
+        );
+        assert_eq!(control::EPTP_HIGH, control::EPTP_FULL + 1);
+    }
+}